@@ -0,0 +1,497 @@
+// backend-derive/src/lib.rs
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Fields, GenericArgument, Ident, LitStr,
+    PathArguments, Type,
+};
+
+/// Derive the `CRUD` implementation, the DTOs and the schema of a model
+///
+/// The generated code refers to the `backend` crate through `crate::` paths,
+/// so the macro is only meant to be used inside it.
+///
+/// ## Container attributes
+///
+/// * `table = "name"` is the name of the table, required
+/// * `soft_delete` makes `CRUD::delete` soft, the model needs a `deleted_at: Option<String>` field
+/// * `unique = "a, b"` defines a unique index on several fields, e.g. `in, out` of an edge table
+/// * `no_dto` skips the DTOs of a model only written by custom queries, its content type is `()`
+///
+/// ## Field attributes
+///
+/// * `id` marks the record id, it is not part of the schema nor of any DTO
+/// * `create` adds the field to the `{Model}Create` DTO
+/// * `patch` adds the field to the `{Model}Patch` DTO, every other field is protected
+/// * `list` allows `CRUD::list` to filter and sort on the field
+/// * `unique` defines a unique index on the field
+/// * `index` defines an index on the field
+/// * `version` marks the `u64` version maintained by the CRUD layer
+/// * `assert = "expr"` adds an `ASSERT` clause to the field, e.g. `is::email($value)`
+/// * `ty = "type"` overrides the SurrealDB type inferred from the Rust type
+///
+/// A field renamed with `#[serde(rename = "...")]` is stored under its serialized name,
+/// e.g. `in` and `out` of an edge table, and keeps it in the DTOs.
+///
+/// ## Generates
+///
+/// * `{Model}Create`, the content type of `CRUD::create`, unless `no_dto`
+/// * `{Model}Patch`, the merge body of `CRUD::update_merge`, every field optional, if a field
+///   is marked `patch`
+/// * `impl CRUD<Model, ModelCreate>` whose `init_table` defines the table, fields and indexes
+/// * `impl Patchable`, and `impl Versioned` when a field is marked `version`
+#[proc_macro_derive(Model, attributes(model))]
+pub fn derive_model(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Attributes of the model
+#[derive(Default)]
+struct ModelAttrs {
+    table: Option<String>,
+    soft_delete: bool,
+    unique: Vec<String>,
+    no_dto: bool,
+}
+
+/// Attributes of a field
+#[derive(Default)]
+struct FieldAttrs {
+    id: bool,
+    create: bool,
+    patch: bool,
+    list: bool,
+    unique: bool,
+    index: bool,
+    version: bool,
+    assert: Option<String>,
+    ty: Option<String>,
+}
+
+/// A field of the model with its attributes
+struct ModelField {
+    ident: Ident,
+    name: String,
+    ty: Type,
+    docs: Vec<Attribute>,
+    attrs: FieldAttrs,
+}
+
+/// Parse the `#[model(...)]` attributes of the model
+fn model_attrs(attrs: &[Attribute]) -> syn::Result<ModelAttrs> {
+    let mut model = ModelAttrs::default();
+
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("model")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("table") {
+                model.table = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("soft_delete") {
+                model.soft_delete = true;
+            } else if meta.path.is_ident("unique") {
+                model.unique.push(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("no_dto") {
+                model.no_dto = true;
+            } else {
+                return Err(meta.error("expected `table`, `soft_delete`, `unique` or `no_dto`"));
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(model)
+}
+
+/// Parse the `#[model(...)]` attributes of a field
+fn field_attrs(attrs: &[Attribute]) -> syn::Result<FieldAttrs> {
+    let mut field = FieldAttrs::default();
+
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("model")) {
+        attr.parse_nested_meta(|meta| {
+            let flag = |name: &str| meta.path.is_ident(name);
+
+            if flag("id") {
+                field.id = true;
+            } else if flag("create") {
+                field.create = true;
+            } else if flag("patch") {
+                field.patch = true;
+            } else if flag("list") {
+                field.list = true;
+            } else if flag("unique") {
+                field.unique = true;
+            } else if flag("index") {
+                field.index = true;
+            } else if flag("version") {
+                field.version = true;
+            } else if flag("assert") {
+                field.assert = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if flag("ty") {
+                field.ty = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else {
+                return Err(meta.error(
+                    "expected `id`, `create`, `patch`, `list`, `unique`, `index`, `version`, `assert` or `ty`",
+                ));
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(field)
+}
+
+/// Returns the name a field is serialized under, from `#[serde(rename = "...")]`
+fn serde_rename(attrs: &[Attribute]) -> syn::Result<Option<String>> {
+    let mut rename = None;
+
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                rename = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.input.peek(syn::Token![=]) {
+                // Skip the other serde attributes
+                meta.value()?.parse::<syn::Expr>()?;
+            } else if meta.input.peek(syn::token::Paren) {
+                meta.parse_nested_meta(|_| Ok(()))?;
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(rename)
+}
+
+/// Returns the generic argument of a single-argument type like `Option<T>`
+fn inner_type(args: &PathArguments) -> Option<&Type> {
+    match args {
+        PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+/// Infer the SurrealDB type of a Rust type
+fn surreal_type(ty: &Type) -> Option<String> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+
+    let surreal = match segment.ident.to_string().as_str() {
+        "String" | "str" => "string".to_string(),
+        "bool" => "bool".to_string(),
+        "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" | "u64" | "usize" => {
+            "int".to_string()
+        }
+        "f32" | "f64" => "float".to_string(),
+        "Thing" => "record".to_string(),
+        "DateTime" | "Datetime" => "datetime".to_string(),
+        "Option" => format!("option<{}>", surreal_type(inner_type(&segment.arguments)?)?),
+        "Vec" => format!("array<{}>", surreal_type(inner_type(&segment.arguments)?)?),
+        _ => return None,
+    };
+
+    Some(surreal)
+}
+
+/// Build the `DEFINE` statements of the table, the version field excepted
+fn schema(table: &str, fields: &[ModelField], unique: &[String]) -> syn::Result<String> {
+    let mut sql = format!("DEFINE TABLE {table} SCHEMAFULL;");
+
+    for field in fields.iter().filter(|f| !f.attrs.id && !f.attrs.version) {
+        let name = &field.name;
+        let ty = match &field.attrs.ty {
+            Some(ty) => ty.clone(),
+            None => surreal_type(&field.ty).ok_or_else(|| {
+                syn::Error::new_spanned(
+                    &field.ty,
+                    "cannot infer the SurrealDB type, set #[model(ty = \"...\")]",
+                )
+            })?,
+        };
+
+        sql.push_str(&format!("DEFINE FIELD {name} ON {table} TYPE {ty}"));
+        if let Some(assert) = &field.attrs.assert {
+            sql.push_str(&format!(" ASSERT {assert}"));
+        }
+        sql.push(';');
+
+        if field.attrs.unique || field.attrs.index {
+            let unique = if field.attrs.unique { " UNIQUE" } else { "" };
+            sql.push_str(&format!(
+                "DEFINE INDEX {name} ON TABLE {table} COLUMNS {name}{unique};"
+            ));
+        }
+    }
+
+    for columns in unique {
+        let columns: Vec<&str> = columns.split(',').map(str::trim).collect();
+        sql.push_str(&format!(
+            "DEFINE INDEX {} ON TABLE {table} COLUMNS {} UNIQUE;",
+            columns.join("_"),
+            columns.join(", ")
+        ));
+    }
+
+    Ok(sql)
+}
+
+/// Generate the DTOs and trait implementations of the model
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let model = model_attrs(&input.attrs)?;
+    let table = model
+        .table
+        .ok_or_else(|| syn::Error::new_spanned(&input.ident, "missing #[model(table = \"...\")]"))?;
+
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(&input.ident, "Model can only be derived for structs"));
+    };
+    let Fields::Named(named) = &data.fields else {
+        return Err(syn::Error::new_spanned(&input.ident, "Model needs named fields"));
+    };
+
+    let fields = named
+        .named
+        .iter()
+        .map(|field| {
+            // Safety: the fields are named
+            let ident = field.ident.clone().unwrap();
+            let name = serde_rename(&field.attrs)?.unwrap_or_else(|| ident.to_string());
+
+            Ok(ModelField {
+                ident,
+                name,
+                ty: field.ty.clone(),
+                docs: field
+                    .attrs
+                    .iter()
+                    .filter(|attr| attr.path().is_ident("doc"))
+                    .cloned()
+                    .collect(),
+                attrs: field_attrs(&field.attrs)?,
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    if model.soft_delete && !fields.iter().any(|f| f.ident == "deleted_at") {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "soft_delete needs a `deleted_at: Option<String>` field",
+        ));
+    }
+    let dto_field = fields.iter().find(|f| f.attrs.create || f.attrs.patch);
+    if let Some(field) = dto_field.filter(|_| model.no_dto) {
+        return Err(syn::Error::new_spanned(
+            &field.ident,
+            "a no_dto model has no `create` or `patch` field",
+        ));
+    }
+
+    let ident = &input.ident;
+    let vis = &input.vis;
+    let create_ident = format_ident!("{}Create", ident);
+    let patch_ident = format_ident!("{}Patch", ident);
+    let create_doc = format!("{ident} Create Struct, the content of a new `{ident}`");
+    let patch_doc = format!("{ident} Patch Struct, the fields of a `{ident}` that may be changed");
+
+    let create_fields = fields.iter().filter(|f| f.attrs.create).map(|f| {
+        let ModelField { ident, name, ty, docs, .. } = f;
+        quote! {
+            #(#docs)*
+            #[serde(rename = #name)]
+            pub #ident: #ty,
+        }
+    });
+    let patch_fields = fields.iter().filter(|f| f.attrs.patch).map(|f| {
+        let ModelField { ident, name, ty, docs, .. } = f;
+        quote! {
+            #(#docs)*
+            #[serde(rename = #name, skip_serializing_if = "Option::is_none")]
+            pub #ident: ::std::option::Option<#ty>,
+        }
+    });
+
+    let list_fields = fields
+        .iter()
+        .filter(|f| f.attrs.list)
+        .map(|f| f.name.clone());
+    let protected = fields
+        .iter()
+        .filter(|f| !f.attrs.patch)
+        .map(|f| f.name.clone());
+    let soft_delete = model.soft_delete;
+
+    let schema = schema(&table, &fields, &model.unique)?;
+    let version = fields.iter().find(|f| f.attrs.version).map(|f| &f.ident);
+    let version_field = version.map(|_| {
+        quote! { .query(crate::models::model::version_field(#table)) }
+    });
+    let versioned = version.map(|field| {
+        quote! {
+            impl crate::repository::surrealdb_repo::Versioned for #ident {
+                fn version(&self) -> u64 {
+                    self.#field
+                }
+            }
+        }
+    });
+
+    let create_dto = (!model.no_dto).then(|| {
+        quote! {
+            #[doc = #create_doc]
+            #[derive(Debug, Clone, ::serde::Serialize, ::serde::Deserialize)]
+            #vis struct #create_ident {
+                #(#create_fields)*
+            }
+        }
+    });
+    let content = if model.no_dto {
+        quote! { () }
+    } else {
+        quote! { #create_ident }
+    };
+    let patch_dto = fields.iter().any(|f| f.attrs.patch).then(|| {
+        quote! {
+            #[doc = #patch_doc]
+            #[derive(Debug, Clone, Default, ::serde::Serialize, ::serde::Deserialize)]
+            #[serde(deny_unknown_fields)]
+            #vis struct #patch_ident {
+                #(#patch_fields)*
+            }
+        }
+    });
+
+    Ok(quote! {
+        #create_dto
+
+        #patch_dto
+
+        #[::async_trait::async_trait]
+        impl crate::models::model::CRUD<#ident, #content> for #ident {
+            const TABLE: &'static str = #table;
+            const LIST_FIELDS: &'static [&'static str] = &[#(#list_fields),*];
+            const SOFT_DELETE: bool = #soft_delete;
+
+            async fn init_table(
+                db: crate::models::model::DBConnection,
+            ) -> ::std::result::Result<(), crate::prelude::Error> {
+                db.query(#schema) #version_field .await?.check()?;
+
+                Ok(())
+            }
+        }
+
+        impl crate::repository::surrealdb_repo::Patchable for #ident {
+            const PROTECTED_FIELDS: &'static [&'static str] = &[#(#protected),*];
+        }
+
+        #versioned
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(input: DeriveInput) -> Vec<ModelField> {
+        let Data::Struct(data) = input.data else {
+            unreachable!()
+        };
+        data.fields
+            .into_iter()
+            .map(|field| ModelField {
+                name: serde_rename(&field.attrs)
+                    .unwrap()
+                    .unwrap_or_else(|| field.ident.as_ref().unwrap().to_string()),
+                ident: field.ident.unwrap(),
+                attrs: field_attrs(&field.attrs).unwrap(),
+                ty: field.ty,
+                docs: Vec::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn infers_surreal_types() {
+        let ty = |ty: &str| surreal_type(&syn::parse_str(ty).unwrap());
+
+        assert_eq!(ty("String").as_deref(), Some("string"));
+        assert_eq!(ty("u64").as_deref(), Some("int"));
+        assert_eq!(ty("Option<String>").as_deref(), Some("option<string>"));
+        assert_eq!(ty("Vec<Option<f64>>").as_deref(), Some("array<option<float>>"));
+        assert_eq!(ty("HashMap<String, String>"), None);
+    }
+
+    #[test]
+    fn generates_schema_from_attributes() {
+        let input: DeriveInput = syn::parse_quote! {
+            struct User {
+                #[model(id)]
+                id: Thing,
+                #[model(create, unique, assert = "is::email($value)")]
+                email: String,
+                #[model(create, index)]
+                name: String,
+                #[model(version)]
+                version: u64,
+                #[model(ty = "option<datetime>")]
+                seen_at: Option<String>,
+            }
+        };
+
+        assert_eq!(
+            schema("users", &fields(input), &[]).unwrap(),
+            "DEFINE TABLE users SCHEMAFULL;\
+            DEFINE FIELD email ON users TYPE string ASSERT is::email($value);\
+            DEFINE INDEX email ON TABLE users COLUMNS email UNIQUE;\
+            DEFINE FIELD name ON users TYPE string;\
+            DEFINE INDEX name ON TABLE users COLUMNS name;\
+            DEFINE FIELD seen_at ON users TYPE option<datetime>;"
+        );
+    }
+
+    #[test]
+    fn uses_serialized_names_and_composite_indexes() {
+        let input: DeriveInput = syn::parse_quote! {
+            struct Contact {
+                #[model(id)]
+                id: Thing,
+                #[serde(rename = "in", default)]
+                #[model(create)]
+                from: Thing,
+                #[serde(rename = "out")]
+                #[model(create)]
+                to: Thing,
+            }
+        };
+
+        assert_eq!(
+            schema("contact", &fields(input), &["in, out".to_string()]).unwrap(),
+            "DEFINE TABLE contact SCHEMAFULL;\
+            DEFINE FIELD in ON contact TYPE record;\
+            DEFINE FIELD out ON contact TYPE record;\
+            DEFINE INDEX in_out ON TABLE contact COLUMNS in, out UNIQUE;"
+        );
+    }
+
+    #[test]
+    fn no_dto_models_have_no_dto_fields() {
+        let input: DeriveInput = syn::parse_quote! {
+            #[model(table = "presence", no_dto)]
+            struct Presence {
+                #[model(id)]
+                id: Thing,
+                #[model(create, unique)]
+                user: Thing,
+            }
+        };
+
+        assert!(expand(input).is_err());
+    }
+}
@@ -0,0 +1,227 @@
+// api/auth.rs
+use actix_identity::Identity;
+use actix_web::{
+    post,
+    web::{self, Json},
+    HttpMessage, HttpRequest,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::response::{ApiResult, Response};
+use crate::{
+    models::{
+        model::{ConnectionData, CRUD},
+        user_model::{User, UserCreate, UserProfile},
+    },
+    prelude::Error,
+};
+
+/// Minimum length of a password
+const PASSWORD_MIN_LENGTH: usize = 8;
+
+/// Register Request Struct
+///
+/// ## Fields
+///
+/// * `Name` is the user's name
+/// * `Email` is the user's email
+/// * `Password` is the user's plaintext password
+/// * `Avatar` is the user's avatar, empty if omitted
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RegisterRequest {
+    pub name: String,
+    pub email: String,
+    pub password: String,
+    #[serde(default)]
+    pub avatar: String,
+}
+
+/// Login Request Struct
+///
+/// ## Fields
+///
+/// * `Email` is the user's email
+/// * `Password` is the user's plaintext password
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LoginRequest {
+    pub email: String,
+    pub password: String,
+}
+
+/// Check that an email looks like `local@domain.tld`
+///
+/// SurrealDB asserts `is::email` on insert, this only gives a readable error earlier.
+fn validate_email(email: &str) -> Result<(), Error> {
+    let valid = match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && domain.contains('.')
+                && !email.chars().any(char::is_whitespace)
+        }
+        None => false,
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::Validation("email is not valid".to_string()))
+    }
+}
+
+/// Check that a password has a minimum length and mixes lowercase, uppercase and digits
+fn validate_password(password: &str) -> Result<(), Error> {
+    if password.chars().count() < PASSWORD_MIN_LENGTH {
+        return Err(Error::Validation(format!(
+            "password must be at least {PASSWORD_MIN_LENGTH} characters long"
+        )));
+    }
+
+    let has_lower = password.chars().any(char::is_lowercase);
+    let has_upper = password.chars().any(char::is_uppercase);
+    let has_digit = password.chars().any(|c| c.is_ascii_digit());
+    if !(has_lower && has_upper && has_digit) {
+        return Err(Error::Validation(
+            "password must contain a lowercase letter, an uppercase letter and a digit"
+                .to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Register a new user
+///
+/// ## Returns
+///
+/// * `201 Created` with the user's profile
+/// * `400 Bad Request` if the email or password is invalid
+/// * `409 Conflict` if the email is already in use
+#[post("/register")]
+pub async fn register(db: ConnectionData, body: Json<RegisterRequest>) -> ApiResult<UserProfile> {
+    let body = body.into_inner();
+    let email = body.email.trim().to_lowercase();
+
+    if body.name.trim().is_empty() {
+        return Err(Error::Validation("name must not be empty".to_string()));
+    }
+    validate_email(&email)?;
+    validate_password(&body.password)?;
+
+    let data = UserCreate {
+        peer_id: Uuid::new_v4().to_string(),
+        name: body.name.trim().to_string(),
+        avatar: body.avatar,
+        email,
+        password_hash: User::hash_password(&body.password)?,
+        creation_date: chrono::Utc::now().to_rfc3339(),
+        is_visible: true,
+        is_inactive: false,
+    };
+
+    let user = User::create(db, User::TABLE.to_string(), data)
+        .await
+        .map_err(Error::map_index_conflict)?;
+
+    Ok(Response::new_success(
+        201,
+        "User registered".to_string(),
+        user.into(),
+    ))
+}
+
+/// Log a user in and attach their id to the session
+///
+/// ## Returns
+///
+/// * `200 OK` with the user's profile
+/// * `401 Unauthorized` if the email or password is wrong
+/// * `403 Forbidden` if the user is inactive
+#[post("/login")]
+pub async fn login(
+    req: HttpRequest,
+    db: ConnectionData,
+    body: Json<LoginRequest>,
+) -> ApiResult<UserProfile> {
+    let body = body.into_inner();
+    let email = body.email.trim().to_lowercase();
+
+    let Some(user) = User::get_from_email(db, &email).await? else {
+        // As long as a wrong password
+        User::verify_dummy_password(&body.password);
+        return Err(Error::InvalidCredentials);
+    };
+
+    user.verify_password(body.password)
+        .map_err(|_| Error::InvalidCredentials)?;
+
+    if user.is_inactive {
+        return Err(Error::InactiveUser);
+    }
+
+    Identity::login(&req.extensions(), user.id.to_string())
+        .map_err(|e| Error::Session(e.to_string()))?;
+
+    Ok(Response::new_success(
+        200,
+        "Logged in".to_string(),
+        user.into(),
+    ))
+}
+
+/// Restore a soft-deleted account before it is purged
+///
+/// The user logs in again afterwards
+///
+/// ## Returns
+///
+/// * `200 OK` with the restored user's profile
+/// * `401 Unauthorized` if the email or password is wrong, or the account is not deleted
+#[post("/restore")]
+pub async fn restore(db: ConnectionData, body: Json<LoginRequest>) -> ApiResult<UserProfile> {
+    let body = body.into_inner();
+    let email = body.email.trim().to_lowercase();
+
+    let Some(user) = User::get_deleted_from_email(db.clone(), &email).await? else {
+        User::verify_dummy_password(&body.password);
+        return Err(Error::InvalidCredentials);
+    };
+
+    user.verify_password(body.password)
+        .map_err(|_| Error::InvalidCredentials)?;
+
+    let user = User::restore(db, user.id).await?;
+
+    Ok(Response::new_success(
+        200,
+        "Account restored".to_string(),
+        user.into(),
+    ))
+}
+
+/// Log the current user out and purge their session
+///
+/// ## Returns
+///
+/// * `200 OK` once the session is purged
+/// * `401 Unauthorized` if nobody is logged in
+#[post("/logout")]
+pub async fn logout(identity: Identity) -> ApiResult<()> {
+    identity.logout();
+
+    Ok(Response::new_success(200, "Logged out".to_string(), ()))
+}
+
+/// Register the auth routes
+///
+/// ## Arguments
+///
+/// * `cfg` - The service config of the `/auth` scope
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(register)
+        .service(login)
+        .service(logout)
+        .service(restore);
+}
@@ -0,0 +1,319 @@
+// api/contacts.rs
+use std::collections::HashMap;
+
+use actix_web::{
+    delete, get, post,
+    web::{self, Json, Path, Query},
+};
+use serde::Deserialize;
+use surrealdb::sql::Thing;
+
+use super::{
+    crud,
+    extractor::AuthUser,
+    response::{ApiResult, Response},
+};
+use crate::{
+    models::{
+        contact_model::{Contact, ContactEntry, ContactKind},
+        model::ConnectionData,
+        query::ListQuery,
+        user_model::{PublicProfile, User},
+    },
+    prelude::Error,
+};
+
+/// Body of the requests targeting a user
+///
+/// ## Fields
+///
+/// * `peer_id` is the peer id of the user
+#[derive(Debug, Deserialize)]
+pub struct PeerRequest {
+    pub peer_id: String,
+}
+
+/// Get the id of the user targeted by the current user
+///
+/// ## Arguments
+///
+/// * `db` - The database connection
+/// * `user` - The current user
+/// * `peer_id` - The peer id of the target
+///
+/// ## Errors
+///
+/// * `Error::Validation` - The target is the current user
+/// * `Error::NotFound` - There is no such user
+async fn target(db: &ConnectionData, user: &User, peer_id: &str) -> Result<Thing, Error> {
+    if peer_id == user.peer_id {
+        return Err(Error::Validation(
+            "you cannot be your own contact".to_string(),
+        ));
+    }
+
+    User::get_from_peer_id(db.clone(), peer_id)
+        .await?
+        .map(|target| target.id)
+        .ok_or_else(|| Error::NotFound(peer_id.to_string()))
+}
+
+/// Build the entry of a contact edge, as seen by the current user
+async fn entry(db: &ConnectionData, contact: Contact, user: &User) -> Result<ContactEntry, Error> {
+    let other = if contact.from == user.id {
+        &contact.to
+    } else {
+        &contact.from
+    };
+    let other = User::get_from_id(db.clone(), other.clone())
+        .await?
+        .ok_or(Error::NoRecord)?;
+
+    Ok(ContactEntry {
+        user: PublicProfile::from(other),
+        status: contact.status,
+        since: contact.updated_at,
+    })
+}
+
+/// List the contacts of the current user, most recently changed first
+///
+/// ## Arguments
+///
+/// * `params` - `kind` is `accepted` (default), `incoming`, `outgoing` or `blocked`,
+///   with `limit`, `offset` and `cursor` as in `ListQuery::from_params`
+///
+/// ## Returns
+///
+/// * `200 OK` with the page and its pagination metadata
+/// * `400 Bad Request` if the query is malformed or has filters or a sort order
+/// * `401 Unauthorized` if nobody is logged in
+#[get("")]
+pub async fn list_contacts(
+    user: AuthUser,
+    db: ConnectionData,
+    params: Query<HashMap<String, String>>,
+) -> ApiResult<Vec<ContactEntry>> {
+    let mut params = params.into_inner();
+    let kind = match params.remove("kind") {
+        Some(kind) => serde_json::from_value(kind.into()).map_err(|_| {
+            Error::Validation(
+                "kind must be accepted, incoming, outgoing or blocked".to_string(),
+            )
+        })?,
+        None => ContactKind::Accepted,
+    };
+    let query = ListQuery::from_params(&params)?;
+    let page = Contact::list(&db, &user.id, kind, query).await?;
+
+    crud::page_response(page)
+}
+
+/// Send a contact request, accepting it right away if the user already sent one
+///
+/// ## Arguments
+///
+/// * `body` - The peer id of the requested user
+///
+/// ## Returns
+///
+/// * `201 Created` with the pending request, or the contact if it was accepted
+/// * `400 Bad Request` if the users are the same
+/// * `401 Unauthorized` if nobody is logged in
+/// * `404 Not Found` if the user does not exist or is blocked
+/// * `409 Conflict` if the request was already sent or the users are already contacts
+#[post("/requests")]
+pub async fn send_request(
+    user: AuthUser,
+    db: ConnectionData,
+    body: Json<PeerRequest>,
+) -> ApiResult<ContactEntry> {
+    let to = target(&db, &user, &body.peer_id).await?;
+    let contact = Contact::request(&db, &user.id, &to).await?;
+    let entry = entry(&db, contact, &user).await?;
+
+    Ok(Response::new_success(
+        201,
+        "Contact request sent".to_string(),
+        entry,
+    ))
+}
+
+/// Accept a contact request
+///
+/// ## Arguments
+///
+/// * `peer_id` - The peer id of the user who sent the request
+///
+/// ## Returns
+///
+/// * `200 OK` with the contact
+/// * `401 Unauthorized` if nobody is logged in
+/// * `404 Not Found` if there is no pending request from the user
+#[post("/requests/{peer_id}/accept")]
+pub async fn accept_request(
+    user: AuthUser,
+    db: ConnectionData,
+    peer_id: Path<String>,
+) -> ApiResult<ContactEntry> {
+    let from = target(&db, &user, &peer_id).await?;
+    let contact = Contact::accept(&db, &user.id, &from).await?;
+    let entry = entry(&db, contact, &user).await?;
+
+    Ok(Response::new_success(
+        200,
+        "Contact request accepted".to_string(),
+        entry,
+    ))
+}
+
+/// Decline a contact request
+///
+/// ## Arguments
+///
+/// * `peer_id` - The peer id of the user who sent the request
+///
+/// ## Returns
+///
+/// * `200 OK` once the request is deleted
+/// * `401 Unauthorized` if nobody is logged in
+/// * `404 Not Found` if there is no pending request from the user
+#[post("/requests/{peer_id}/decline")]
+pub async fn decline_request(
+    user: AuthUser,
+    db: ConnectionData,
+    peer_id: Path<String>,
+) -> ApiResult<()> {
+    let from = target(&db, &user, &peer_id).await?;
+    Contact::decline(&db, &user.id, &from).await?;
+
+    Ok(Response::new_success(
+        200,
+        "Contact request declined".to_string(),
+        (),
+    ))
+}
+
+/// Cancel a sent contact request
+///
+/// ## Arguments
+///
+/// * `peer_id` - The peer id of the requested user
+///
+/// ## Returns
+///
+/// * `200 OK` once the request is deleted
+/// * `401 Unauthorized` if nobody is logged in
+/// * `404 Not Found` if there is no pending request to the user
+#[delete("/requests/{peer_id}")]
+pub async fn cancel_request(
+    user: AuthUser,
+    db: ConnectionData,
+    peer_id: Path<String>,
+) -> ApiResult<()> {
+    let to = target(&db, &user, &peer_id).await?;
+    Contact::cancel(&db, &user.id, &to).await?;
+
+    Ok(Response::new_success(
+        200,
+        "Contact request cancelled".to_string(),
+        (),
+    ))
+}
+
+/// Block a user, removing any contact or request between the users
+///
+/// Blocked users cannot find each other, send each other requests or see each other's presence
+///
+/// ## Arguments
+///
+/// * `body` - The peer id of the user to block
+///
+/// ## Returns
+///
+/// * `201 Created` with the block
+/// * `400 Bad Request` if the users are the same
+/// * `401 Unauthorized` if nobody is logged in
+/// * `404 Not Found` if the user does not exist
+#[post("/blocks")]
+pub async fn block(
+    user: AuthUser,
+    db: ConnectionData,
+    body: Json<PeerRequest>,
+) -> ApiResult<ContactEntry> {
+    let blocked = target(&db, &user, &body.peer_id).await?;
+    let contact = Contact::block(&db, &user.id, &blocked).await?;
+    let entry = entry(&db, contact, &user).await?;
+
+    Ok(Response::new_success(
+        201,
+        "User blocked".to_string(),
+        entry,
+    ))
+}
+
+/// Unblock a user
+///
+/// ## Arguments
+///
+/// * `peer_id` - The peer id of the blocked user
+///
+/// ## Returns
+///
+/// * `200 OK` once the block is lifted
+/// * `401 Unauthorized` if nobody is logged in
+/// * `404 Not Found` if the user is not blocked
+#[delete("/blocks/{peer_id}")]
+pub async fn unblock(
+    user: AuthUser,
+    db: ConnectionData,
+    peer_id: Path<String>,
+) -> ApiResult<()> {
+    let blocked = target(&db, &user, &peer_id).await?;
+    Contact::unblock(&db, &user.id, &blocked).await?;
+
+    Ok(Response::new_success(200, "User unblocked".to_string(), ()))
+}
+
+/// Remove a contact
+///
+/// ## Arguments
+///
+/// * `peer_id` - The peer id of the contact
+///
+/// ## Returns
+///
+/// * `200 OK` once the contact is removed
+/// * `401 Unauthorized` if nobody is logged in
+/// * `404 Not Found` if the users are not contacts
+#[delete("/{peer_id}")]
+pub async fn remove_contact(
+    user: AuthUser,
+    db: ConnectionData,
+    peer_id: Path<String>,
+) -> ApiResult<()> {
+    let contact = target(&db, &user, &peer_id).await?;
+    Contact::unfriend(&db, &user.id, &contact).await?;
+
+    Ok(Response::new_success(
+        200,
+        "Contact removed".to_string(),
+        (),
+    ))
+}
+
+/// Register the contact routes
+///
+/// ## Arguments
+///
+/// * `cfg` - The service config of the `/contacts` scope
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_contacts)
+        .service(send_request)
+        .service(accept_request)
+        .service(decline_request)
+        .service(cancel_request)
+        .service(block)
+        .service(unblock)
+        .service(remove_contact);
+}
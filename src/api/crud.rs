@@ -0,0 +1,105 @@
+// api/crud.rs
+use actix_web::{
+    http::header::IfMatch,
+    web::{Header, Path},
+};
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+use super::{
+    extractor::AuthUser,
+    response::{ApiResult, Pagination, Response, TaggedResult},
+};
+use crate::{
+    models::{
+        model::{ConnectionData, CRUD},
+        query::Page,
+    },
+    prelude::Error,
+    repository::surrealdb_repo::Versioned,
+};
+
+/// Get the version a conditional update expects from the `If-Match` header
+///
+/// ## Arguments
+///
+/// * `header` - The `If-Match` header, if the request has one
+///
+/// ## Returns
+///
+/// * `Result<Option<u64>, Error>` - The expected version, `None` without header or with `*`
+///
+/// ## Errors
+///
+/// * `Error::Validation` - The header does not carry an `ETag` returned by the API
+pub fn if_match(header: Option<Header<IfMatch>>) -> Result<Option<u64>, Error> {
+    match header.map(Header::into_inner) {
+        None | Some(IfMatch::Any) => Ok(None),
+        Some(IfMatch::Items(tags)) => match tags.as_slice() {
+            [tag] if !tag.weak => tag.tag().parse().map(Some).map_err(|_| {
+                Error::Validation("If-Match must carry an ETag returned by the API".to_string())
+            }),
+            _ => Err(Error::Validation(
+                "If-Match must carry exactly one strong ETag".to_string(),
+            )),
+        },
+    }
+}
+
+/// Get an entry of any versioned CRUD model, with its version as `ETag`
+///
+/// ## Generic Types
+///
+/// * `M` - The model implementing `CRUD`
+/// * `D` - The data type
+/// * `C` - The content type
+/// * `O` - The type returned to the client, e.g. a public view of `D`
+///
+/// ## Returns
+///
+/// * `200 OK` with the entry and its `ETag`
+/// * `401 Unauthorized` if nobody is logged in
+/// * `404 Not Found` if the entry does not exist
+pub async fn get<M, D, C, O>(
+    _user: AuthUser,
+    db: ConnectionData,
+    id: Path<String>,
+) -> TaggedResult<O>
+where
+    M: CRUD<D, C>,
+    D: Versioned + Serialize + Send + Sync + for<'de> Deserialize<'de> + 'static,
+    C: Serialize + Send + Sync + for<'de> Deserialize<'de> + 'static,
+    O: From<D> + Serialize,
+{
+    let id = Thing::from((M::TABLE, id.as_str()));
+    let entry = M::get_from_id(db, id.clone())
+        .await?
+        .ok_or_else(|| Error::NotFound(id.to_string()))?;
+    let version = entry.version();
+
+    Ok(Response::new_success(200, "OK".to_string(), O::from(entry)).with_etag(version))
+}
+
+/// Build the response of a page, with its pagination metadata
+///
+/// ## Arguments
+///
+/// * `page` - The page
+///
+/// ## Returns
+///
+/// * `ApiResult<Vec<O>>` - `200 OK` with the items of the page
+pub fn page_response<D, O>(page: Page<D>) -> ApiResult<Vec<O>>
+where
+    O: From<D> + Serialize,
+{
+    let pagination = Pagination {
+        total: page.total,
+        limit: page.limit,
+        offset: Some(page.offset),
+        next_cursor: page.next_cursor,
+    };
+    let items = page.items.into_iter().map(O::from).collect();
+
+    Ok(Response::new_success(200, "OK".to_string(), items).with_pagination(pagination))
+}
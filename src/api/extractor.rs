@@ -0,0 +1,78 @@
+// api/extractor.rs
+use std::ops::Deref;
+
+use actix_identity::Identity;
+use actix_web::{dev::Payload, FromRequest, HttpRequest};
+use futures::future::LocalBoxFuture;
+use log::error;
+use surrealdb::sql::thing;
+
+use crate::{
+    models::{
+        model::{ConnectionData, CRUD},
+        user_model::User,
+    },
+    prelude::Error,
+};
+
+/// Authenticated User Extractor
+///
+/// Resolves the `User` attached to the request's `Identity`
+///
+/// ## Fields
+///
+/// * `0` is the loaded user
+///
+/// ## Errors
+///
+/// * `401 Unauthorized` if there is no identity or the user does not exist
+/// * `403 Forbidden` if the user is inactive
+#[derive(Debug, Clone)]
+pub struct AuthUser(pub User);
+
+impl AuthUser {
+    /// Consume the extractor and return the user
+    ///
+    /// ## Returns
+    ///
+    /// * `User` - The authenticated user
+    pub fn into_inner(self) -> User {
+        self.0
+    }
+}
+
+impl Deref for AuthUser {
+    type Target = User;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromRequest for AuthUser {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let identity = Identity::extract(req);
+        let db = req.app_data::<ConnectionData>().cloned();
+
+        Box::pin(async move {
+            let identity = identity.await.map_err(|_| Error::CtxFail)?;
+            let id = identity.id().map_err(|_| Error::CtxFail)?;
+            let id = thing(&id).map_err(|_| Error::CtxFail)?;
+
+            let Some(db) = db else {
+                error!("ConnectionData is missing from the app data");
+                return Err(Error::CtxFail);
+            };
+
+            let user = User::get_from_id(db, id).await?.ok_or(Error::CtxFail)?;
+            if user.is_inactive {
+                return Err(Error::InactiveUser);
+            }
+
+            Ok(AuthUser(user))
+        })
+    }
+}
@@ -0,0 +1,18 @@
+// api/health.rs
+use actix_web::get;
+
+use super::response::{ApiResult, Response};
+
+/// Health check
+///
+/// ## Returns
+///
+/// * `200 OK` with the crate version as payload
+#[get("/health")]
+pub async fn health() -> ApiResult<String> {
+    Ok(Response::new_success(
+        200,
+        "OK".to_string(),
+        env!("CARGO_PKG_VERSION").to_string(),
+    ))
+}
@@ -0,0 +1,221 @@
+// api/live.rs
+use std::time::Duration;
+
+use actix_web::{web::Data, HttpRequest};
+use actix_web_lab::sse::{self, Sse};
+use futures::Stream;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::broadcast::error::RecvError;
+
+use super::extractor::AuthUser;
+use crate::{
+    models::{
+        contact_model::Contact,
+        model::{ConnectionData, Viewer, Watchable, CRUD},
+    },
+    prelude::Error,
+    repository::live::{record_id, Change, ChangeAction, Cursor, LiveHub},
+};
+
+/// Interval of the comments keeping idle connections open
+pub const HEARTBEAT: Duration = Duration::from_secs(15);
+/// Delay before a client reconnects after losing the connection
+pub const RETRY: Duration = Duration::from_secs(3);
+
+/// A change streamed to clients, sent as the data of the SSE event named after the action
+///
+/// An `update` of a record the client does not know, e.g. a restored one, should be
+/// treated as a `create`. A soft-deleted record is sent as a `delete`.
+///
+/// ## Variants
+///
+/// * `Create` carries the created record
+/// * `Update` carries the updated record
+/// * `Delete` carries the id of the deleted record
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum ChangeEvent<O> {
+    Create { data: O },
+    Update { data: O },
+    Delete { id: String },
+}
+
+impl<O> ChangeEvent<O> {
+    /// Returns the name of the SSE event
+    fn name(&self) -> &'static str {
+        match self {
+            ChangeEvent::Create { .. } => "create",
+            ChangeEvent::Update { .. } => "update",
+            ChangeEvent::Delete { .. } => "delete",
+        }
+    }
+}
+
+/// Deserialize a record of a change
+fn parse<M, D, C>(data: &Value) -> Option<D>
+where
+    M: CRUD<D, C>,
+    D: Serialize + Send + Sync + for<'de> Deserialize<'de> + 'static,
+    C: Serialize + Send + Sync + for<'de> Deserialize<'de> + 'static,
+{
+    match serde_json::from_value(data.clone()) {
+        Ok(record) => Some(record),
+        Err(e) => {
+            warn!("Skipping a change of {} that does not deserialize: {e}", M::TABLE);
+            None
+        }
+    }
+}
+
+/// Convert a change of the table to the event the viewer may see
+///
+/// A record the viewer may no longer see, e.g. a user who became invisible, is sent as a
+/// `delete`. Deletions are only sent if the viewer could see the last known record.
+///
+/// ## Returns
+///
+/// * `Option<ChangeEvent<O>>` - The event, `None` if the viewer may not see it
+fn change_event<M, D, C, O>(viewer: &Viewer, change: &Change) -> Option<ChangeEvent<O>>
+where
+    M: CRUD<D, C> + Watchable<D>,
+    D: Serialize + Send + Sync + for<'de> Deserialize<'de> + 'static,
+    C: Serialize + Send + Sync + for<'de> Deserialize<'de> + 'static,
+    O: From<D>,
+{
+    let watched = change
+        .before
+        .as_ref()
+        .and_then(parse::<M, D, C>)
+        .is_some_and(|before| M::can_watch(viewer, &before));
+    let record = match change.action {
+        ChangeAction::Delete => None,
+        _ => Some(parse::<M, D, C>(&change.data)?),
+    };
+    let record = match record {
+        Some(record) if M::can_watch(viewer, &record) => record,
+        // Deleted, or hidden from the viewer since
+        _ => {
+            return record_id(&change.data)
+                .filter(|_| watched)
+                .map(|id| ChangeEvent::Delete { id })
+        }
+    };
+
+    let deleted = change
+        .data
+        .get("deleted_at")
+        .is_some_and(|deleted_at| !deleted_at.is_null());
+    if M::SOFT_DELETE && deleted {
+        return record_id(&change.data).map(|id| ChangeEvent::Delete { id });
+    }
+
+    Some(match change.action {
+        ChangeAction::Create => ChangeEvent::Create {
+            data: O::from(record),
+        },
+        _ => ChangeEvent::Update {
+            data: O::from(record),
+        },
+    })
+}
+
+/// Build an SSE event
+///
+/// ## Arguments
+///
+/// * `name` - The name of the event
+/// * `id` - The id of the event, sent back as `Last-Event-ID` on reconnection
+/// * `data` - The data of the event, serialized as JSON
+pub fn sse_event(
+    name: &'static str,
+    id: Option<String>,
+    data: impl Serialize,
+) -> Result<sse::Event, Error> {
+    let data = sse::Data::new_json(data)
+        .map_err(|e| Error::Live(format!("cannot serialize the {name} event, {e}")))?
+        .event(name);
+
+    Ok(sse::Event::Data(match id {
+        Some(id) => data.id(id),
+        None => data,
+    }))
+}
+
+/// Stream the changes of any watchable CRUD model over Server-Sent Events
+///
+/// Events are `create`, `update` and `delete` with a `ChangeEvent` as data, and:
+///
+/// * `ready` once the client is up to date, after the changes missed since `Last-Event-ID`
+/// * `reset` when missed changes are lost, the client should fetch the list again
+///
+/// Blocks between users are read when the stream opens, see `Viewer`.
+///
+/// ## Generic Types
+///
+/// * `M` - The model implementing `CRUD` and `Watchable`
+/// * `D` - The data type
+/// * `C` - The content type
+/// * `O` - The type returned to the client, e.g. a public view of `D`
+///
+/// ## Returns
+///
+/// * `200 OK` with the `text/event-stream`
+/// * `401 Unauthorized` if nobody is logged in
+pub async fn changes<M, D, C, O>(
+    user: AuthUser,
+    db: ConnectionData,
+    hub: Data<LiveHub>,
+    req: HttpRequest,
+) -> Result<Sse<impl Stream<Item = Result<sse::Event, Error>>>, Error>
+where
+    M: CRUD<D, C> + Watchable<D>,
+    D: Serialize + Send + Sync + for<'de> Deserialize<'de> + 'static,
+    C: Serialize + Send + Sync + for<'de> Deserialize<'de> + 'static,
+    O: From<D> + Serialize + 'static,
+{
+    let last_event_id = req
+        .headers()
+        .get("last-event-id")
+        .and_then(|id| id.to_str().ok());
+    let viewer = Viewer {
+        blocked: Contact::blocked_with(&db, &user.id).await?,
+        user: user.into_inner(),
+    };
+    let mut subscription = hub.subscribe(M::TABLE, last_event_id).await?;
+
+    let stream = async_stream::stream! {
+        let cursor = subscription.cursor.clone();
+        let event_id = |position: u64| Cursor { position, ..cursor.clone() }.to_string();
+
+        match subscription.replay.take() {
+            Some(changes) => {
+                for change in changes {
+                    if let Some(event) = change_event::<M, D, C, O>(&viewer, &change) {
+                        yield sse_event(event.name(), Some(event_id(change.position)), event);
+                    }
+                }
+                yield sse_event("ready", Some(cursor.to_string()), json!({}));
+            }
+            None => yield sse_event("reset", Some(cursor.to_string()), json!({})),
+        }
+
+        loop {
+            match subscription.receiver.recv().await {
+                Ok(change) => {
+                    if let Some(event) = change_event::<M, D, C, O>(&viewer, &change) {
+                        yield sse_event(event.name(), Some(event_id(change.position)), event);
+                    }
+                }
+                // The client is too slow and changes were dropped
+                Err(RecvError::Lagged(_)) => yield sse_event("reset", None, json!({})),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    };
+
+    Ok(Sse::from_stream(stream)
+        .with_keep_alive(HEARTBEAT)
+        .with_retry_duration(RETRY))
+}
@@ -1 +1,34 @@
 // api/mod.rs
+pub mod auth;
+pub mod contacts;
+pub mod crud;
+pub mod extractor;
+pub mod health;
+pub mod live;
+pub mod notifications;
+pub mod presence;
+pub mod response;
+pub mod signaling;
+pub mod timetable;
+pub mod untis;
+pub mod users;
+
+use actix_web::web;
+
+/// Register every API route
+///
+/// ## Arguments
+///
+/// * `cfg` - The service config of the app
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(health::health)
+        .service(signaling::signaling)
+        .service(timetable::calendar_feed)
+        .service(web::scope("/auth").configure(auth::config))
+        .service(web::scope("/contacts").configure(contacts::config))
+        .service(web::scope("/notifications").configure(notifications::config))
+        .service(web::scope("/presence").configure(presence::config))
+        .service(web::scope("/timetable").configure(timetable::config))
+        .service(web::scope("/untis").configure(untis::config))
+        .service(web::scope("/users").configure(users::config));
+}
@@ -0,0 +1,115 @@
+// api/notifications.rs
+use std::collections::HashMap;
+
+use actix_web::{
+    get, post,
+    web::{self, Path, Query},
+};
+
+use super::{
+    crud,
+    extractor::AuthUser,
+    live,
+    response::{ApiResult, Response},
+};
+use crate::{
+    models::{
+        model::ConnectionData,
+        notification_model::{Notification, NotificationCreate, NotificationEntry},
+        query::ListQuery,
+    },
+    prelude::Error,
+};
+
+/// List the notifications of the current user, newest first
+///
+/// ## Arguments
+///
+/// * `params` - `unread=true` lists the unread notifications only,
+///   with `limit`, `offset` and `cursor` as in `ListQuery::from_params`
+///
+/// ## Returns
+///
+/// * `200 OK` with the page and its pagination metadata
+/// * `400 Bad Request` if the query is malformed or has filters or a sort order
+/// * `401 Unauthorized` if nobody is logged in
+#[get("")]
+pub async fn list_notifications(
+    user: AuthUser,
+    db: ConnectionData,
+    params: Query<HashMap<String, String>>,
+) -> ApiResult<Vec<NotificationEntry>> {
+    let mut params = params.into_inner();
+    let unread = match params.remove("unread").as_deref() {
+        Some("true") => true,
+        Some("false") | None => false,
+        Some(_) => {
+            return Err(Error::Validation("unread must be true or false".to_string()));
+        }
+    };
+    let query = ListQuery::from_params(&params)?;
+    let page = Notification::list(&db, &user.id, unread, query).await?;
+
+    crud::page_response(page)
+}
+
+/// Mark every notification of the current user as read
+///
+/// ## Returns
+///
+/// * `200 OK` with the number of notifications that were unread
+/// * `401 Unauthorized` if nobody is logged in
+#[post("/read")]
+pub async fn read_all(user: AuthUser, db: ConnectionData) -> ApiResult<usize> {
+    let read = Notification::mark_all_read(&db, &user.id).await?;
+
+    Ok(Response::new_success(
+        200,
+        "Notifications read".to_string(),
+        read,
+    ))
+}
+
+/// Mark a notification of the current user as read
+///
+/// ## Arguments
+///
+/// * `id` - The id of the notification
+///
+/// ## Returns
+///
+/// * `200 OK` with the notification
+/// * `401 Unauthorized` if nobody is logged in
+/// * `404 Not Found` if the user has no such notification
+#[post("/{id}/read")]
+pub async fn read_notification(
+    user: AuthUser,
+    db: ConnectionData,
+    id: Path<String>,
+) -> ApiResult<NotificationEntry> {
+    let id = Notification::id_of(&id);
+    let notification = Notification::mark_read(&db, &user.id, &id).await?;
+
+    Ok(Response::new_success(
+        200,
+        "Notification read".to_string(),
+        notification,
+    ))
+}
+
+/// Register the notification routes
+///
+/// ## Arguments
+///
+/// * `cfg` - The service config of the `/notifications` scope
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_notifications)
+        .route(
+            "/live",
+            web::get().to(
+                live::changes::<Notification, Notification, NotificationCreate, NotificationEntry>,
+            ),
+        )
+        .service(read_all)
+        .service(read_notification);
+}
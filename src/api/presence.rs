@@ -0,0 +1,239 @@
+// api/presence.rs
+use std::collections::{HashMap, HashSet};
+
+use actix_web::{
+    get,
+    web::{self, Data, Query},
+};
+use actix_web_lab::sse::{self, Sse};
+use futures::{
+    future::{select, Either},
+    Stream,
+};
+use serde::Deserialize;
+use surrealdb::sql::Thing;
+use tokio::sync::broadcast::error::RecvError;
+
+use super::{
+    extractor::AuthUser,
+    live::{sse_event, HEARTBEAT, RETRY},
+    response::{ApiResult, Response},
+};
+use crate::{
+    models::{
+        contact_model::Contact,
+        model::{ConnectionData, DBConnection, CRUD},
+        presence_model::{LastSeen, Presence, PresenceStatus},
+        user_model::User,
+    },
+    prelude::Error,
+    repository::live::{Change, LiveHub},
+    signaling::presence::PresenceTracker,
+};
+
+/// Maximum number of peer ids of a presence request
+const MAX_PEER_IDS: usize = 100;
+
+/// Query of the presence endpoints
+///
+/// ## Fields
+///
+/// * `peer_ids` are the comma separated peer ids of the users
+#[derive(Debug, Deserialize)]
+pub struct PresenceQuery {
+    pub peer_ids: String,
+}
+
+impl PresenceQuery {
+    /// Parse the peer ids, without duplicates
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Validation` - There is no peer id or too many of them
+    fn peer_ids(&self) -> Result<Vec<String>, Error> {
+        let mut seen = HashSet::new();
+        let peer_ids: Vec<String> = self
+            .peer_ids
+            .split(',')
+            .map(str::trim)
+            .filter(|peer_id| !peer_id.is_empty() && seen.insert(*peer_id))
+            .map(str::to_string)
+            .collect();
+
+        if peer_ids.is_empty() {
+            return Err(Error::Validation("`peer_ids` must not be empty".to_string()));
+        }
+        if peer_ids.len() > MAX_PEER_IDS {
+            return Err(Error::Validation(format!(
+                "`peer_ids` must not have more than {MAX_PEER_IDS} peer ids"
+            )));
+        }
+
+        Ok(peer_ids)
+    }
+}
+
+/// Get the users of the peer ids the viewer may see, by peer id
+///
+/// Only the contacts of the viewer and the viewer themselves are kept, blocked users are
+/// never contacts.
+async fn visible_users(
+    db: &DBConnection,
+    viewer: &User,
+    peer_ids: &[String],
+) -> Result<HashMap<String, LastSeen>, Error> {
+    let mut users = Presence::last_seen(db, peer_ids).await?;
+    let contacts = Contact::accepted_with(db, &viewer.id).await?;
+    users.retain(|_, user| user.id == viewer.id || contacts.contains(&user.id));
+
+    Ok(users)
+}
+
+/// Whether a change of the `contact` table concerns a user
+fn concerns(change: &Change, user: &Thing) -> bool {
+    // Deletions only carry the id, the last known edge has its users
+    let edge = change.before.as_ref().unwrap_or(&change.data);
+
+    ["in", "out"].iter().any(|side| {
+        edge.get(side)
+            .and_then(|id| serde_json::from_value::<Thing>(id.clone()).ok())
+            .is_some_and(|id| &id == user)
+    })
+}
+
+/// Get the presence of users as seen by the viewer
+///
+/// Unknown users are reported offline, like invisible users and those who are not contacts,
+/// so that presence does not reveal whether a peer id exists. Viewers always see their own
+/// presence.
+fn statuses(
+    presence: &PresenceTracker,
+    viewer: &User,
+    users: &HashMap<String, LastSeen>,
+    peer_ids: Vec<String>,
+) -> Vec<PresenceStatus> {
+    peer_ids
+        .into_iter()
+        .map(|peer_id| match users.get(&peer_id) {
+            Some(user) if user.is_visible || peer_id == viewer.peer_id => {
+                let online = presence.is_online(&peer_id);
+                PresenceStatus {
+                    online,
+                    last_seen: if online { None } else { user.last_seen.clone() },
+                    peer_id,
+                }
+            }
+            _ => PresenceStatus {
+                peer_id,
+                online: false,
+                last_seen: None,
+            },
+        })
+        .collect()
+}
+
+/// Get the presence of users
+///
+/// ## Arguments
+///
+/// * `query` - The peer ids of the users, `?peer_ids=a,b`
+///
+/// ## Returns
+///
+/// * `200 OK` with the presence of every requested user, only contacts may be online
+/// * `400 Bad Request` if there is no peer id or more than 100
+/// * `401 Unauthorized` if nobody is logged in
+#[get("")]
+pub async fn get_presence(
+    user: AuthUser,
+    db: ConnectionData,
+    presence: Data<PresenceTracker>,
+    query: Query<PresenceQuery>,
+) -> ApiResult<Vec<PresenceStatus>> {
+    let peer_ids = query.peer_ids()?;
+    let users = visible_users(&db, &user, &peer_ids).await?;
+    let statuses = statuses(&presence, &user, &users, peer_ids);
+
+    Ok(Response::new_success(200, "OK".to_string(), statuses))
+}
+
+/// Stream the presence changes of users over Server-Sent Events
+///
+/// Starts with a `presence` event per requested user, then sends one per change. Every
+/// user is sent again when the contacts of the viewer change, e.g. after a block.
+///
+/// ## Arguments
+///
+/// * `query` - The peer ids of the users, `?peer_ids=a,b`
+///
+/// ## Returns
+///
+/// * `200 OK` with the `text/event-stream`
+/// * `400 Bad Request` if there is no peer id or more than 100
+/// * `401 Unauthorized` if nobody is logged in
+#[get("/live")]
+pub async fn live_presence(
+    user: AuthUser,
+    db: ConnectionData,
+    presence: Data<PresenceTracker>,
+    hub: Data<LiveHub>,
+    query: Query<PresenceQuery>,
+) -> Result<Sse<impl Stream<Item = Result<sse::Event, Error>>>, Error> {
+    let peer_ids = query.peer_ids()?;
+    // Subscribe before reading the current presence so no change is missed
+    let mut receiver = presence.subscribe();
+    let mut contacts = hub.subscribe(Contact::TABLE, None).await?;
+    let viewer = user.into_inner();
+    let db = db.get_ref().clone();
+
+    let stream = async_stream::stream! {
+        'stream: loop {
+            let users = match visible_users(&db, &viewer, &peer_ids).await {
+                Ok(users) => users,
+                Err(e) => {
+                    yield Err(e);
+                    break;
+                }
+            };
+            for status in statuses(&presence, &viewer, &users, peer_ids.clone()) {
+                yield sse_event("presence", None, status);
+            }
+
+            loop {
+                let next = select(
+                    Box::pin(receiver.recv()),
+                    Box::pin(contacts.receiver.recv()),
+                )
+                .await;
+                match next {
+                    // Unknown users and those who are not contacts never change
+                    Either::Left((Ok(status), _)) if users.contains_key(&status.peer_id) => {
+                        yield sse_event("presence", None, status);
+                    }
+                    Either::Left((Ok(_), _)) => {}
+                    // The contacts of the viewer changed, send the current presence again
+                    Either::Right((Ok(change), _)) if concerns(&change, &viewer.id) => break,
+                    Either::Right((Ok(_), _)) => {}
+                    // Changes were dropped, send the current presence again
+                    Either::Left((Err(RecvError::Lagged(_)), _))
+                    | Either::Right((Err(RecvError::Lagged(_)), _)) => break,
+                    Either::Left((Err(RecvError::Closed), _))
+                    | Either::Right((Err(RecvError::Closed), _)) => break 'stream,
+                }
+            }
+        }
+    };
+
+    Ok(Sse::from_stream(stream)
+        .with_keep_alive(HEARTBEAT)
+        .with_retry_duration(RETRY))
+}
+
+/// Register the presence routes
+///
+/// ## Arguments
+///
+/// * `cfg` - The service config of the `/presence` scope
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_presence).service(live_presence);
+}
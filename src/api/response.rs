@@ -0,0 +1,148 @@
+// api/response.rs
+use actix_web::{
+    body::BoxBody,
+    http::{
+        header::{EntityTag, ETag},
+        StatusCode,
+    },
+    CustomizeResponder, HttpRequest, HttpResponse, Responder,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::Error;
+
+/// Result type returned by every API handler
+///
+/// ## Type Alias
+///
+/// * `Result<Response<T>, Error>` - The JSON envelope or the crate error
+pub type ApiResult<T> = Result<Response<T>, Error>;
+
+/// Result type returned by handlers of a single versioned record
+///
+/// ## Type Alias
+///
+/// * `Result<CustomizeResponder<Response<T>>, Error>` - The JSON envelope with an `ETag`
+pub type TaggedResult<T> = Result<CustomizeResponder<Response<T>>, Error>;
+
+/// Pagination metadata attached to list responses
+///
+/// ## Fields
+///
+/// * `total` is the total number of records matching the query
+/// * `limit` is the maximum number of records in the page
+/// * `offset` is the offset of the page, if offset pagination was used
+/// * `next_cursor` is the cursor of the next page, if there is one
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct Pagination {
+    pub total: u64,
+    pub limit: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// JSON envelope returned by every endpoint
+///
+/// The HTTP status of the response is its `code`.
+///
+/// ## Fields
+///
+/// * `code` is the status code of the response
+/// * `success` is true if the request succeeded
+/// * `message` is a human readable message
+/// * `data` is the payload of the response, if any
+/// * `pagination` is the pagination metadata, if the payload is a page
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Response<T> {
+    pub code: u16,
+    pub success: bool,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pagination: Option<Pagination>,
+}
+
+impl<T: Serialize> Response<T> {
+    /// Create a successful response
+    ///
+    /// ## Arguments
+    ///
+    /// * `code` - The status code
+    /// * `message` - The message
+    /// * `data` - The payload
+    ///
+    /// ## Returns
+    ///
+    /// * `Response<T>` - The response
+    pub fn new_success(code: u16, message: String, data: T) -> Self {
+        Self {
+            code,
+            success: true,
+            message,
+            data: Some(data),
+            pagination: None,
+        }
+    }
+
+    /// Create an error response
+    ///
+    /// ## Arguments
+    ///
+    /// * `code` - The status code
+    /// * `message` - The error message
+    ///
+    /// ## Returns
+    ///
+    /// * `Response<T>` - The response, without payload
+    pub fn new_error(code: u16, message: String) -> Self {
+        Self {
+            code,
+            success: false,
+            message,
+            data: None,
+            pagination: None,
+        }
+    }
+
+    /// Attach pagination metadata to the response
+    ///
+    /// ## Arguments
+    ///
+    /// * `pagination` - The pagination metadata
+    ///
+    /// ## Returns
+    ///
+    /// * `Response<T>` - The response with pagination
+    pub fn with_pagination(mut self, pagination: Pagination) -> Self {
+        self.pagination = Some(pagination);
+        self
+    }
+
+    /// Attach the `ETag` of a record version to the response
+    ///
+    /// ## Arguments
+    ///
+    /// * `version` - The version of the returned record
+    ///
+    /// ## Returns
+    ///
+    /// * `CustomizeResponder<Response<T>>` - The response with a strong `ETag`
+    pub fn with_etag(self, version: u64) -> CustomizeResponder<Self> {
+        self.customize()
+            .insert_header(ETag(EntityTag::new_strong(version.to_string())))
+    }
+}
+
+impl<T: Serialize> Responder for Response<T> {
+    type Body = BoxBody;
+
+    /// Serialize the envelope as JSON, with its `code` as HTTP status
+    fn respond_to(self, _req: &HttpRequest) -> HttpResponse<Self::Body> {
+        let status = StatusCode::from_u16(self.code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+
+        HttpResponse::build(status).json(self)
+    }
+}
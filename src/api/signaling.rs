@@ -0,0 +1,218 @@
+// api/signaling.rs
+use std::time::{Duration, Instant};
+
+use actix_web::{
+    get, rt,
+    web::{self, Data},
+    HttpRequest, HttpResponse,
+};
+use actix_ws::{CloseCode, CloseReason, Message, MessageStream, Session};
+use futures::StreamExt;
+use log::{info, warn};
+use surrealdb::sql::Thing;
+
+use super::extractor::AuthUser;
+use crate::{
+    config::{AppConfig, SignalingConfig},
+    models::{
+        contact_model::Contact, model::ConnectionData, presence_model::Presence, user_model::User,
+    },
+    prelude::Error,
+    signaling::{
+        hub::{to_text, SignalingHub},
+        message::{ClientMessage, ServerMessage},
+        presence::PresenceTracker,
+        rate_limit::RateLimiter,
+    },
+};
+
+/// Everything a signaling connection shares with the others
+struct Shared {
+    db: ConnectionData,
+    hub: SignalingHub,
+    presence: PresenceTracker,
+}
+
+/// Open the signaling WebSocket of the current user
+///
+/// The user is reachable at their `peer_id` while connected. Clients send `offer`, `answer`
+/// and `ice_candidate` messages with the peer id of the recipient in `to`, which receives
+/// them with the peer id of the sender in `from`. A message that cannot be relayed is
+/// answered with an `error` message.
+///
+/// The user is online while the connection is open, see `PresenceTracker`.
+///
+/// ## Returns
+///
+/// * `101 Switching Protocols` with the WebSocket
+/// * `400 Bad Request` if the request is not a WebSocket handshake
+/// * `401 Unauthorized` if nobody is logged in
+#[get("/signaling")]
+pub async fn signaling(
+    user: AuthUser,
+    db: ConnectionData,
+    hub: Data<SignalingHub>,
+    presence: Data<PresenceTracker>,
+    config: Data<AppConfig>,
+    req: HttpRequest,
+    body: web::Payload,
+) -> Result<HttpResponse, Error> {
+    let (response, session, messages) = actix_ws::handle(&req, body)
+        .map_err(|e| Error::Validation(format!("cannot open the WebSocket, {e}")))?;
+
+    let shared = Shared {
+        db,
+        hub: hub.get_ref().clone(),
+        presence: presence.get_ref().clone(),
+    };
+    rt::spawn(run(
+        shared,
+        session,
+        messages,
+        user.into_inner(),
+        config.signaling,
+    ));
+
+    Ok(response)
+}
+
+/// Serve a signaling connection until it is closed
+async fn run(
+    shared: Shared,
+    mut session: Session,
+    mut messages: MessageStream,
+    user: User,
+    config: SignalingConfig,
+) {
+    let Shared { db, hub, presence } = shared;
+    let peer_id = user.peer_id;
+    let connection = hub.connect(&peer_id, session.clone()).await;
+    presence.connect(&peer_id, user.is_visible);
+    info!("🔌 Peer {peer_id} connected");
+    let now = chrono::Utc::now().to_rfc3339();
+    if let Err(e) = Presence::touch(&db, &user.id, &now).await {
+        warn!("Failed to record that {peer_id} was seen: {e}");
+    }
+
+    let mut limiter = RateLimiter::new(config.messages_per_second, config.burst_size);
+    let heartbeat = Duration::from_secs(config.heartbeat_secs);
+    let mut last_seen = Instant::now();
+
+    let welcome = ServerMessage::Welcome {
+        peer_id: peer_id.clone(),
+    };
+    let reason = match session.text(to_text(&welcome)).await {
+        Err(_) => None,
+        Ok(()) => loop {
+            let message = match rt::time::timeout(heartbeat, messages.next()).await {
+                // Idle, make sure the client is still there
+                Err(_) if last_seen.elapsed() < heartbeat * 2 => {
+                    if session.ping(b"").await.is_err() {
+                        break None;
+                    }
+                    continue;
+                }
+                Err(_) => {
+                    break Some(CloseReason {
+                        code: CloseCode::Away,
+                        description: Some("heartbeat timed out".to_string()),
+                    })
+                }
+                Ok(None) => break None,
+                Ok(Some(Err(e))) => {
+                    warn!("Closing the signaling connection of {peer_id}: {e}");
+                    break Some(CloseCode::Protocol.into());
+                }
+                Ok(Some(Ok(message))) => message,
+            };
+            last_seen = Instant::now();
+
+            let result = match message {
+                Message::Text(text) => {
+                    handle_text(&db, &hub, &user.id, &peer_id, &config, &mut limiter, &text).await
+                }
+                Message::Binary(_) => Err(Error::Validation(
+                    "only text messages are supported".to_string(),
+                )),
+                Message::Ping(bytes) => {
+                    if session.pong(&bytes).await.is_err() {
+                        break None;
+                    }
+                    Ok(())
+                }
+                Message::Close(reason) => break reason,
+                Message::Continuation(_) => {
+                    break Some(CloseReason {
+                        code: CloseCode::Size,
+                        description: Some("fragmented messages are not supported".to_string()),
+                    })
+                }
+                Message::Pong(_) | Message::Nop => Ok(()),
+            };
+
+            if let Err(e) = result {
+                if session.text(to_text(&ServerMessage::from(&e))).await.is_err() {
+                    break None;
+                }
+            }
+        },
+    };
+
+    let _ = session.close(reason).await;
+    // A replaced connection leaves the peer online
+    if hub.disconnect(&peer_id, connection) {
+        // The tracker follows the hub before anything is awaited, a reconnection may follow
+        let last_seen = chrono::Utc::now().to_rfc3339();
+        presence.disconnect(&peer_id, last_seen.clone());
+        if let Err(e) = Presence::touch(&db, &user.id, &last_seen).await {
+            warn!("Failed to record that {peer_id} was seen: {e}");
+        }
+    }
+    info!("🔌 Peer {peer_id} disconnected");
+}
+
+/// Validate a text message and relay it to its recipient
+///
+/// ## Errors
+///
+/// * `Error::RateLimited` - The connection sent too many messages
+/// * `Error::Validation` - The message is too large or invalid
+/// * `Error::NotFound` - The recipient is not connected, invisible or not a contact of the user
+async fn handle_text(
+    db: &ConnectionData,
+    hub: &SignalingHub,
+    user: &Thing,
+    peer_id: &str,
+    config: &SignalingConfig,
+    limiter: &mut RateLimiter,
+    text: &str,
+) -> Result<(), Error> {
+    if !limiter.check() {
+        return Err(Error::RateLimited);
+    }
+    if text.len() > config.max_message_size {
+        return Err(Error::Validation(format!(
+            "message is larger than {} bytes",
+            config.max_message_size
+        )));
+    }
+
+    let message: ClientMessage = serde_json::from_str(text)
+        .map_err(|e| Error::Validation(format!("invalid message, {e}")))?;
+    message.validate(peer_id)?;
+
+    let (to, message) = message.relay_from(peer_id);
+    // Invisible users, users who are not contacts and blocked users look offline
+    let reachable = match User::get_from_peer_id(db.clone(), &to).await? {
+        Some(recipient) => {
+            recipient.is_visible
+                && Contact::accepted_with(db, user).await?.contains(&recipient.id)
+                && !Contact::is_blocked(db, user, &recipient.id).await?
+        }
+        None => false,
+    };
+    if !reachable {
+        return Err(Error::NotFound(format!("peer {to} is not connected")));
+    }
+    hub.relay(&to, &message).await
+}
@@ -0,0 +1,357 @@
+// api/timetable.rs
+use actix_web::{
+    delete, get, post,
+    web::{self, Data, Query},
+    HttpResponse,
+};
+use chrono::{DateTime, Days, Duration, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    extractor::AuthUser,
+    response::{ApiResult, Response},
+};
+use crate::{
+    config::{AppConfig, UntisConfig},
+    jobs::{self, timetable::SyncLocks},
+    models::{
+        calendar_model::CalendarToken,
+        model::ConnectionData,
+        timetable_model::{Lesson, LessonStatus, Timetable, TimetableSync},
+        untis_model::UntisAccount,
+    },
+    prelude::Error,
+    utils::ics::{self, Event},
+};
+
+/// Number of days of a timetable request without `to`
+const DEFAULT_DAYS: u64 = 7;
+/// Maximum number of days of a timetable request
+const MAX_DAYS: i64 = 90;
+
+/// Domain of the UIDs of the calendar events
+const UID_DOMAIN: &str = "timetable.backend";
+/// Start of the sequence numbers of the calendar events, 2024-01-01T00:00:00Z
+const SEQUENCE_EPOCH: i64 = 1_704_067_200;
+
+/// Query of the timetable endpoints
+///
+/// ## Fields
+///
+/// * `from` is the first day, `yyyy-mm-dd`, today by default
+/// * `to` is the last day, included, a week after `from` by default
+#[derive(Debug, Deserialize)]
+pub struct TimetableQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+impl TimetableQuery {
+    /// Parse the days of the request
+    ///
+    /// ## Arguments
+    ///
+    /// * `config` - The WebUntis configuration, `from` defaults to the current day at the schools
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Validation` - A day is malformed, `to` is before `from`, or the days span
+    ///   more than `MAX_DAYS`
+    /// * `Error::Config` - The time zone of the schools is invalid
+    fn days(&self, config: &UntisConfig) -> Result<(String, String), Error> {
+        let parse = |day: &str| {
+            NaiveDate::parse_from_str(day, "%Y-%m-%d")
+                .map_err(|_| Error::Validation(format!("`{day}` is not a yyyy-mm-dd date")))
+        };
+        let from = match &self.from {
+            Some(from) => parse(from)?,
+            None => jobs::timetable::today(config)?,
+        };
+        let to = match &self.to {
+            Some(to) => parse(to)?,
+            None => from + Days::new(DEFAULT_DAYS - 1),
+        };
+
+        if to < from {
+            return Err(Error::Validation("`to` must not be before `from`".to_string()));
+        }
+        if (to - from).num_days() >= MAX_DAYS {
+            return Err(Error::Validation(format!(
+                "the timetable must not span more than {MAX_DAYS} days"
+            )));
+        }
+
+        Ok((from.format("%Y-%m-%d").to_string(), to.format("%Y-%m-%d").to_string()))
+    }
+}
+
+/// Returns the age after which a cached timetable is stale, a missed run of the job is tolerated
+fn max_age(config: &UntisConfig) -> Duration {
+    Duration::seconds(config.sync_interval_secs.saturating_mul(2) as i64)
+}
+
+/// Get the account linked to a user
+///
+/// ## Errors
+///
+/// * `Error::NotFound` - No account is linked
+async fn account(db: &ConnectionData, user: &AuthUser) -> Result<UntisAccount, Error> {
+    UntisAccount::of(db, &user.id)
+        .await?
+        .ok_or_else(|| Error::NotFound(UntisAccount::id_of(&user.id).to_string()))
+}
+
+/// Get the cached timetable of the current user
+///
+/// ## Arguments
+///
+/// * `query` - The days of the timetable, `?from=yyyy-mm-dd&to=yyyy-mm-dd`
+///
+/// ## Returns
+///
+/// * `200 OK` with the lessons and the freshness of the cache
+/// * `400 Bad Request` if the days are malformed or span more than 90 days
+/// * `401 Unauthorized` if nobody is logged in
+/// * `404 Not Found` if no WebUntis account is linked
+#[get("")]
+pub async fn get_timetable(
+    user: AuthUser,
+    db: ConnectionData,
+    config: Data<AppConfig>,
+    query: Query<TimetableQuery>,
+) -> ApiResult<Timetable> {
+    let (from, to) = query.days(&config.untis)?;
+    account(&db, &user).await?;
+    let timetable = Timetable::of(&db, &user.id, &from, &to, max_age(&config.untis)).await?;
+
+    Ok(Response::new_success(200, "OK".to_string(), timetable))
+}
+
+/// Synchronise the timetable of the current user now, then get it
+///
+/// A refresh less than `refresh_cooldown_secs` after the previous synchronisation
+/// returns the cached timetable without asking WebUntis.
+///
+/// ## Arguments
+///
+/// * `query` - The days of the timetable, `?from=yyyy-mm-dd&to=yyyy-mm-dd`
+///
+/// ## Returns
+///
+/// * `200 OK` with the lessons and the freshness of the cache
+/// * `400 Bad Request` if the days are malformed or the credentials were rejected
+/// * `401 Unauthorized` if nobody is logged in
+/// * `404 Not Found` if no WebUntis account is linked
+/// * `502 Bad Gateway` if the WebUntis server cannot be reached
+#[post("/refresh")]
+pub async fn refresh_timetable(
+    user: AuthUser,
+    db: ConnectionData,
+    config: Data<AppConfig>,
+    syncs: Data<SyncLocks>,
+    query: Query<TimetableQuery>,
+) -> ApiResult<Timetable> {
+    let (from, to) = query.days(&config.untis)?;
+    let account = account(&db, &user).await?;
+    let cooldown = Duration::seconds(config.untis.refresh_cooldown_secs as i64);
+
+    let recent = TimetableSync::of(&db, &user.id)
+        .await?
+        .is_some_and(|sync| sync.attempted_within(cooldown));
+    let message = if recent {
+        "Timetable was refreshed recently"
+    } else {
+        let cipher = config.untis.cipher()?;
+        jobs::timetable::sync(&db, &config.untis, &cipher, &syncs, &account).await?;
+        "Timetable refreshed"
+    };
+    let timetable = Timetable::of(&db, &user.id, &from, &to, max_age(&config.untis)).await?;
+
+    Ok(Response::new_success(200, message.to_string(), timetable))
+}
+
+/// Calendar Feed Struct, the address of the calendar feed of a user
+///
+/// ## Fields
+///
+/// * `Token` is the token of the feed, shown once
+/// * `Path` is the path of the feed, to subscribe to in a calendar app
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CalendarFeed {
+    pub token: String,
+    pub path: String,
+}
+
+/// Query of the calendar feed
+///
+/// ## Fields
+///
+/// * `token` is the token of the feed
+#[derive(Debug, Deserialize)]
+pub struct CalendarQuery {
+    pub token: String,
+}
+
+/// Convert a local time of the schools to a UTC date-time of a calendar
+///
+/// ## Arguments
+///
+/// * `time` - The local time, `yyyy-mm-ddThh:mm:ss`
+/// * `timezone` - The time zone of the schools
+///
+/// ## Returns
+///
+/// * `String` - The UTC time, `yyyymmddThhmmssZ`
+///
+/// ## Errors
+///
+/// * `Error::UntisError` - The time is invalid
+fn utc(time: &str, timezone: Tz) -> Result<String, Error> {
+    let invalid = || Error::UntisError(format!("invalid lesson time `{time}`"));
+    let local = NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M:%S").map_err(|_| invalid())?;
+    let time = match timezone.from_local_datetime(&local) {
+        LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => time,
+        // Skipped when the clocks are put forward, they show an hour later
+        LocalResult::None => timezone
+            .from_local_datetime(&(local + Duration::hours(1)))
+            .earliest()
+            .ok_or_else(invalid)?,
+    };
+
+    Ok(time.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string())
+}
+
+/// Convert a lesson to a calendar event
+///
+/// The UID is made of the user and the WebUntis period, so a lesson keeps it across
+/// synchronisations and calendar clients update it rather than duplicating it. The sequence
+/// is the number of seconds from `SEQUENCE_EPOCH` to the last change of the lesson, which
+/// only grows when the lesson changes.
+///
+/// ## Errors
+///
+/// * `Error::UntisError` - The times of the lesson are invalid
+fn event(lesson: &Lesson, timezone: Tz) -> Result<Event, Error> {
+    let stamp = DateTime::parse_from_rfc3339(&lesson.updated_at)
+        .map(|updated_at| updated_at.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now());
+    let sequence = (stamp.timestamp() - SEQUENCE_EPOCH).clamp(0, u32::MAX.into()) as u32;
+    let summary = match (lesson.subjects.is_empty(), &lesson.text) {
+        (true, Some(text)) => text.clone(),
+        (true, None) => "Lesson".to_string(),
+        (false, _) => lesson.subjects.join(", "),
+    };
+    let mut description = Vec::new();
+    if !lesson.teachers.is_empty() {
+        description.push(format!("Teachers: {}", lesson.teachers.join(", ")));
+    }
+    description.extend(lesson.substitution_text.iter().cloned());
+    if !lesson.subjects.is_empty() {
+        description.extend(lesson.text.iter().cloned());
+    }
+    description.extend(lesson.info.iter().cloned());
+
+    Ok(Event {
+        uid: format!("{}-{}@{UID_DOMAIN}", lesson.user.id.to_raw(), lesson.period),
+        stamp: stamp.format("%Y%m%dT%H%M%SZ").to_string(),
+        sequence,
+        start: utc(&lesson.start, timezone)?,
+        end: utc(&lesson.end, timezone)?,
+        summary,
+        location: (!lesson.rooms.is_empty()).then(|| lesson.rooms.join(", ")),
+        description: (!description.is_empty()).then(|| description.join("\n")),
+        cancelled: lesson.status == LessonStatus::Cancelled,
+    })
+}
+
+/// Create the calendar feed of the current user, revoking the previous one
+///
+/// ## Returns
+///
+/// * `200 OK` with the token and the path of the feed
+/// * `401 Unauthorized` if nobody is logged in
+/// * `404 Not Found` if no WebUntis account is linked
+#[post("/calendar")]
+pub async fn create_calendar_feed(user: AuthUser, db: ConnectionData) -> ApiResult<CalendarFeed> {
+    account(&db, &user).await?;
+    let token = CalendarToken::rotate(&db, &user.id).await?;
+    let feed = CalendarFeed {
+        path: format!("/timetable.ics?token={token}"),
+        token,
+    };
+
+    Ok(Response::new_success(
+        200,
+        "Calendar feed created".to_string(),
+        feed,
+    ))
+}
+
+/// Revoke the calendar feed of the current user
+///
+/// ## Returns
+///
+/// * `200 OK` if the feed was revoked
+/// * `401 Unauthorized` if nobody is logged in
+/// * `404 Not Found` if the user has no calendar feed
+#[delete("/calendar")]
+pub async fn delete_calendar_feed(user: AuthUser, db: ConnectionData) -> ApiResult<()> {
+    if !CalendarToken::revoke(&db, &user.id).await? {
+        return Err(Error::NotFound(CalendarToken::id_of(&user.id).to_string()));
+    }
+
+    Ok(Response::new_success(
+        200,
+        "Calendar feed revoked".to_string(),
+        (),
+    ))
+}
+
+/// Get the calendar feed of a user, the cached lessons of the synchronised days
+///
+/// The feed is authenticated by its token rather than a session, so calendar apps can
+/// subscribe to it.
+///
+/// ## Arguments
+///
+/// * `query` - The token of the feed, `?token=...`
+///
+/// ## Returns
+///
+/// * `200 OK` with the `text/calendar` document
+/// * `404 Not Found` if the token is unknown or was revoked, or its user was deleted
+#[get("/timetable.ics")]
+pub async fn calendar_feed(
+    db: ConnectionData,
+    config: Data<AppConfig>,
+    query: Query<CalendarQuery>,
+) -> Result<HttpResponse, Error> {
+    let user = CalendarToken::user_of(&db, &query.token)
+        .await?
+        .ok_or_else(|| Error::NotFound("calendar feed".to_string()))?;
+    let (from, to) = jobs::timetable::window(&config.untis)?;
+    let (from, to) = (from.format("%Y-%m-%d").to_string(), to.format("%Y-%m-%d").to_string());
+    let lessons = Lesson::between(&db, &user, &from, &to).await?;
+    let timezone = config.untis.time_zone()?;
+    let events = lessons
+        .iter()
+        .map(|lesson| event(lesson, timezone))
+        .collect::<Result<Vec<Event>, Error>>()?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/calendar; charset=utf-8")
+        .body(ics::calendar("Timetable", &config.untis.timezone, &events)))
+}
+
+/// Register the timetable routes
+///
+/// ## Arguments
+///
+/// * `cfg` - The service config of the `/timetable` scope
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_timetable)
+        .service(refresh_timetable)
+        .service(create_calendar_feed)
+        .service(delete_calendar_feed);
+}
@@ -0,0 +1,189 @@
+// api/untis.rs
+use actix_web::{
+    delete, get, post, put,
+    web::{self, Data, Json},
+};
+use log::warn;
+use serde::Deserialize;
+
+use super::{
+    extractor::AuthUser,
+    response::{ApiResult, Response},
+};
+use crate::{
+    config::{AppConfig, UntisConfig},
+    models::{
+        model::ConnectionData,
+        untis_model::{UntisAccount, UntisCredentials, UntisLink},
+    },
+    prelude::Error,
+    untis::client::UntisClient,
+};
+
+/// Body of `PUT /untis/account`
+///
+/// ## Fields
+///
+/// * `server` is the WebUntis server of the school, e.g. `nessa.webuntis.com`
+/// * `school` is the login name of the school
+/// * `username` is the WebUntis user name
+/// * `password` is the WebUntis password
+#[derive(Deserialize)]
+pub struct LinkRequest {
+    pub server: String,
+    pub school: String,
+    pub username: String,
+    pub password: String,
+}
+
+/// Log in to WebUntis and out again, to check credentials
+///
+/// ## Errors
+///
+/// * `Error::Validation` - The server or school is empty, or the credentials were rejected
+/// * `Error::UntisError` - The login failed
+async fn test_login(
+    config: &UntisConfig,
+    server: &str,
+    school: &str,
+    credentials: &UntisCredentials,
+) -> Result<(), Error> {
+    let mut client = UntisClient::new(server, school, config)?;
+    client
+        .authenticate(&credentials.username, &credentials.password)
+        .await?;
+    if let Err(e) = client.logout().await {
+        warn!("Failed to log out of WebUntis after a test login: {e}");
+    }
+
+    Ok(())
+}
+
+/// Get the WebUntis account linked to the current user
+///
+/// ## Returns
+///
+/// * `200 OK` with the account, without its credentials
+/// * `401 Unauthorized` if nobody is logged in
+/// * `404 Not Found` if no account is linked
+#[get("/account")]
+pub async fn get_account(user: AuthUser, db: ConnectionData) -> ApiResult<UntisLink> {
+    let account = UntisAccount::of(&db, &user.id)
+        .await?
+        .ok_or_else(|| Error::NotFound(UntisAccount::id_of(&user.id).to_string()))?;
+
+    Ok(Response::new_success(200, "OK".to_string(), account.into()))
+}
+
+/// Link a WebUntis account to the current user, replacing the linked one
+///
+/// The credentials are checked with a test login, then stored encrypted.
+///
+/// ## Arguments
+///
+/// * `body` - The server, school and credentials of the account
+///
+/// ## Returns
+///
+/// * `200 OK` with the account, without its credentials
+/// * `400 Bad Request` if a field is empty or the credentials were rejected
+/// * `401 Unauthorized` if nobody is logged in
+/// * `502 Bad Gateway` if the WebUntis server cannot be reached
+#[put("/account")]
+pub async fn link_account(
+    user: AuthUser,
+    db: ConnectionData,
+    config: Data<AppConfig>,
+    body: Json<LinkRequest>,
+) -> ApiResult<UntisLink> {
+    let body = body.into_inner();
+    if body.username.trim().is_empty() || body.password.is_empty() {
+        return Err(Error::Validation(
+            "the WebUntis username and password must not be empty".to_string(),
+        ));
+    }
+    let cipher = config.untis.cipher()?;
+    let server = body.server.trim();
+    let school = body.school.trim();
+    let credentials = UntisCredentials {
+        username: body.username.trim().to_string(),
+        password: body.password,
+    };
+
+    test_login(&config.untis, server, school, &credentials).await?;
+    let account = UntisAccount::link(&db, &cipher, &user.id, server, school, &credentials).await?;
+
+    Ok(Response::new_success(
+        200,
+        "WebUntis account linked".to_string(),
+        account.into(),
+    ))
+}
+
+/// Check that the stored credentials are still accepted
+///
+/// The outcome is recorded as `verified_at` or `last_error`.
+///
+/// ## Returns
+///
+/// * `200 OK` with the account, without its credentials
+/// * `400 Bad Request` if the credentials were rejected
+/// * `401 Unauthorized` if nobody is logged in
+/// * `404 Not Found` if no account is linked
+/// * `502 Bad Gateway` if the WebUntis server cannot be reached
+#[post("/account/verify")]
+pub async fn verify_account(
+    user: AuthUser,
+    db: ConnectionData,
+    config: Data<AppConfig>,
+) -> ApiResult<UntisLink> {
+    let account = UntisAccount::of(&db, &user.id)
+        .await?
+        .ok_or_else(|| Error::NotFound(UntisAccount::id_of(&user.id).to_string()))?;
+    let credentials = account.credentials(&config.untis.cipher()?)?;
+
+    let result = test_login(&config.untis, &account.server, &account.school, &credentials).await;
+    let error = result.as_ref().err().map(ToString::to_string);
+    let account = UntisAccount::record_verification(&db, &user.id, error).await?;
+    result?;
+
+    Ok(Response::new_success(
+        200,
+        "WebUntis account verified".to_string(),
+        account.into(),
+    ))
+}
+
+/// Unlink the WebUntis account of the current user, deleting its credentials
+///
+/// ## Returns
+///
+/// * `200 OK` once the account is unlinked
+/// * `401 Unauthorized` if nobody is logged in
+/// * `404 Not Found` if no account is linked
+#[delete("/account")]
+pub async fn unlink_account(user: AuthUser, db: ConnectionData) -> ApiResult<()> {
+    if !UntisAccount::unlink(&db, &user.id).await? {
+        return Err(Error::NotFound(
+            UntisAccount::id_of(&user.id).to_string(),
+        ));
+    }
+
+    Ok(Response::new_success(
+        200,
+        "WebUntis account unlinked".to_string(),
+        (),
+    ))
+}
+
+/// Register the WebUntis routes
+///
+/// ## Arguments
+///
+/// * `cfg` - The service config of the `/untis` scope
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_account)
+        .service(link_account)
+        .service(verify_account)
+        .service(unlink_account);
+}
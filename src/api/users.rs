@@ -0,0 +1,232 @@
+// api/users.rs
+use std::collections::HashMap;
+
+use actix_identity::Identity;
+use actix_web::{
+    delete, get,
+    guard::GuardContext,
+    http::header::{self, IfMatch},
+    patch,
+    web::{self, Data, Header, Json, Path, Query},
+};
+use surrealdb::sql::Thing;
+
+use super::{
+    crud::{self, if_match},
+    extractor::AuthUser,
+    live,
+    response::{ApiResult, Response, TaggedResult},
+};
+use crate::{
+    models::{
+        contact_model::Contact,
+        model::{ConnectionData, CRUD},
+        patch::PatchOperation,
+        query::{Filter, FilterOp, ListQuery},
+        untis_model::UntisAccount,
+        user_model::{PublicProfile, User, UserCreate, UserPatch, UserProfile},
+    },
+    prelude::Error,
+    signaling::presence::PresenceTracker,
+};
+
+/// Content type of a JSON Patch (RFC 6902) body
+const JSON_PATCH: &str = "application/json-patch+json";
+
+/// Guard matching requests with a JSON Patch body
+fn is_json_patch(ctx: &GuardContext) -> bool {
+    ctx.head()
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|mime| mime.trim().eq_ignore_ascii_case(JSON_PATCH))
+        .unwrap_or(false)
+}
+
+/// Get the profile of the current user
+///
+/// ## Returns
+///
+/// * `200 OK` with the user's profile and its `ETag`
+/// * `401 Unauthorized` if nobody is logged in
+#[get("/me")]
+pub async fn me(user: AuthUser) -> TaggedResult<UserProfile> {
+    let user = user.into_inner();
+    let version = user.version;
+
+    Ok(Response::new_success(200, "OK".to_string(), UserProfile::from(user)).with_etag(version))
+}
+
+/// Update the profile of the current user with a JSON Patch
+///
+/// ## Arguments
+///
+/// * `ops` - The patch, sent as `application/json-patch+json`
+/// * `version` - The `If-Match` header, the `ETag` the patch was made against
+///
+/// ## Returns
+///
+/// * `200 OK` with the updated profile and its `ETag`
+/// * `400 Bad Request` if the patch touches a protected field or does not apply
+/// * `401 Unauthorized` if nobody is logged in
+/// * `409 Conflict` if the profile was modified since the `If-Match` version
+#[patch("/me", guard = "is_json_patch")]
+pub async fn patch_me(
+    user: AuthUser,
+    db: ConnectionData,
+    presence: Data<PresenceTracker>,
+    ops: Json<Vec<PatchOperation>>,
+    version: Option<Header<IfMatch>>,
+) -> TaggedResult<UserProfile> {
+    let version = if_match(version)?;
+    let user = User::update_patch(db, user.into_inner().id, ops.into_inner(), version)
+        .await
+        .map_err(Error::map_invalid_data)?;
+    presence.set_visible(&user.peer_id, user.is_visible);
+    let version = user.version;
+
+    Ok(
+        Response::new_success(200, "Profile updated".to_string(), UserProfile::from(user))
+            .with_etag(version),
+    )
+}
+
+/// Update the profile of the current user by merging the given fields
+///
+/// ## Arguments
+///
+/// * `data` - The fields to change
+/// * `version` - The `If-Match` header, the `ETag` the change was made against
+///
+/// ## Returns
+///
+/// * `200 OK` with the updated profile and its `ETag`
+/// * `400 Bad Request` if the body has unknown fields or is empty
+/// * `401 Unauthorized` if nobody is logged in
+/// * `409 Conflict` if the profile was modified since the `If-Match` version
+#[patch("/me")]
+pub async fn merge_me(
+    user: AuthUser,
+    db: ConnectionData,
+    presence: Data<PresenceTracker>,
+    data: Json<UserPatch>,
+    version: Option<Header<IfMatch>>,
+) -> TaggedResult<UserProfile> {
+    let version = if_match(version)?;
+    let user = User::update_merge(db, user.into_inner().id, data.into_inner(), version).await?;
+    presence.set_visible(&user.peer_id, user.is_visible);
+    let version = user.version;
+
+    Ok(
+        Response::new_success(200, "Profile updated".to_string(), UserProfile::from(user))
+            .with_etag(version),
+    )
+}
+
+/// Delete the account of the current user and log them out
+///
+/// The account is soft-deleted, it can be restored with `POST /auth/restore` until it is purged.
+/// A linked WebUntis account is unlinked right away.
+///
+/// ## Returns
+///
+/// * `200 OK` once the account is deleted
+/// * `401 Unauthorized` if nobody is logged in
+#[delete("/me")]
+pub async fn delete_me(
+    user: AuthUser,
+    identity: Identity,
+    db: ConnectionData,
+) -> ApiResult<()> {
+    let id = user.into_inner().id;
+    // The WebUntis credentials are not kept for a restore
+    UntisAccount::unlink(&db, &id).await?;
+    User::delete(db, id).await?;
+    identity.logout();
+
+    Ok(Response::new_success(
+        200,
+        "Account deleted".to_string(),
+        (),
+    ))
+}
+
+/// List the visible users, without those who blocked the current user or were blocked by them
+///
+/// Query parameters are parsed by `ListQuery::from_params`, e.g. `?peer_id=...` or `?name=...`
+///
+/// ## Returns
+///
+/// * `200 OK` with the page and its pagination metadata
+/// * `400 Bad Request` if the query is malformed or uses a field that cannot be listed
+/// * `401 Unauthorized` if nobody is logged in
+pub async fn list_users(
+    user: AuthUser,
+    db: ConnectionData,
+    params: Query<HashMap<String, String>>,
+) -> ApiResult<Vec<PublicProfile>> {
+    let mut query = ListQuery::from_params(&params)?;
+    query.filters.push(Filter {
+        field: "is_visible".to_string(),
+        op: FilterOp::Eq,
+        value: true.into(),
+    });
+    query.exclude_ids = Contact::blocked_with(&db, &user.id)
+        .await?
+        .iter()
+        .map(Thing::to_string)
+        .collect();
+    let page = User::list(db, query).await?;
+
+    crud::page_response(page)
+}
+
+/// Get a visible user, users who blocked the current user or were blocked by them are not found
+///
+/// ## Arguments
+///
+/// * `id` - The id of the user, without the table
+///
+/// ## Returns
+///
+/// * `200 OK` with the user's public profile and its `ETag`
+/// * `401 Unauthorized` if nobody is logged in
+/// * `404 Not Found` if the user does not exist, is invisible or is blocked
+pub async fn get_user(
+    user: AuthUser,
+    db: ConnectionData,
+    id: Path<String>,
+) -> TaggedResult<PublicProfile> {
+    let thing = Thing::from((User::TABLE, id.as_str()));
+    let found = User::get_from_id(db.clone(), thing.clone()).await?;
+    let hidden = match found {
+        Some(found) => !found.is_visible || Contact::is_blocked(&db, &user.id, &thing).await?,
+        None => true,
+    };
+    if hidden {
+        return Err(Error::NotFound(thing.to_string()));
+    }
+
+    crud::get::<User, User, UserCreate, PublicProfile>(user, db, id).await
+}
+
+/// Register the user routes
+///
+/// ## Arguments
+///
+/// * `cfg` - The service config of the `/users` scope
+pub fn config(cfg: &mut web::ServiceConfig) {
+    // The JSON Patch route is guarded on its content type and must come before the merge one,
+    // and `/live` must come before `/{id}`
+    cfg.service(me)
+        .service(patch_me)
+        .service(merge_me)
+        .service(delete_me)
+        .route("", web::get().to(list_users))
+        .route(
+            "/live",
+            web::get().to(live::changes::<User, User, UserCreate, PublicProfile>),
+        )
+        .route("/{id}", web::get().to(get_user));
+}
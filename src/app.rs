@@ -0,0 +1,183 @@
+// app.rs
+use actix_governor::{Governor, GovernorConfigBuilder};
+use actix_identity::{config::LogoutBehaviour, IdentityMiddleware};
+use actix_session::{config::PersistentSession, SessionMiddleware};
+use actix_session_surrealdb::SurrealSessionStore;
+use actix_web::{
+    body::MessageBody,
+    cookie::{time::Duration, Key},
+    dev::{ServiceFactory, ServiceRequest, ServiceResponse},
+    middleware::Logger,
+    web::{self, Data},
+    App, ResponseError,
+};
+
+use crate::{
+    api,
+    config::AppConfig,
+    cors,
+    jobs::timetable::SyncLocks,
+    prelude::Error,
+    repository::{live::LiveHub, surrealdb_repo::SurrealDBRepo},
+    signaling::{hub::SignalingHub, presence::PresenceTracker},
+};
+
+#[cfg(feature = "proxy")]
+use crate::governor::NginxIpKeyExctrator;
+
+/// Format of the access log, the one of `Logger::default` with a masked request line
+const LOG_FORMAT: &str = r#"%a "%{request_line}xi" %s %b "%{Referer}i" "%{User-Agent}i" %T"#;
+/// Query parameters carrying a secret, e.g. the token of the calendar feed
+const SECRET_PARAMS: [&str; 1] = ["token"];
+
+/// Everything needed to build the App
+///
+/// ## Fields
+///
+/// * `surreal` is the database repository
+/// * `cookie_key` is the key signing the session cookie
+/// * `config` is the application configuration, also injected as app data
+/// * `live` is the hub of the live query feeds, shared by every worker
+/// * `signaling` is the hub of the connected peers, shared by every worker
+/// * `presence` is the tracker of the online users, shared by every worker
+/// * `syncs` are the locks of the timetables being synchronised, shared with the job
+#[derive(Clone)]
+pub struct AppState {
+    pub surreal: SurrealDBRepo,
+    pub cookie_key: Key,
+    pub config: Data<AppConfig>,
+    pub live: Data<LiveHub>,
+    pub signaling: Data<SignalingHub>,
+    pub presence: Data<PresenceTracker>,
+    pub syncs: Data<SyncLocks>,
+}
+
+/// Write the request line of the access log, the values of `SECRET_PARAMS` masked
+///
+/// ## Arguments
+///
+/// * `req` - The request
+///
+/// ## Returns
+///
+/// * `String` - The method, path, query and HTTP version,
+///   e.g. `GET /timetable.ics?token=*** HTTP/1.1`
+pub fn request_line(req: &ServiceRequest) -> String {
+    let query: Vec<String> = req
+        .query_string()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if SECRET_PARAMS.contains(&name) => format!("{name}=***"),
+            _ => pair.to_string(),
+        })
+        .collect();
+    let uri = if query.is_empty() {
+        req.path().to_string()
+    } else {
+        format!("{}?{}", req.path(), query.join("&"))
+    };
+
+    format!("{} {uri} {:?}", req.method(), req.version())
+}
+
+/// Build the App with its middlewares, app data and routes
+///
+/// ## Arguments
+///
+/// * `state` - The state shared by every worker
+///
+/// ## Returns
+///
+/// * `App` - The app, ready to be served or passed to `actix_web::test::init_service`
+pub fn create_app(
+    state: &AppState,
+) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    let config = &state.config;
+    let logger = Logger::new(LOG_FORMAT).custom_request_replace("request_line", request_line);
+    let json_config = web::JsonConfig::default()
+        .limit(65536) // 64 KiB
+        .error_handler(|err, _req| {
+            let response = Error::Validation(format!("invalid JSON body, {err}")).error_response();
+            actix_web::error::InternalError::from_response(err, response).into()
+        });
+    let query_config = web::QueryConfig::default().error_handler(|err, _req| {
+        let response = Error::Validation(format!("invalid query string, {err}")).error_response();
+        actix_web::error::InternalError::from_response(err, response).into()
+    });
+
+    let cors = cors::cors(&config.cors);
+
+    #[cfg(feature = "proxy")]
+    let governor_config = GovernorConfigBuilder::default()
+        .key_extractor(NginxIpKeyExctrator::new(config.proxy.whitelist.clone()))
+        .per_second(config.rate_limit.per_second)
+        .burst_size(config.rate_limit.burst_size)
+        .use_headers()
+        .finish()
+        .unwrap();
+
+    #[cfg(not(feature = "proxy"))]
+    let governor_config = GovernorConfigBuilder::default()
+        .per_second(config.rate_limit.per_second)
+        .burst_size(config.rate_limit.burst_size)
+        .use_headers()
+        .finish()
+        .unwrap();
+
+    #[allow(clippy::let_and_return)]
+    let app = App::new()
+        .wrap(Governor::new(&governor_config))
+        .wrap(
+            IdentityMiddleware::builder()
+                .logout_behaviour(LogoutBehaviour::PurgeSession)
+                .build(),
+        )
+        .wrap(logger)
+        .wrap(
+            SessionMiddleware::builder(
+                SurrealSessionStore::from_connection(state.surreal.session_db.clone(), "sessions"),
+                state.cookie_key.clone(),
+            )
+            .cookie_same_site(if config.session.cookie_secure {
+                actix_web::cookie::SameSite::None
+            } else {
+                actix_web::cookie::SameSite::Lax
+            })
+            .cookie_secure(config.session.cookie_secure)
+            .cookie_http_only(true)
+            .session_lifecycle(
+                PersistentSession::default()
+                    .session_ttl_extension_policy(
+                        actix_session::config::TtlExtensionPolicy::OnStateChanges,
+                    )
+                    .session_ttl(Duration::days(7)),
+            )
+            .build(),
+        )
+        .wrap(cors)
+        .app_data(json_config)
+        .app_data(query_config)
+        .app_data(Data::new(state.surreal.db.clone()))
+        .app_data(config.clone())
+        .app_data(state.live.clone())
+        .app_data(state.signaling.clone())
+        .app_data(state.presence.clone())
+        .app_data(state.syncs.clone())
+        .configure(api::config);
+    #[cfg(feature = "proxy")]
+    let app = match config.proxy.reverse_proxy {
+        Some(reverse_proxy) => app.app_data(Data::new(reverse_proxy)),
+        None => app,
+    };
+
+    app
+}
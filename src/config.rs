@@ -0,0 +1,665 @@
+// config.rs
+use std::{collections::HashMap, env, fs, net::IpAddr, path::Path, str::FromStr};
+
+use actix_web::http::{header::HeaderName, Method};
+use chrono_tz::Tz;
+use serde::Deserialize;
+
+use crate::{
+    cors, prelude::Error, repository::migrations::MigrationMode, utils::crypto::Cipher,
+};
+
+/// Default path of the optional TOML configuration file
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Minimum length of the cookie key, `Key::from` panics below it
+const COOKIE_KEY_MIN_LENGTH: usize = 64;
+
+/// Maximum number of days of timetable kept in sync, WebUntis limits the range of a request
+const MAX_SYNC_DAYS: u32 = 90;
+
+/// Database configuration
+///
+/// ## Fields
+///
+/// * `location` is the address of the database, `mem://` for an in-memory one
+/// * `username` is the root username
+/// * `password` is the root password
+/// * `namespace` is the namespace to use
+/// * `database` is the database to use
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DatabaseConfig {
+    pub location: String,
+    pub username: String,
+    pub password: String,
+    pub namespace: String,
+    pub database: String,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            location: "127.0.0.1:8000".to_string(),
+            username: "root".to_string(),
+            password: "root".to_string(),
+            namespace: "test".to_string(),
+            database: "test".to_string(),
+        }
+    }
+}
+
+/// Session configuration
+///
+/// ## Fields
+///
+/// * `cookie_key` is the key signing the session cookie, generated on boot if unset
+/// * `cookie_secure` marks the cookie `Secure`, only disable it without TLS
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    pub cookie_key: Option<String>,
+    pub cookie_secure: bool,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            cookie_key: None,
+            cookie_secure: true,
+        }
+    }
+}
+
+/// CORS configuration
+///
+/// ## Fields
+///
+/// * `allowed_origins` is the list of origins allowed to call the API, `https://*.example.com` allows every subdomain
+/// * `allowed_methods` is the list of methods allowed in cross-origin requests
+/// * `allowed_headers` is the list of request headers allowed in cross-origin requests
+/// * `exposed_headers` is the list of response headers exposed to the frontend
+/// * `max_age` is the number of seconds a preflight response may be cached, `None` to not send it
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub exposed_headers: Vec<String>,
+    pub max_age: Option<usize>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec!["http://localhost:3000".to_string()],
+            allowed_methods: ["GET", "POST", "PUT", "PATCH", "DELETE"]
+                .map(String::from)
+                .to_vec(),
+            allowed_headers: ["accept", "content-type", "authorization", "if-match"]
+                .map(String::from)
+                .to_vec(),
+            exposed_headers: vec!["etag".to_string()],
+            max_age: Some(3600),
+        }
+    }
+}
+
+/// Rate limit applied by the Governor middleware
+///
+/// ## Fields
+///
+/// * `per_second` is the number of seconds after which one request is replenished
+/// * `burst_size` is the number of requests allowed in a burst
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub per_second: u64,
+    pub burst_size: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            per_second: 10,
+            burst_size: 2,
+        }
+    }
+}
+
+/// TLS configuration
+///
+/// ## Fields
+///
+/// * `enabled` serves the API over HTTPS
+/// * `cert_path` is the path of the PEM certificate chain
+/// * `key_path` is the path of the PEM PKCS#8 private key
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TlsConfig {
+    pub enabled: bool,
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            cert_path: "cert.pem".to_string(),
+            key_path: "key.pem".to_string(),
+        }
+    }
+}
+
+/// Reverse proxy configuration, used with the `proxy` feature
+///
+/// ## Fields
+///
+/// * `reverse_proxy` is the IP address of the reverse proxy
+/// * `whitelist` is the list of IP addresses exempt from rate limiting
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ProxyConfig {
+    pub reverse_proxy: Option<IpAddr>,
+    pub whitelist: Vec<IpAddr>,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        Self {
+            reverse_proxy: None,
+            whitelist: vec![IpAddr::from([127, 0, 0, 1])],
+        }
+    }
+}
+
+/// Migrations configuration
+///
+/// ## Fields
+///
+/// * `mode` is `apply`, `verify` or `rollback`
+/// * `target` is the version to roll back to
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MigrationsConfig {
+    pub mode: String,
+    pub target: u32,
+}
+
+impl Default for MigrationsConfig {
+    fn default() -> Self {
+        Self {
+            mode: "apply".to_string(),
+            target: 0,
+        }
+    }
+}
+
+/// Soft delete configuration
+///
+/// ## Fields
+///
+/// * `retention_days` is the number of days a soft-deleted record can be restored before it is purged
+/// * `purge_interval_secs` is the number of seconds between two runs of the purge job
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct SoftDeleteConfig {
+    pub retention_days: u32,
+    pub purge_interval_secs: u64,
+}
+
+impl Default for SoftDeleteConfig {
+    fn default() -> Self {
+        Self {
+            retention_days: 30,
+            purge_interval_secs: 3600,
+        }
+    }
+}
+
+/// WebSocket signaling configuration
+///
+/// ## Fields
+///
+/// * `messages_per_second` is the number of messages a connection is allowed every second
+/// * `burst_size` is the number of messages a connection is allowed in a burst
+/// * `max_message_size` is the maximum size of a message in bytes
+/// * `heartbeat_secs` is the number of idle seconds before the server pings the client,
+///   which is disconnected after twice as long without any message
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct SignalingConfig {
+    pub messages_per_second: u32,
+    pub burst_size: u32,
+    pub max_message_size: usize,
+    pub heartbeat_secs: u64,
+}
+
+impl Default for SignalingConfig {
+    fn default() -> Self {
+        Self {
+            messages_per_second: 10,
+            burst_size: 50,
+            max_message_size: 16384,
+            heartbeat_secs: 15,
+        }
+    }
+}
+
+/// WebUntis client configuration
+///
+/// ## Fields
+///
+/// * `client_name` identifies the application to WebUntis servers
+/// * `timeout_secs` is the number of seconds before a request to WebUntis fails
+/// * `key` is the base64 encoded key of 32 bytes encrypting the stored credentials,
+///   accounts cannot be linked without it
+/// * `sync_interval_secs` is the number of seconds between two synchronisations of the timetables
+/// * `sync_days_before` is the number of past days kept in sync
+/// * `sync_days_ahead` is the number of upcoming days kept in sync
+/// * `refresh_cooldown_secs` is the number of seconds a user waits between manual refreshes
+/// * `allowed_hosts` are the servers accounts may be linked to, exact host names or wildcard
+///   subdomains (`*.webuntis.com`), IP addresses and `localhost` only if listed as such
+/// * `allow_http` is whether servers may be contacted without TLS, for tests
+/// * `timezone` is the IANA time zone of the schools, WebUntis gives their local times
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct UntisConfig {
+    pub client_name: String,
+    pub timeout_secs: u64,
+    pub key: Option<String>,
+    pub sync_interval_secs: u64,
+    pub sync_days_before: u32,
+    pub sync_days_ahead: u32,
+    pub refresh_cooldown_secs: u64,
+    pub allowed_hosts: Vec<String>,
+    pub allow_http: bool,
+    pub timezone: String,
+}
+
+impl Default for UntisConfig {
+    fn default() -> Self {
+        Self {
+            client_name: "backend".to_string(),
+            timeout_secs: 10,
+            key: None,
+            sync_interval_secs: 900,
+            sync_days_before: 7,
+            sync_days_ahead: 14,
+            refresh_cooldown_secs: 60,
+            allowed_hosts: vec!["*.webuntis.com".to_string()],
+            allow_http: false,
+            timezone: "Europe/Berlin".to_string(),
+        }
+    }
+}
+
+impl UntisConfig {
+    /// Returns the cipher of the stored credentials
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Config` - There is no key, accounts cannot be linked
+    pub fn cipher(&self) -> Result<Cipher, Error> {
+        let key = self.key.as_deref().ok_or_else(|| {
+            Error::Config("UNTIS_KEY is not set, WebUntis accounts cannot be linked".to_string())
+        })?;
+
+        Cipher::new(key)
+    }
+
+    /// Returns the time zone of the schools
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Config` - The time zone is not an IANA time zone
+    pub fn time_zone(&self) -> Result<Tz, Error> {
+        self.timezone.parse().map_err(|_| {
+            Error::Config(format!(
+                "Untis time zone `{}` must be an IANA time zone, e.g. Europe/Berlin",
+                self.timezone
+            ))
+        })
+    }
+}
+
+/// Application configuration, loaded once at startup
+///
+/// Values are read from the defaults, then the TOML file at `CONFIG_FILE`
+/// (`config.toml` if it exists), then the environment.
+///
+/// ## Fields
+///
+/// * `port` is the port the server listens on
+/// * `database` is the database configuration
+/// * `session` is the session configuration
+/// * `cors` is the CORS configuration
+/// * `rate_limit` is the rate limit configuration
+/// * `tls` is the TLS configuration
+/// * `proxy` is the reverse proxy configuration
+/// * `migrations` is the migrations configuration
+/// * `soft_delete` is the soft delete configuration
+/// * `signaling` is the WebSocket signaling configuration
+/// * `untis` is the WebUntis client configuration
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    pub port: u16,
+    pub database: DatabaseConfig,
+    pub session: SessionConfig,
+    pub cors: CorsConfig,
+    pub rate_limit: RateLimitConfig,
+    pub tls: TlsConfig,
+    pub proxy: ProxyConfig,
+    pub migrations: MigrationsConfig,
+    pub soft_delete: SoftDeleteConfig,
+    pub signaling: SignalingConfig,
+    pub untis: UntisConfig,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            port: 8080,
+            database: DatabaseConfig::default(),
+            session: SessionConfig::default(),
+            cors: CorsConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            tls: TlsConfig::default(),
+            proxy: ProxyConfig::default(),
+            migrations: MigrationsConfig::default(),
+            soft_delete: SoftDeleteConfig::default(),
+            signaling: SignalingConfig::default(),
+            untis: UntisConfig::default(),
+        }
+    }
+}
+
+/// Parse an environment variable, naming it in the error
+fn parse_var<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("{key} has an invalid value `{value}`"))
+}
+
+/// Parse a comma separated environment variable
+fn parse_list<T: FromStr>(key: &str, value: &str) -> Result<Vec<T>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| parse_var(key, item))
+        .collect()
+}
+
+impl AppConfig {
+    /// Load the configuration from the TOML file and the environment, then validate it
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<AppConfig, Error>` - The validated configuration
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Config` - The file cannot be read or parsed, or a value is invalid
+    pub fn load() -> Result<Self, Error> {
+        let vars: HashMap<String, String> = env::vars().collect();
+
+        Self::from_vars(&vars)
+    }
+
+    /// Load the configuration from the TOML file and the given environment variables,
+    /// then validate it
+    ///
+    /// ## Arguments
+    ///
+    /// * `vars` - The environment variables
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<AppConfig, Error>` - The validated configuration
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Config` - The file cannot be read or parsed, or the list of the variables
+    ///   that cannot be parsed and of the invalid values
+    pub fn from_vars(vars: &HashMap<String, String>) -> Result<Self, Error> {
+        let mut config = match vars.get("CONFIG_FILE").filter(|path| !path.is_empty()) {
+            Some(path) => Self::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => Self::from_file(DEFAULT_CONFIG_FILE)?,
+            None => Self::default(),
+        };
+        let mut errors = config.apply_env(vars);
+        errors.extend(config.errors());
+
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(Error::Config(errors.join("; ")))
+        }
+    }
+
+    /// Read the configuration from a TOML file
+    ///
+    /// ## Arguments
+    ///
+    /// * `path` - The path of the file
+    fn from_file(path: &str) -> Result<Self, Error> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| Error::Config(format!("cannot read {path}: {e}")))?;
+
+        toml::from_str(&content).map_err(|e| Error::Config(format!("cannot parse {path}: {e}")))
+    }
+
+    /// Override the configuration with the environment variables that are set
+    ///
+    /// Empty variables, as left by `.env.example`, are ignored
+    ///
+    /// ## Arguments
+    ///
+    /// * `vars` - The environment variables
+    ///
+    /// ## Returns
+    ///
+    /// * `Vec<String>` - Why each variable that cannot be parsed was rejected
+    fn apply_env(&mut self, vars: &HashMap<String, String>) -> Vec<String> {
+        let mut keys: Vec<&String> = vars.keys().collect();
+        keys.sort();
+
+        keys.into_iter()
+            .filter_map(|key| self.apply_var(key, &vars[key]).err())
+            .collect()
+    }
+
+    /// Override the configuration with an environment variable, if it is set
+    ///
+    /// ## Arguments
+    ///
+    /// * `key` - The name of the variable
+    /// * `value` - The value of the variable
+    ///
+    /// ## Errors
+    ///
+    /// * `String` - The value cannot be parsed
+    fn apply_var(&mut self, key: &str, value: &str) -> Result<(), String> {
+        if value.is_empty() {
+            return Ok(());
+        }
+        match key {
+            "PORT" => self.port = parse_var(key, value)?,
+            "DB_LOCATION" => self.database.location = value.to_string(),
+            "DB_USERNAME" => self.database.username = value.to_string(),
+            "DB_PASSWORD" => self.database.password = value.to_string(),
+            "DB_NAMESPACE" => self.database.namespace = value.to_string(),
+            "DB_DATABASE" => self.database.database = value.to_string(),
+            "COOKIE_KEY" => self.session.cookie_key = Some(value.to_string()),
+            "COOKIE_SECURE" => self.session.cookie_secure = parse_var(key, value)?,
+            "CORS_ORIGINS" => self.cors.allowed_origins = parse_list(key, value)?,
+            "CORS_METHODS" => self.cors.allowed_methods = parse_list(key, value)?,
+            "CORS_HEADERS" => self.cors.allowed_headers = parse_list(key, value)?,
+            "CORS_EXPOSED_HEADERS" => self.cors.exposed_headers = parse_list(key, value)?,
+            "CORS_MAX_AGE" => self.cors.max_age = Some(parse_var(key, value)?),
+            "RATE_LIMIT_PER_SECOND" => self.rate_limit.per_second = parse_var(key, value)?,
+            "RATE_LIMIT_BURST_SIZE" => self.rate_limit.burst_size = parse_var(key, value)?,
+            "TLS_ENABLED" => self.tls.enabled = parse_var(key, value)?,
+            "TLS_CERT_PATH" => self.tls.cert_path = value.to_string(),
+            "TLS_KEY_PATH" => self.tls.key_path = value.to_string(),
+            "REVERSE_PROXY" => self.proxy.reverse_proxy = Some(parse_var(key, value)?),
+            "WHITELIST" => self.proxy.whitelist = parse_list(key, value)?,
+            "MIGRATION_MODE" => self.migrations.mode = value.to_string(),
+            "MIGRATION_TARGET" => self.migrations.target = parse_var(key, value)?,
+            "SOFT_DELETE_RETENTION_DAYS" => {
+                self.soft_delete.retention_days = parse_var(key, value)?
+            }
+            "SOFT_DELETE_PURGE_INTERVAL" => {
+                self.soft_delete.purge_interval_secs = parse_var(key, value)?
+            }
+            "SIGNALING_MESSAGES_PER_SECOND" => {
+                self.signaling.messages_per_second = parse_var(key, value)?
+            }
+            "SIGNALING_BURST_SIZE" => self.signaling.burst_size = parse_var(key, value)?,
+            "SIGNALING_MAX_MESSAGE_SIZE" => {
+                self.signaling.max_message_size = parse_var(key, value)?
+            }
+            "SIGNALING_HEARTBEAT" => self.signaling.heartbeat_secs = parse_var(key, value)?,
+            "UNTIS_CLIENT_NAME" => self.untis.client_name = value.to_string(),
+            "UNTIS_TIMEOUT" => self.untis.timeout_secs = parse_var(key, value)?,
+            "UNTIS_KEY" => self.untis.key = Some(value.to_string()),
+            "UNTIS_SYNC_INTERVAL" => self.untis.sync_interval_secs = parse_var(key, value)?,
+            "UNTIS_SYNC_DAYS_BEFORE" => self.untis.sync_days_before = parse_var(key, value)?,
+            "UNTIS_SYNC_DAYS_AHEAD" => self.untis.sync_days_ahead = parse_var(key, value)?,
+            "UNTIS_REFRESH_COOLDOWN" => self.untis.refresh_cooldown_secs = parse_var(key, value)?,
+            "UNTIS_ALLOWED_HOSTS" => self.untis.allowed_hosts = parse_list(key, value)?,
+            "UNTIS_ALLOW_HTTP" => self.untis.allow_http = parse_var(key, value)?,
+            "UNTIS_TIMEZONE" => self.untis.timezone = value.to_string(),
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Returns why each invalid value of the configuration is invalid
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if self.port == 0 {
+            errors.push("port must not be 0".to_string());
+        }
+        if self.database.location.is_empty() {
+            errors.push("database location must not be empty".to_string());
+        }
+        if self.database.namespace.is_empty() || self.database.database.is_empty() {
+            errors.push("database namespace and database must not be empty".to_string());
+        }
+        if let Some(key) = &self.session.cookie_key {
+            if key.len() < COOKIE_KEY_MIN_LENGTH {
+                errors.push(format!(
+                    "cookie key must be at least {COOKIE_KEY_MIN_LENGTH} bytes long"
+                ));
+            }
+        }
+        for origin in &self.cors.allowed_origins {
+            if !(origin.starts_with("http://") || origin.starts_with("https://")) {
+                errors.push(format!("CORS origin `{origin}` must start with http:// or https://"));
+            } else if origin.matches('*').count() > usize::from(cors::is_wildcard(origin)) {
+                errors.push(format!(
+                    "CORS origin `{origin}` may only use a wildcard as its first subdomain, e.g. https://*.example.com"
+                ));
+            }
+        }
+        for method in &self.cors.allowed_methods {
+            if Method::from_bytes(method.as_bytes()).is_err() {
+                errors.push(format!("CORS method `{method}` is not a valid HTTP method"));
+            }
+        }
+        for header in self.cors.allowed_headers.iter().chain(&self.cors.exposed_headers) {
+            if HeaderName::from_bytes(header.as_bytes()).is_err() {
+                errors.push(format!("CORS header `{header}` is not a valid header name"));
+            }
+        }
+        if self.rate_limit.per_second == 0 || self.rate_limit.burst_size == 0 {
+            errors.push("rate limit period and burst size must be greater than 0".to_string());
+        }
+        if self.tls.enabled {
+            for path in [&self.tls.cert_path, &self.tls.key_path] {
+                if !Path::new(path).exists() {
+                    errors.push(format!("TLS file `{path}` does not exist"));
+                }
+            }
+        }
+        if cfg!(feature = "proxy") && self.proxy.reverse_proxy.is_none() {
+            errors.push("reverse proxy address is required with the proxy feature".to_string());
+        }
+        if let Err(e) = self.migration_mode() {
+            errors.push(e.to_string());
+        }
+        if self.soft_delete.purge_interval_secs == 0 {
+            errors.push("soft delete purge interval must be greater than 0".to_string());
+        }
+        let signaling = &self.signaling;
+        if signaling.messages_per_second == 0 || signaling.burst_size == 0 {
+            errors.push("signaling rate and burst size must be greater than 0".to_string());
+        }
+        if signaling.max_message_size == 0 || signaling.heartbeat_secs == 0 {
+            errors.push(
+                "signaling message size and heartbeat must be greater than 0".to_string(),
+            );
+        }
+        if self.untis.client_name.trim().is_empty() {
+            errors.push("Untis client name must not be empty".to_string());
+        }
+        if self.untis.timeout_secs == 0 {
+            errors.push("Untis timeout must be greater than 0".to_string());
+        }
+        if self.untis.sync_interval_secs == 0 {
+            errors.push("Untis sync interval must be greater than 0".to_string());
+        }
+        let untis = &self.untis;
+        let sync_days = untis.sync_days_before.checked_add(untis.sync_days_ahead);
+        if !sync_days.is_some_and(|days| days <= MAX_SYNC_DAYS) {
+            errors.push(format!(
+                "Untis sync window must not be longer than {MAX_SYNC_DAYS} days"
+            ));
+        }
+        if self.untis.allowed_hosts.is_empty() {
+            errors.push("Untis allowed hosts must not be empty".to_string());
+        }
+        for host in &self.untis.allowed_hosts {
+            let name = host.strip_prefix("*.").unwrap_or(host);
+            if name.is_empty() || name.contains(['*', '/']) {
+                errors.push(format!(
+                    "Untis allowed host `{host}` must be a host name, e.g. *.webuntis.com"
+                ));
+            }
+        }
+        if let Err(Error::Config(e)) = untis.time_zone() {
+            errors.push(e);
+        }
+        if let Some(key) = &self.untis.key {
+            if let Err(Error::Config(e)) = Cipher::new(key) {
+                errors.push(format!("Untis {e}"));
+            }
+        }
+
+        errors
+    }
+
+    /// Returns the migration mode
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Config` - The mode is unknown
+    pub fn migration_mode(&self) -> Result<MigrationMode, Error> {
+        match self.migrations.mode.as_str() {
+            "apply" => Ok(MigrationMode::Apply),
+            "verify" => Ok(MigrationMode::Verify),
+            "rollback" => Ok(MigrationMode::Rollback(self.migrations.target)),
+            other => Err(Error::Config(format!(
+                "migration mode `{other}` must be apply, verify or rollback"
+            ))),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+// cors.rs
+use actix_cors::Cors;
+use actix_web::http::header::HeaderValue;
+
+use crate::config::CorsConfig;
+
+/// Check whether an origin matches an allowed origin pattern
+///
+/// A pattern is either an exact origin (`https://app.example.com`) or a wildcard
+/// subdomain origin (`https://*.example.com`), which matches any subdomain depth
+/// but not the bare domain itself.
+///
+/// ## Arguments
+///
+/// * `pattern` - The allowed origin pattern
+/// * `origin` - The `Origin` header of the request
+///
+/// ## Returns
+///
+/// * `bool` - True if the origin is allowed by the pattern
+pub fn origin_matches(pattern: &str, origin: &str) -> bool {
+    let Some((scheme, host)) = pattern.split_once("://*.") else {
+        return pattern.eq_ignore_ascii_case(origin);
+    };
+    let Some((origin_scheme, origin_host)) = origin.split_once("://") else {
+        return false;
+    };
+
+    let origin_host = origin_host.to_ascii_lowercase();
+    let suffix = format!(".{}", host.to_ascii_lowercase());
+
+    origin_scheme.eq_ignore_ascii_case(scheme)
+        && origin_host.len() > suffix.len()
+        && origin_host.ends_with(&suffix)
+}
+
+/// Check whether an origin pattern is a wildcard subdomain pattern
+///
+/// ## Arguments
+///
+/// * `pattern` - The allowed origin pattern
+pub fn is_wildcard(pattern: &str) -> bool {
+    pattern.contains("://*.")
+}
+
+/// Build the CORS middleware from the configuration
+///
+/// ## Arguments
+///
+/// * `config` - The CORS configuration
+///
+/// ## Returns
+///
+/// * `Cors` - The CORS middleware, supporting credentials
+pub fn cors(config: &CorsConfig) -> Cors {
+    let (wildcards, exact): (Vec<String>, Vec<String>) = config
+        .allowed_origins
+        .iter()
+        .cloned()
+        .partition(|origin| is_wildcard(origin));
+
+    let cors = Cors::default()
+        .supports_credentials()
+        .allowed_methods(config.allowed_methods.iter().map(String::as_str))
+        .allowed_headers(config.allowed_headers.iter().map(String::as_str))
+        .max_age(config.max_age);
+    let cors = if config.exposed_headers.is_empty() {
+        cors
+    } else {
+        cors.expose_headers(config.exposed_headers.iter().map(String::as_str))
+    };
+    let cors = exact
+        .iter()
+        .fold(cors, |cors, origin| cors.allowed_origin(origin));
+
+    if wildcards.is_empty() {
+        return cors;
+    }
+
+    cors.allowed_origin_fn(move |origin: &HeaderValue, _| {
+        origin
+            .to_str()
+            .map(|origin| wildcards.iter().any(|pattern| origin_matches(pattern, origin)))
+            .unwrap_or(false)
+    })
+}
@@ -1,25 +1,84 @@
 use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use log::error;
+use serde::{Deserialize, Serialize};
 
 /// Error type for the API
-/// 
+///
 /// ## Fields
-/// 
+///
 /// * `CtxFail` is the error type for when the context fails
 /// * `UntisError` is the error type for when fetching from Untis fails
+/// * `Validation` is the error type for when the request is invalid
+/// * `Conflict` is the error type for when a unique value is already taken
+/// * `NotFound` is the error type for when a record does not exist
+/// * `Transaction` is the error type for when a transaction failed and was rolled back
+/// * `Live` is the error type for when a live query cannot be started
+/// * `RateLimited` is the error type for when a client sends too many messages
+/// * `InvalidCredentials` is the error type for when the email or password is wrong
+/// * `InactiveUser` is the error type for when the user is inactive
+/// * `Session` is the error type for when the session cannot be attached
+/// * `PasswordHash` is the error type for when hashing a password fails
+/// * `Encryption` is the error type for when a stored secret cannot be encrypted or decrypted
+/// * `NoRecord` is the error type for when the database returns no record
+/// * `Migration` is the error type for when a migration cannot be applied or verified
+/// * `Config` is the error type for when the configuration is invalid
 /// * `Surreal` is the error type for SurrealDB
 /// * `IO` is the error type for IO
 /// * `Reqwest` is the error type for Reqwest
-/// 
+///
 /// ## Methods
-/// 
+///
+/// * `code` returns the stable error code
 /// * `error_response` returns the error response
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Fail to get Ctx")]
     CtxFail,
 
-    #[error("Fetching from Untis failed")]
-    UntisError,
+    #[error("Fetching from Untis failed: {0}")]
+    UntisError(String),
+
+    #[error("Invalid request: {0}")]
+    Validation(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Transaction failed: {0}")]
+    Transaction(String),
+
+    #[error("Live query failed: {0}")]
+    Live(String),
+
+    #[error("Too many messages, slow down")]
+    RateLimited,
+
+    #[error("Invalid email or password")]
+    InvalidCredentials,
+
+    #[error("User is inactive")]
+    InactiveUser,
+
+    #[error("Failed to attach the session: {0}")]
+    Session(String),
+
+    #[error("Failed to hash password")]
+    PasswordHash,
+
+    #[error("Encryption failed: {0}")]
+    Encryption(String),
+
+    #[error("The database returned no record")]
+    NoRecord,
+
+    #[error("Migration failed: {0}")]
+    Migration(String),
+
+    #[error("Invalid configuration: {0}")]
+    Config(String),
 
     #[error(transparent)]
     Surreal(#[from] surrealdb::Error),
@@ -31,27 +90,150 @@ pub enum Error {
     Reqwest(#[from] reqwest::Error),
 }
 
+impl Error {
+    /// Returns the stable, machine-readable code of the error
+    ///
+    /// Clients may match on it, so never change an existing code
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::CtxFail => "unauthenticated",
+            Error::UntisError(_) => "untis_failed",
+            Error::Validation(_) => "validation_failed",
+            Error::Conflict(_) => "conflict",
+            Error::NotFound(_) => "not_found",
+            Error::Transaction(_) => "transaction_failed",
+            Error::Live(_) => "live_query_failed",
+            Error::RateLimited => "rate_limited",
+            Error::InvalidCredentials => "invalid_credentials",
+            Error::InactiveUser => "inactive_user",
+            Error::Session(_) => "session_failed",
+            Error::PasswordHash => "password_hash_failed",
+            Error::Encryption(_) => "encryption_failed",
+            Error::NoRecord => "no_record",
+            Error::Migration(_) => "migration_failed",
+            Error::Config(_) => "invalid_configuration",
+            Error::Surreal(_) => "database_error",
+            Error::IO(_) => "io_error",
+            Error::Reqwest(_) => "upstream_error",
+        }
+    }
+
+    /// Map a SurrealDB unique index violation to a `Conflict`
+    ///
+    /// ## Returns
+    ///
+    /// * `Error::Conflict` naming the index if the error is a unique index violation
+    /// * `self` otherwise
+    pub fn map_index_conflict(self) -> Self {
+        match self {
+            Error::Surreal(ref err) => {
+                // SurrealDB reports: Database index `email` already contains ...
+                let msg = err.to_string();
+                if !msg.contains("already contains") {
+                    return self;
+                }
+                let index = msg.split('`').nth(1).unwrap_or("value");
+                Error::Conflict(format!("{index} is already in use"))
+            }
+            _ => self,
+        }
+    }
+
+    /// Map a SurrealDB error caused by the written data to a `Validation`
+    ///
+    /// ## Returns
+    ///
+    /// * `Error::Validation` if a field assertion or type check failed, or a JSON Patch
+    ///   operation did not apply
+    /// * `self` otherwise, e.g. when the database cannot be reached
+    pub fn map_invalid_data(self) -> Self {
+        match self {
+            Error::Surreal(ref err) => {
+                // SurrealDB reports: Found ... for field `name`, with record ..., and
+                // The JSON Patch contains invalid operations / Given test operation failed
+                let msg = err.to_string();
+                let invalid = ["for field `", "JSON Patch"]
+                    .iter()
+                    .any(|pattern| msg.contains(pattern));
+                if !invalid {
+                    return self;
+                }
+                Error::Validation(format!("the data is invalid, {msg}"))
+            }
+            _ => self,
+        }
+    }
+}
+
+/// RFC 7807 problem details, the body of every error response
+///
+/// ## Fields
+///
+/// * `type` is `about:blank`, the `title` is the HTTP status
+/// * `title` is the reason phrase of the status
+/// * `status` is the HTTP status code
+/// * `detail` is a message safe to show to the client
+/// * `code` is the stable, machine-readable error code
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    pub code: String,
+}
+
 /// The error response
-/// 
+///
 /// ## Error response
-/// 
-/// * `404 Not Found` if the status code is 404
-/// * `403 Forbidden` if the status code is 403
-/// * `409 Conflict` if the status code is 409
-/// * `500 Internal Server Error` if the status code is 500
-/// * `500 Internal Server Error` if the status code is anything else
+///
+/// Every error is rendered as `application/problem+json`.
+/// Server errors only expose a generic detail, the full error is logged instead.
 impl ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Error::Validation(_) => StatusCode::BAD_REQUEST,
+            Error::CtxFail | Error::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            Error::InactiveUser => StatusCode::FORBIDDEN,
+            Error::NotFound(_) => StatusCode::NOT_FOUND,
+            Error::Conflict(_) => StatusCode::CONFLICT,
+            Error::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            Error::UntisError(_) | Error::Reqwest(_) => StatusCode::BAD_GATEWAY,
+            Error::Session(_)
+            | Error::PasswordHash
+            | Error::Encryption(_)
+            | Error::NoRecord
+            | Error::Transaction(_)
+            | Error::Live(_)
+            | Error::Migration(_)
+            | Error::Config(_)
+            | Error::Surreal(_)
+            | Error::IO(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
     fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
-        let code = self.status_code();
-        match code {
-            StatusCode::NOT_FOUND => HttpResponse::NotFound().body(format!("404 Not Found\n{self}")),
-            StatusCode::FORBIDDEN => HttpResponse::Forbidden().body(format!("403 Forbidden\n{self}")),
-            StatusCode::CONFLICT => HttpResponse::Conflict().body(format!("409 Conflict\n{self}")),
-            StatusCode::INTERNAL_SERVER_ERROR => {
-                HttpResponse::InternalServerError().body(format!("500 Internal Server Error\n{self}"))
+        let status = self.status_code();
+
+        let detail = if status.is_server_error() {
+            error!("{status} [{}]: {self:?}", self.code());
+            match status {
+                StatusCode::BAD_GATEWAY => "An upstream service failed".to_string(),
+                _ => "An internal error occurred".to_string(),
             }
-            // These 2⭥ are seperate if we ever wanna change the default/add a new code
-            _ => HttpResponse::InternalServerError().body(format!("500 Internal Server Error\n{self}")),
-        }
+        } else {
+            self.to_string()
+        };
+
+        HttpResponse::build(status)
+            .content_type("application/problem+json")
+            .json(ProblemDetails {
+                kind: "about:blank".to_string(),
+                title: status.canonical_reason().unwrap_or("Unknown").to_string(),
+                status: status.as_u16(),
+                detail,
+                code: self.code().to_string(),
+            })
     }
-}
\ No newline at end of file
+}
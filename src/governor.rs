@@ -4,17 +4,26 @@ use actix_governor::{KeyExtractor, SimpleKeyExtractionError};
 use actix_web::web;
 use log::error;
 
-use crate::utils::env::get_env_or;
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// Nginx configuration for IP address extraction
 /// 
 /// ## Fields
 /// 
-/// * `proxy_ip` is the IP address of the reverse proxy
 /// * `whitelisted` is a list of whitelisted IP addresses
-/// * `key_name` is the name of the key
-pub(super) struct NginxIpKeyExctrator;
+pub(super) struct NginxIpKeyExctrator {
+    whitelisted: Vec<IpAddr>,
+}
+
+impl NginxIpKeyExctrator {
+    /// Creates a key extractor exempting the given IP addresses
+    /// 
+    /// ## Parameters
+    /// 
+    /// * `whitelisted` is a list of whitelisted IP addresses
+    pub(super) fn new(whitelisted: Vec<IpAddr>) -> Self {
+        Self { whitelisted }
+    }
+}
 
 /// Macro rule to create a SimpleKeyExtractionError with a static string if IP address extraction fails
 macro_rules! couldntExtract {
@@ -120,8 +129,7 @@ impl KeyExtractor for NginxIpKeyExctrator {
     /// 
     /// Returns a list of whitelisted IP addresses
     fn whitelisted_keys(&self) -> Vec<Self::Key> {
-        let whitelisted = get_env_or("WHITELIST", "127.0.0.1").parse::<IpAddr>().unwrap();
-        vec![whitelisted]
+        self.whitelisted.clone()
     }
 
     /// Returns the name of the key
@@ -0,0 +1,3 @@
+// jobs/mod.rs
+pub mod purge;
+pub mod timetable;
@@ -0,0 +1,85 @@
+// jobs/purge.rs
+use std::time::Duration;
+
+use actix_web::rt;
+use log::{error, info};
+use surrealdb::sql::Value;
+
+use crate::{
+    config::SoftDeleteConfig,
+    models::{model::DBConnection, user_model::User},
+    prelude::Error,
+    repository::transaction::Transaction,
+};
+
+/// Ids of the users deleted before `$before`
+const PURGED: &str =
+    "(SELECT VALUE id FROM users WHERE deleted_at != NONE AND deleted_at < $before)";
+
+/// Tables of the records belonging to a user through their `user` field
+const DEPENDENTS: [&str; 6] = [
+    "presence",
+    "notification",
+    "lesson",
+    "timetable_sync",
+    "calendar_token",
+    "untis_account",
+];
+
+/// Permanently delete every user soft-deleted longer ago than the retention window
+///
+/// Their contacts, presence, notifications, lessons, synchronisation state, calendar feed and
+/// WebUntis account are deleted in the same transaction, so nothing points at a purged user.
+///
+/// ## Arguments
+///
+/// * `db` - The database connection
+/// * `retention_days` - The number of days a deleted record is kept
+///
+/// ## Returns
+///
+/// * `Result<usize, Error>` - The number of purged users
+///
+/// ## Errors
+///
+/// * `Error::Transaction` - A record cannot be deleted, none was
+/// * `Error` - The error returned by the database
+pub async fn purge(db: &DBConnection, retention_days: u32) -> Result<usize, Error> {
+    let before = (chrono::Utc::now() - chrono::Duration::days(retention_days.into())).to_rfc3339();
+
+    let mut tx = Transaction::new();
+    tx.bind("before", before)?;
+    tx.query::<Value>(format!(
+        "DELETE contact WHERE in INSIDE {PURGED} OR out INSIDE {PURGED}"
+    ));
+    for table in DEPENDENTS {
+        tx.query::<Value>(format!("DELETE {table} WHERE user INSIDE {PURGED}"));
+    }
+    // The users go last, the other statements look them up
+    let users = tx.query::<User>(format!("DELETE users WHERE id INSIDE {PURGED} RETURN BEFORE"));
+    let mut committed = tx.commit(db).await?;
+
+    Ok(committed.rows(users)?.len())
+}
+
+/// Spawn the job purging soft-deleted records on the configured interval
+///
+/// ## Arguments
+///
+/// * `db` - The database connection
+/// * `config` - The soft delete configuration
+pub fn spawn(db: DBConnection, config: SoftDeleteConfig) {
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(Duration::from_secs(config.purge_interval_secs));
+
+        loop {
+            interval.tick().await;
+
+            match purge(&db, config.retention_days).await {
+                Ok(0) => {}
+                Ok(purged) => info!("🧹 Purged {purged} deleted records"),
+                Err(e) => error!("🔥 Failed to purge deleted records: {e}"),
+            }
+        }
+    });
+}
@@ -0,0 +1,361 @@
+// jobs/timetable.rs
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use actix_web::rt;
+use chrono::{Days, NaiveDate, Utc};
+use log::{debug, error, info, warn};
+use surrealdb::sql::Thing;
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+use crate::{
+    config::UntisConfig,
+    models::{
+        model::DBConnection,
+        notification_model::Notification,
+        timetable_model::{Lesson, LessonChange, LessonCreate, LessonStatus, TimetableSync},
+        untis_model::UntisAccount,
+    },
+    prelude::Error,
+    untis::{
+        client::UntisClient,
+        types::{ElementRef, Period, PeriodCode},
+    },
+    utils::crypto::Cipher,
+};
+
+/// Locks of the accounts being synchronised, shared by the job and the refreshes
+///
+/// Two synchronisations of an account at once would both differ from the same snapshot and
+/// notify every change twice, the second one waits for the first instead.
+///
+/// ## Fields
+///
+/// * `accounts` are the locks of the accounts, by user id
+#[derive(Clone, Default)]
+pub struct SyncLocks {
+    accounts: Arc<Mutex<HashMap<String, Arc<AsyncMutex<()>>>>>,
+}
+
+impl SyncLocks {
+    /// Create the locks, no account being synchronised
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait until no other synchronisation of an account runs
+    ///
+    /// ## Arguments
+    ///
+    /// * `user` - The id of the user of the account
+    ///
+    /// ## Returns
+    ///
+    /// * `OwnedMutexGuard<()>` - The lock of the account, released when dropped
+    async fn lock(&self, user: &Thing) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut accounts = self.accounts.lock().unwrap();
+            // Locks nobody holds or waits for are dropped, the map only keeps running syncs
+            accounts.retain(|_, lock| Arc::strong_count(lock) > 1);
+            accounts.entry(user.to_string()).or_default().clone()
+        };
+
+        lock.lock_owned().await
+    }
+}
+
+/// Names of the elements of a school, by id
+///
+/// Students may not be allowed to read every list, elements missing from one are
+/// named by their id.
+#[derive(Default)]
+struct Names {
+    subjects: HashMap<i64, String>,
+    teachers: HashMap<i64, String>,
+    rooms: HashMap<i64, String>,
+    klassen: HashMap<i64, String>,
+}
+
+impl Names {
+    /// Fetch the names of the elements of the school
+    async fn fetch(client: &UntisClient) -> Self {
+        let or_empty = |list: &str, e: Error| {
+            debug!("Cannot read the {list} of the school: {e}");
+            HashMap::new()
+        };
+
+        Self {
+            subjects: match client.subjects().await {
+                Ok(subjects) => subjects
+                    .into_iter()
+                    .map(|s| {
+                        let name = if s.long_name.is_empty() { s.name } else { s.long_name };
+                        (s.id, name)
+                    })
+                    .collect(),
+                Err(e) => or_empty("subjects", e),
+            },
+            teachers: match client.teachers().await {
+                Ok(teachers) => teachers.into_iter().map(|t| (t.id, t.name)).collect(),
+                Err(e) => or_empty("teachers", e),
+            },
+            rooms: match client.rooms().await {
+                Ok(rooms) => rooms.into_iter().map(|r| (r.id, r.name)).collect(),
+                Err(e) => or_empty("rooms", e),
+            },
+            klassen: match client.klassen().await {
+                Ok(klassen) => klassen.into_iter().map(|k| (k.id, k.name)).collect(),
+                Err(e) => or_empty("classes", e),
+            },
+        }
+    }
+}
+
+/// Name the elements of a period
+fn name(names: &HashMap<i64, String>, elements: &[ElementRef]) -> Vec<String> {
+    elements
+        .iter()
+        .map(|element| names.get(&element.id).cloned().unwrap_or_else(|| element.id.to_string()))
+        .collect()
+}
+
+/// Name the elements originally planned for a period, e.g. the teachers being substituted
+fn name_original(names: &HashMap<i64, String>, elements: &[ElementRef]) -> Vec<String> {
+    elements
+        .iter()
+        .filter_map(|element| element.orgid)
+        .map(|id| names.get(&id).cloned().unwrap_or_else(|| id.to_string()))
+        .collect()
+}
+
+/// Convert a period to the lesson of a user, `None` if its date or times are invalid
+fn lesson(user: &Thing, period: Period, names: &Names, now: &str) -> Option<LessonCreate> {
+    let (start, end) = (period.start()?, period.end()?);
+    let status = match period.code {
+        None | Some(PeriodCode::Regular) => LessonStatus::Regular,
+        Some(PeriodCode::Cancelled) => LessonStatus::Cancelled,
+        Some(PeriodCode::Irregular) => LessonStatus::Irregular,
+    };
+
+    Some(LessonCreate {
+        user: user.clone(),
+        period: period.id,
+        date: start.date().format("%Y-%m-%d").to_string(),
+        start: start.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        end: end.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        subjects: name(&names.subjects, &period.subjects),
+        teachers: name(&names.teachers, &period.teachers),
+        rooms: name(&names.rooms, &period.rooms),
+        klassen: name(&names.klassen, &period.klassen),
+        original_teachers: name_original(&names.teachers, &period.teachers),
+        original_rooms: name_original(&names.rooms, &period.rooms),
+        status,
+        text: period.lesson_text.filter(|text| !text.is_empty()),
+        substitution_text: period.substitution_text.filter(|text| !text.is_empty()),
+        info: period.info.filter(|text| !text.is_empty()),
+        updated_at: now.to_string(),
+    })
+}
+
+/// Returns the current day in the time zone of the schools
+///
+/// ## Arguments
+///
+/// * `config` - The WebUntis configuration
+///
+/// ## Errors
+///
+/// * `Error::Config` - The time zone is not an IANA time zone
+pub fn today(config: &UntisConfig) -> Result<NaiveDate, Error> {
+    Ok(Utc::now().with_timezone(&config.time_zone()?).date_naive())
+}
+
+/// Returns the first and last day kept in sync
+///
+/// ## Arguments
+///
+/// * `config` - The WebUntis configuration
+///
+/// ## Errors
+///
+/// * `Error::Config` - The time zone is not an IANA time zone
+pub fn window(config: &UntisConfig) -> Result<(NaiveDate, NaiveDate), Error> {
+    let today = today(config)?;
+    let from = today - Days::new(config.sync_days_before.into());
+    let to = today + Days::new(config.sync_days_ahead.into());
+
+    Ok((from, to))
+}
+
+/// Fetch the lessons of an account from WebUntis
+///
+/// ## Errors
+///
+/// * `Error::Encryption` - The credentials cannot be opened
+/// * `Error::Validation` - The credentials were rejected or the account has no timetable
+/// * `Error::UntisError` - The request failed
+async fn fetch(
+    config: &UntisConfig,
+    cipher: &Cipher,
+    account: &UntisAccount,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<LessonCreate>, Error> {
+    let credentials = account.credentials(cipher)?;
+    let mut client = UntisClient::new(&account.server, &account.school, config)?;
+    client
+        .authenticate(&credentials.username, &credentials.password)
+        .await?;
+
+    let periods = client.own_timetable(from, to).await;
+    let names = if periods.is_ok() {
+        Names::fetch(&client).await
+    } else {
+        Names::default()
+    };
+    if let Err(e) = client.logout().await {
+        warn!("Failed to log out of WebUntis after a synchronisation: {e}");
+    }
+
+    let now = Utc::now().to_rfc3339();
+    Ok(periods?
+        .into_iter()
+        .filter_map(|period| lesson(&account.user, period, &names, &now))
+        .collect())
+}
+
+/// Synchronise the timetable of an account, recording the outcome
+///
+/// Upcoming lessons cancelled, moved or substituted since the previous synchronisation are
+/// notified. The first snapshot of an account is not, it has nothing to differ from.
+/// Synchronisations of the same account run one after the other.
+///
+/// ## Arguments
+///
+/// * `db` - The database connection
+/// * `config` - The WebUntis configuration
+/// * `cipher` - The cipher of the stored credentials
+/// * `locks` - The locks of the accounts being synchronised
+/// * `account` - The account
+///
+/// ## Returns
+///
+/// * `Result<Vec<LessonChange>, Error>` - The differences with the previous snapshot
+///
+/// ## Errors
+///
+/// * `Error::Encryption` - The credentials cannot be opened
+/// * `Error::Validation` - The credentials were rejected or the account has no timetable
+/// * `Error::UntisError` - The request failed
+/// * `Error` - The error returned by the database
+pub async fn sync(
+    db: &DBConnection,
+    config: &UntisConfig,
+    cipher: &Cipher,
+    locks: &SyncLocks,
+    account: &UntisAccount,
+) -> Result<Vec<LessonChange>, Error> {
+    let _lock = locks.lock(&account.user).await;
+    let (first, last) = window(config)?;
+    let (from, to) = (first.format("%Y-%m-%d").to_string(), last.format("%Y-%m-%d").to_string());
+
+    let lessons = match fetch(config, cipher, account, first, last).await {
+        Ok(lessons) => lessons,
+        Err(e) => {
+            TimetableSync::record(db, &account.user, None, Some(e.to_string())).await?;
+            return Err(e);
+        }
+    };
+    let previous = TimetableSync::of(db, &account.user).await?;
+    let changes = Lesson::replace(db, &account.user, &from, &to, lessons).await?;
+    TimetableSync::record(db, &account.user, Some((&from, &to)), None).await?;
+
+    if previous.is_some_and(|sync| sync.synced_at.is_some()) {
+        let today = today(config)?.format("%Y-%m-%d").to_string();
+        let now = Utc::now().to_rfc3339();
+        let notifications = changes
+            .iter()
+            .filter(|change| change.lesson().date >= today)
+            .flat_map(|change| Notification::of_change(change, &now))
+            .collect();
+        Notification::create_all(db, notifications).await?;
+    }
+
+    Ok(changes)
+}
+
+/// Synchronise the timetable of every linked account, one after the other
+///
+/// ## Arguments
+///
+/// * `db` - The database connection
+/// * `config` - The WebUntis configuration
+/// * `cipher` - The cipher of the stored credentials
+/// * `locks` - The locks of the accounts being synchronised
+///
+/// ## Returns
+///
+/// * `Result<(usize, usize, usize), Error>` - The number of accounts synchronised and failed,
+///   and of lessons changed
+///
+/// ## Errors
+///
+/// * `Error` - The error returned by the database
+pub async fn sync_all(
+    db: &DBConnection,
+    config: &UntisConfig,
+    cipher: &Cipher,
+    locks: &SyncLocks,
+) -> Result<(usize, usize, usize), Error> {
+    let (mut synced, mut failed, mut changed) = (0, 0, 0);
+
+    for account in UntisAccount::all(db).await? {
+        match sync(db, config, cipher, locks, &account).await {
+            Ok(changes) => {
+                synced += 1;
+                changed += changes.len();
+            }
+            Err(e) => {
+                warn!("Failed to synchronise the timetable of {}: {e}", account.user);
+                failed += 1;
+            }
+        }
+    }
+
+    Ok((synced, failed, changed))
+}
+
+/// Spawn the job synchronising the timetables on the configured interval
+///
+/// Nothing is spawned without a key, no account can be linked then.
+///
+/// ## Arguments
+///
+/// * `db` - The database connection
+/// * `config` - The WebUntis configuration
+/// * `locks` - The locks of the accounts being synchronised, shared with the refreshes
+pub fn spawn(db: DBConnection, config: UntisConfig, locks: SyncLocks) {
+    let Ok(cipher) = config.cipher() else {
+        info!("📅 UNTIS_KEY is not set, timetables are not synchronised");
+        return;
+    };
+
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(Duration::from_secs(config.sync_interval_secs));
+
+        loop {
+            interval.tick().await;
+
+            match sync_all(&db, &config, &cipher, &locks).await {
+                Ok((0, 0, _)) => {}
+                Ok((synced, failed, changed)) => info!(
+                    "📅 Synchronised {synced} timetables, {changed} lessons changed, {failed} failed"
+                ),
+                Err(e) => error!("🔥 Failed to synchronise the timetables: {e}"),
+            }
+        }
+    });
+}
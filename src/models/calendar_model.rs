@@ -0,0 +1,120 @@
+// models/calendar_model.rs
+use backend_derive::Model;
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+use super::model::DBConnection;
+use crate::{
+    prelude::Error,
+    utils::crypto::{hash_token, random_token},
+};
+
+/// Calendar Token Struct, the token of the calendar feed of a user
+///
+/// The record shares the id of its user, `calendar_token:⟨id⟩` for `users:⟨id⟩`.
+/// Only the hash of the token is stored, the token is shown once when it is created.
+///
+/// ## Fields
+///
+/// * `ID` is the id of the user in the `calendar_token` table
+/// * `User` is the user
+/// * `TokenHash` is the SHA-256 hash of the token
+/// * `CreatedAt` is the date the token was created
+#[derive(Debug, Serialize, Deserialize, Clone, Model)]
+#[model(table = "calendar_token")]
+pub struct CalendarToken {
+    #[model(id)]
+    pub id: Thing,
+    #[model(create, unique)]
+    pub user: Thing,
+    #[model(create, unique)]
+    pub token_hash: String,
+    #[model(create)]
+    pub created_at: String,
+}
+
+impl CalendarToken {
+    /// Returns the id of the token of a user
+    pub fn id_of(user: &Thing) -> Thing {
+        Thing::from(("calendar_token", user.id.clone()))
+    }
+
+    /// Create the token of a user, revoking the previous one
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<String, Error>` - The token
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn rotate(db: &DBConnection, user: &Thing) -> Result<String, Error> {
+        let token = random_token();
+        let content = CalendarTokenCreate {
+            user: user.clone(),
+            token_hash: hash_token(&token),
+            created_at: chrono::Utc::now().to_rfc3339(),
+        };
+
+        db.query("UPDATE $id CONTENT $content")
+            .bind(("id", Self::id_of(user)))
+            .bind(("content", content))
+            .await?
+            .check()?;
+
+        Ok(token)
+    }
+
+    /// Get the user a token belongs to
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `token` - The token
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<Option<Thing>, Error>` - The id of the user, `None` if the token is unknown
+    ///   or the user was deleted
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn user_of(db: &DBConnection, token: &str) -> Result<Option<Thing>, Error> {
+        let mut res = db
+            .query(
+                "SELECT VALUE user FROM calendar_token \
+                WHERE token_hash = $hash AND user.deleted_at = NONE",
+            )
+            .bind(("hash", hash_token(token)))
+            .await?;
+        let user: Option<Thing> = res.take(0)?;
+
+        Ok(user)
+    }
+
+    /// Revoke the token of a user
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<bool, Error>` - Whether the user had a token
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn revoke(db: &DBConnection, user: &Thing) -> Result<bool, Error> {
+        let token: Option<CalendarToken> = db.delete(Self::id_of(user)).await?;
+
+        Ok(token.is_some())
+    }
+}
@@ -0,0 +1,531 @@
+// models/contact_model.rs
+use backend_derive::Model;
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+use super::{
+    model::DBConnection,
+    query::{ListQuery, Page},
+    user_model::{PublicProfile, User},
+};
+use crate::prelude::Error;
+
+/// Status of a contact edge
+///
+/// ## Variants
+///
+/// * `Pending` is a request from `in` to `out` waiting for an answer
+/// * `Accepted` makes `in` and `out` contacts of each other
+/// * `Blocked` is `in` blocking `out`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContactStatus {
+    Pending,
+    Accepted,
+    Blocked,
+}
+
+/// Contact Struct, the `users:a->contact->users:b` edge between two users
+///
+/// There is at most one edge per direction. A request and the contact it becomes share the
+/// edge of the requester, while each user blocking the other has an edge of their own.
+///
+/// ## Fields
+///
+/// * `ID` is the id of the edge
+/// * `From` is the user who sent the request or blocked the other, `in`
+/// * `To` is the other user, `out`
+/// * `Status` is the status of the edge
+/// * `CreatedAt` is the date the edge was created
+/// * `UpdatedAt` is the date the status last changed
+#[derive(Debug, Serialize, Deserialize, Clone, Model)]
+#[model(table = "contact", unique = "in, out", no_dto)]
+pub struct Contact {
+    #[model(id)]
+    pub id: Thing,
+    #[serde(rename = "in")]
+    #[model(ty = "record<users>")]
+    pub from: Thing,
+    #[serde(rename = "out")]
+    #[model(ty = "record<users>")]
+    pub to: Thing,
+    #[model(
+        ty = "string",
+        assert = "$value INSIDE ['pending', 'accepted', 'blocked']"
+    )]
+    pub status: ContactStatus,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Which contacts of a user to list
+///
+/// ## Variants
+///
+/// * `Accepted` are the contacts of the user
+/// * `Incoming` are the requests the user received
+/// * `Outgoing` are the requests the user sent
+/// * `Blocked` are the users the user blocked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContactKind {
+    Accepted,
+    Incoming,
+    Outgoing,
+    Blocked,
+}
+
+/// Contact Entry Struct, a contact as listed to one of its users
+///
+/// ## Fields
+///
+/// * `User` is the profile of the other user
+/// * `Status` is the status of the edge
+/// * `Since` is the date the status last changed
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ContactEntry {
+    pub user: PublicProfile,
+    pub status: ContactStatus,
+    pub since: String,
+}
+
+/// A contact edge with the other user, as read by `Contact::list`
+#[derive(Debug, Deserialize)]
+struct ListedContact {
+    user: User,
+    status: ContactStatus,
+    updated_at: String,
+}
+
+impl ContactKind {
+    /// Returns the condition matching the edges of the kind, for the user `$me`
+    fn condition(self) -> &'static str {
+        match self {
+            ContactKind::Accepted => {
+                "status = 'accepted' AND \
+                ((in = $me AND out.deleted_at = NONE) OR (out = $me AND in.deleted_at = NONE))"
+            }
+            ContactKind::Incoming => {
+                "status = 'pending' AND out = $me AND in.deleted_at = NONE"
+            }
+            ContactKind::Outgoing => {
+                "status = 'pending' AND in = $me AND out.deleted_at = NONE"
+            }
+            ContactKind::Blocked => "status = 'blocked' AND in = $me",
+        }
+    }
+}
+
+impl Contact {
+    /// Get the edges between two users, in both directions
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `a` - The id of a user
+    /// * `b` - The id of the other user
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn between(db: &DBConnection, a: &Thing, b: &Thing) -> Result<Vec<Contact>, Error> {
+        let mut res = db
+            .query("SELECT * FROM contact WHERE (in = $a AND out = $b) OR (in = $b AND out = $a)")
+            .bind(("a", a))
+            .bind(("b", b))
+            .await?;
+        let contacts = res.take(0)?;
+
+        Ok(contacts)
+    }
+
+    /// Whether either user blocked the other
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `a` - The id of a user
+    /// * `b` - The id of the other user
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn is_blocked(db: &DBConnection, a: &Thing, b: &Thing) -> Result<bool, Error> {
+        let contacts = Self::between(db, a, b).await?;
+
+        Ok(contacts
+            .iter()
+            .any(|contact| contact.status == ContactStatus::Blocked))
+    }
+
+    /// Get the ids of the users a user blocked or is blocked by
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<Vec<Thing>, Error>` - The ids of the users hidden from the user
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn blocked_with(db: &DBConnection, user: &Thing) -> Result<Vec<Thing>, Error> {
+        let mut res = db
+            .query(
+                "SELECT VALUE out FROM contact WHERE in = $user AND status = 'blocked';\
+                SELECT VALUE in FROM contact WHERE out = $user AND status = 'blocked';",
+            )
+            .bind(("user", user))
+            .await?;
+        let mut blocked: Vec<Thing> = res.take(0)?;
+        let blocked_by: Vec<Thing> = res.take(1)?;
+        blocked.extend(blocked_by);
+
+        Ok(blocked)
+    }
+
+    /// Get the ids of the contacts of a user
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<Vec<Thing>, Error>` - The ids of the users whose request was accepted
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn accepted_with(db: &DBConnection, user: &Thing) -> Result<Vec<Thing>, Error> {
+        let mut res = db
+            .query(
+                "SELECT VALUE out FROM contact WHERE in = $user AND status = 'accepted';\
+                SELECT VALUE in FROM contact WHERE out = $user AND status = 'accepted';",
+            )
+            .bind(("user", user))
+            .await?;
+        let mut contacts: Vec<Thing> = res.take(0)?;
+        let requesters: Vec<Thing> = res.take(1)?;
+        contacts.extend(requesters);
+
+        Ok(contacts)
+    }
+
+    /// Create an edge from a user to another one
+    async fn relate(
+        db: &DBConnection,
+        from: &Thing,
+        to: &Thing,
+        status: ContactStatus,
+    ) -> Result<Contact, Error> {
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut res = db
+            .query(
+                "RELATE $from->contact->$to \
+                SET status = $status, created_at = $now, updated_at = $now",
+            )
+            .bind(("from", from))
+            .bind(("to", to))
+            .bind(("status", status))
+            .bind(("now", now))
+            .await?;
+        let contact: Option<Contact> = res
+            .take(0)
+            .map_err(|e| Error::from(e).map_index_conflict())?;
+
+        contact.ok_or(Error::NoRecord)
+    }
+
+    /// Set the status of an edge
+    async fn set_status(
+        db: &DBConnection,
+        id: &Thing,
+        status: ContactStatus,
+    ) -> Result<Contact, Error> {
+        let mut res = db
+            .query("UPDATE $id SET status = $status, updated_at = $now RETURN AFTER")
+            .bind(("id", id))
+            .bind(("status", status))
+            .bind(("now", chrono::Utc::now().to_rfc3339()))
+            .await?;
+        let contact: Option<Contact> = res.take(0)?;
+
+        contact.ok_or_else(|| Error::NotFound(id.to_string()))
+    }
+
+    /// Delete the edge from a user to another one if it has the given status
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::NotFound` - There is no such edge
+    async fn remove(
+        db: &DBConnection,
+        from: &Thing,
+        to: &Thing,
+        status: ContactStatus,
+    ) -> Result<(), Error> {
+        let mut res = db
+            .query(
+                "DELETE contact WHERE in = $from AND out = $to AND status = $status RETURN BEFORE",
+            )
+            .bind(("from", from))
+            .bind(("to", to))
+            .bind(("status", status))
+            .await?;
+        let deleted: Vec<Contact> = res.take(0)?;
+
+        if deleted.is_empty() {
+            return Err(Error::NotFound(format!("{status:?} contact").to_lowercase()));
+        }
+
+        Ok(())
+    }
+
+    /// Send a contact request, accepting the request of the other user if there is one
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `from` - The id of the user sending the request
+    /// * `to` - The id of the requested user
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<Contact, Error>` - The pending request, or the accepted contact
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::NotFound` - Either user blocked the other, reported like an unknown user
+    /// * `Error::Conflict` - The request was already sent, or the users are already contacts
+    /// * `Error` - The error returned by the database
+    pub async fn request(db: &DBConnection, from: &Thing, to: &Thing) -> Result<Contact, Error> {
+        let contacts = Self::between(db, from, to).await?;
+
+        if contacts.iter().any(|c| c.status == ContactStatus::Blocked) {
+            return Err(Error::NotFound(to.to_string()));
+        }
+        if contacts.iter().any(|c| c.status == ContactStatus::Accepted) {
+            return Err(Error::Conflict("you are already contacts".to_string()));
+        }
+        if let Some(received) = contacts.iter().find(|c| &c.from == to) {
+            return Self::set_status(db, &received.id, ContactStatus::Accepted).await;
+        }
+        if !contacts.is_empty() {
+            return Err(Error::Conflict("the request was already sent".to_string()));
+        }
+
+        Self::relate(db, from, to, ContactStatus::Pending).await
+    }
+
+    /// Accept a received contact request
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user who received the request
+    /// * `requester` - The id of the user who sent it
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::NotFound` - There is no pending request from `requester`
+    /// * `Error` - The error returned by the database
+    pub async fn accept(
+        db: &DBConnection,
+        user: &Thing,
+        requester: &Thing,
+    ) -> Result<Contact, Error> {
+        let contacts = Self::between(db, requester, user).await?;
+        let request = contacts
+            .into_iter()
+            .find(|c| &c.from == requester && c.status == ContactStatus::Pending)
+            .ok_or_else(|| Error::NotFound("pending contact".to_string()))?;
+
+        Self::set_status(db, &request.id, ContactStatus::Accepted).await
+    }
+
+    /// Decline a received contact request
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user who received the request
+    /// * `requester` - The id of the user who sent it
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::NotFound` - There is no pending request from `requester`
+    /// * `Error` - The error returned by the database
+    pub async fn decline(db: &DBConnection, user: &Thing, requester: &Thing) -> Result<(), Error> {
+        Self::remove(db, requester, user, ContactStatus::Pending).await
+    }
+
+    /// Cancel a sent contact request
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user who sent the request
+    /// * `requested` - The id of the requested user
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::NotFound` - There is no pending request to `requested`
+    /// * `Error` - The error returned by the database
+    pub async fn cancel(db: &DBConnection, user: &Thing, requested: &Thing) -> Result<(), Error> {
+        Self::remove(db, user, requested, ContactStatus::Pending).await
+    }
+
+    /// Remove a contact, whoever sent the request
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user
+    /// * `contact` - The id of the contact to remove
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::NotFound` - The users are not contacts
+    /// * `Error` - The error returned by the database
+    pub async fn unfriend(db: &DBConnection, user: &Thing, contact: &Thing) -> Result<(), Error> {
+        match Self::remove(db, user, contact, ContactStatus::Accepted).await {
+            Err(Error::NotFound(_)) => {
+                Self::remove(db, contact, user, ContactStatus::Accepted).await
+            }
+            result => result,
+        }
+    }
+
+    /// Block a user, removing any contact or request between both users
+    ///
+    /// A block of the other user is kept, both blocks then have to be lifted.
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user blocking
+    /// * `blocked` - The id of the user to block
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<Contact, Error>` - The block
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn block(db: &DBConnection, user: &Thing, blocked: &Thing) -> Result<Contact, Error> {
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut res = db
+            .query(
+                "BEGIN TRANSACTION;\
+                DELETE contact WHERE (in = $user AND out = $blocked) \
+                    OR (in = $blocked AND out = $user AND status != 'blocked');\
+                RELATE $user->contact->$blocked \
+                    SET status = 'blocked', created_at = $now, updated_at = $now;\
+                COMMIT TRANSACTION;",
+            )
+            .bind(("user", user))
+            .bind(("blocked", blocked))
+            .bind(("now", now))
+            .await?;
+        let contact: Option<Contact> = res.take(1)?;
+
+        contact.ok_or(Error::NoRecord)
+    }
+
+    /// Lift the block of a user
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user who blocked the other
+    /// * `blocked` - The id of the blocked user
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::NotFound` - `user` did not block `blocked`
+    /// * `Error` - The error returned by the database
+    pub async fn unblock(db: &DBConnection, user: &Thing, blocked: &Thing) -> Result<(), Error> {
+        Self::remove(db, user, blocked, ContactStatus::Blocked).await
+    }
+
+    /// List the contacts of a user, most recently changed first
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user
+    /// * `kind` - Which contacts to list
+    /// * `query` - The page to fetch, filters and sort orders are not supported
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<Page<ContactEntry>, Error>` - The page and the total number of contacts
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Validation` - The query has filters or a sort order, or is malformed
+    /// * `Error` - The error returned by the database
+    pub async fn list(
+        db: &DBConnection,
+        user: &Thing,
+        kind: ContactKind,
+        query: ListQuery,
+    ) -> Result<Page<ContactEntry>, Error> {
+        #[derive(Deserialize)]
+        struct Count {
+            total: u64,
+        }
+
+        query.validate(&[])?;
+        if !query.filters.is_empty() || query.sort.is_some() {
+            return Err(Error::Validation(
+                "contacts cannot be filtered or sorted".to_string(),
+            ));
+        }
+        let start = query.start()?;
+
+        let condition = kind.condition();
+        let mut res = db
+            .query(format!(
+                "SELECT status, updated_at, IF in = $me THEN out ELSE in END AS user \
+                FROM contact WHERE {condition} \
+                ORDER BY updated_at DESC LIMIT $limit START $start FETCH user;\
+                SELECT count() AS total FROM contact WHERE {condition} GROUP ALL;"
+            ))
+            .bind(("me", user))
+            .bind(("limit", query.limit + 1))
+            .bind(("start", start))
+            .await?;
+        let mut listed: Vec<ListedContact> = res.take(0)?;
+        let total: Option<Count> = res.take(1)?;
+
+        // One extra contact was fetched to know if there is a next page
+        let next_cursor = if listed.len() as u64 > query.limit {
+            listed.truncate(query.limit as usize);
+            Some(ListQuery::encode_cursor(start + query.limit))
+        } else {
+            None
+        };
+
+        Ok(Page {
+            items: listed
+                .into_iter()
+                .map(|contact| ContactEntry {
+                    user: PublicProfile::from(contact.user),
+                    status: contact.status,
+                    since: contact.updated_at,
+                })
+                .collect(),
+            total: total.map(|count| count.total).unwrap_or(0),
+            limit: query.limit,
+            offset: start,
+            next_cursor,
+        })
+    }
+}
@@ -1,3 +1,11 @@
 // models/mod.rs
+pub mod calendar_model;
+pub mod contact_model;
 pub mod model;
+pub mod notification_model;
+pub mod patch;
+pub mod presence_model;
+pub mod query;
+pub mod timetable_model;
+pub mod untis_model;
 pub mod user_model;
\ No newline at end of file
@@ -1,15 +1,20 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use surrealdb::{engine::remote::ws::Client, sql::Thing, Surreal};
+use surrealdb::{engine::any::Any, sql::Thing, Surreal};
 
-use crate::prelude::Error;
+use super::{
+    patch::{check_merge, check_patch, PatchOperation},
+    query::{ListQuery, Page},
+    user_model::User,
+};
+use crate::{prelude::Error, repository::surrealdb_repo::Patchable};
 
 /// Database Connection
 ///
 /// ## Type Alias
 /// 
-/// * `Surreal<Client>` - The database connection
-pub type DBConnection = Surreal<Client>;
+/// * `Surreal<Any>` - The database connection, remote or embedded
+pub type DBConnection = Surreal<Any>;
 /// Database Connection Data
 /// 
 /// ## Type Alias
@@ -17,6 +22,96 @@ pub type DBConnection = Surreal<Client>;
 /// * `actix_web::web::Data<DBConnection>` - The database connection data
 pub type ConnectionData = actix_web::web::Data<DBConnection>;
 
+/// Define the `version` field of a table
+///
+/// The version starts at 1 and is incremented by the database on every write,
+/// whatever the written content says, so conditional updates can rely on it
+///
+/// ## Arguments
+///
+/// * `tb` - The table name
+///
+/// ## Returns
+///
+/// * `String` - The `DEFINE FIELD` statement, to run in `CRUD::init_table`
+pub fn version_field(tb: &str) -> String {
+    format!("DEFINE FIELD version ON {tb} TYPE int VALUE IF $before THEN $before + 1 ELSE 1 END;")
+}
+
+/// Run an update only if the entry exists and, when given, its version matches
+///
+/// ## Arguments
+///
+/// * `db` - The database connection
+/// * `id` - The id of the entry
+/// * `operation` - What follows `UPDATE $id`, e.g. `MERGE $data`
+/// * `binding` - The parameter the operation uses
+/// * `version` - The expected version, `None` to update unconditionally
+///
+/// ## Errors
+///
+/// * `Error::NotFound` - The entry does not exist
+/// * `Error::Conflict` - The entry was modified since the expected version
+/// * `Error` - The error returned by the database
+async fn conditional_update<D, V>(
+    db: &DBConnection,
+    id: Thing,
+    operation: &str,
+    binding: (&'static str, V),
+    version: Option<u64>,
+) -> Result<D, Error>
+where
+    D: for<'de> Deserialize<'de> + Send,
+    V: Serialize + Send,
+{
+    // UPDATE creates missing records, only update existing ones
+    let mut res = db
+        .query(format!(
+            "BEGIN TRANSACTION;\
+            SELECT VALUE version FROM $id;\
+            IF $id.id != NONE THEN \
+                (UPDATE $id {operation} WHERE $version = NONE OR version = $version RETURN AFTER) \
+            END;\
+            COMMIT TRANSACTION;"
+        ))
+        .bind(("id", &id))
+        .bind(("version", version))
+        .bind(binding)
+        .await?;
+    let current: Option<u64> = res.take(0)?;
+    let updated: Option<D> = res.take(1)?;
+
+    match (current, updated) {
+        (None, _) => Err(Error::NotFound(id.to_string())),
+        (Some(current), None) => Err(Error::Conflict(format!(
+            "{id} is at version {current}, not {}",
+            version.unwrap_or_default()
+        ))),
+        (Some(_), Some(updated)) => Ok(updated),
+    }
+}
+
+/// The user watching the changes of a table with `api::live::changes`
+///
+/// ## Fields
+///
+/// * `User` is the user
+/// * `Blocked` are the users they blocked or are blocked by, when the stream opened
+#[derive(Debug, Clone)]
+pub struct Viewer {
+    pub user: User,
+    pub blocked: Vec<Thing>,
+}
+
+/// A model whose changes can be streamed to clients with `api::live::changes`
+///
+/// ## Methods
+///
+/// * `can_watch` returns true if the viewer may see the changes of the record
+pub trait Watchable<D> {
+    fn can_watch(viewer: &Viewer, record: &D) -> bool;
+}
+
 /// CRUD Trait
 /// 
 /// ## Methods
@@ -25,8 +120,14 @@ pub type ConnectionData = actix_web::web::Data<DBConnection>;
 /// * `create` - Create a new entry in the table
 /// * `create_id` - Create a new entry in the table
 /// * `get_from_id` - Get entry from the table
+/// * `list` - List entries of the table
 /// * `update_replace` - Update an entry in the table
-/// * `delete` - Delete an entry from the table
+/// * `update_merge` - Merge data into an entry of the table
+/// * `update_patch` - Apply a JSON Patch to an entry of the table
+/// * `delete` - Delete an entry from the table, softly if the table supports it
+/// * `restore` - Restore a soft-deleted entry
+/// * `purge` - Permanently delete an entry from the table
+/// * `purge_deleted` - Permanently delete the entries soft-deleted before a date
 /// 
 /// ## Generic Types
 /// 
@@ -41,6 +142,15 @@ where
     D: Serialize + Send + Sync + for<'de> Deserialize<'de> + 'static,
     C: Serialize + Send + Sync + for<'de> Deserialize<'de> + 'static,
 {
+    /// Name of the table
+    const TABLE: &'static str;
+
+    /// Fields that `list` may filter and sort on, besides `id`
+    const LIST_FIELDS: &'static [&'static str] = &[];
+
+    /// Whether `delete` only sets `deleted_at`, hiding the entry from lookups and lists
+    const SOFT_DELETE: bool = false;
+
     /// Initialize the table
     /// 
     /// ## Arguments
@@ -72,9 +182,9 @@ where
     /// 
     /// * `Error` - The error returned by the database
     async fn create(db: ConnectionData, tb: String, data: C) -> Result<D, Error> {
-        let res: D = db.create(tb).content(data).await?;
+        let res: Vec<D> = db.create(tb).content(data).await?;
 
-        Ok(res)
+        res.into_iter().next().ok_or(Error::NoRecord)
     }
 
     /// Create a new entry in the table
@@ -107,17 +217,88 @@ where
     /// 
     /// ## Returns
     /// 
-    /// * `Result<Option<D>, Error>` - The result of the operation
+    /// * `Result<Option<D>, Error>` - The entry, `None` if it does not exist or is soft-deleted
     /// 
     /// ## Errors
     /// 
     /// * `Error` - The error returned by the database
     async fn get_from_id(db: ConnectionData, id: Thing) -> Result<Option<D>, Error> {
-        let res: Option<D> = db.select(id).await?;
+        if !Self::SOFT_DELETE {
+            let res: Option<D> = db.select(id).await?;
+
+            return Ok(res);
+        }
+
+        let mut res = db
+            .query("SELECT * FROM $id WHERE deleted_at = NONE")
+            .bind(("id", id))
+            .await?;
+        let res: Option<D> = res.take(0)?;
 
         Ok(res)
     }
 
+    /// List entries of the table
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `query` - The filters, sort order and page to fetch
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<Page<D>, Error>` - The page and the total number of matching entries
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Validation` - The query uses a field not in `LIST_FIELDS` or is malformed
+    /// * `Error` - The error returned by the database
+    async fn list(db: ConnectionData, query: ListQuery) -> Result<Page<D>, Error> {
+        #[derive(Deserialize)]
+        struct Count {
+            total: u64,
+        }
+
+        query.validate(Self::LIST_FIELDS)?;
+        let start = query.start()?;
+        let (where_clause, bindings) =
+            query.where_clause(Self::SOFT_DELETE && !query.include_deleted);
+
+        let sql = format!(
+            "SELECT * FROM {tb}{where_clause}{order} LIMIT $limit START $start;\
+            SELECT count() AS total FROM {tb}{where_clause} GROUP ALL;",
+            tb = Self::TABLE,
+            order = query.order_clause(),
+        );
+        // Fetch one extra entry to know if there is a next page
+        let mut req = db
+            .query(sql)
+            .bind(("limit", query.limit + 1))
+            .bind(("start", start));
+        for binding in bindings {
+            req = req.bind(binding);
+        }
+
+        let mut res = req.await?;
+        let mut items: Vec<D> = res.take(0)?;
+        let total: Option<Count> = res.take(1)?;
+
+        let next_cursor = if items.len() as u64 > query.limit {
+            items.truncate(query.limit as usize);
+            Some(ListQuery::encode_cursor(start + query.limit))
+        } else {
+            None
+        };
+
+        Ok(Page {
+            items,
+            total: total.map(|count| count.total).unwrap_or(0),
+            limit: query.limit,
+            offset: start,
+            next_cursor,
+        })
+    }
+
     /// Update an entry in the table
     /// 
     /// ## Arguments
@@ -125,21 +306,99 @@ where
     /// * `db` - The database connection
     /// * `id` - The id of the entry
     /// * `data` - The data to insert
+    /// * `version` - The version the data was read at, `None` to overwrite unconditionally
     /// 
     /// ## Returns
     /// 
-    /// * `Result<Option<D>, Error>` - The result of the operation
+    /// * `Result<D, Error>` - The updated entry
     /// 
     /// ## Errors
     /// 
+    /// * `Error::NotFound` - The entry does not exist
+    /// * `Error::Conflict` - The entry was modified since `version`
     /// * `Error` - The error returned by the database
-    async fn update_replace(db: ConnectionData, id: Thing, data: D) -> Result<(), Error> {
-        let _: D = db.update(id).content(data).await?;
+    async fn update_replace(
+        db: ConnectionData,
+        id: Thing,
+        data: D,
+        version: Option<u64>,
+    ) -> Result<D, Error> {
+        conditional_update(&db, id, "CONTENT $data", ("data", data), version).await
+    }
+
+    /// Merge data into an entry of the table
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `id` - The id of the entry
+    /// * `data` - The fields to change, must not contain a protected field
+    /// * `version` - The version the entry was read at, `None` to update unconditionally
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<D, Error>` - The updated entry
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Validation` - The data writes a protected field
+    /// * `Error::NotFound` - The entry does not exist
+    /// * `Error::Conflict` - The entry was modified since `version`
+    /// * `Error` - The error returned by the database
+    async fn update_merge<P>(
+        db: ConnectionData,
+        id: Thing,
+        data: P,
+        version: Option<u64>,
+    ) -> Result<D, Error>
+    where
+        Self: Patchable,
+        P: Serialize + Send + 'static,
+    {
+        let data = serde_json::to_value(data)
+            .map_err(|e| Error::Validation(format!("merge is not valid JSON, {e}")))?;
+        check_merge(&data, Self::PROTECTED_FIELDS)?;
+
+        conditional_update(&db, id, "MERGE $data", ("data", data), version).await
+    }
 
-        Ok(())
+    /// Apply a JSON Patch (RFC 6902) to an entry of the table
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `id` - The id of the entry
+    /// * `ops` - The patch, must not read or write a protected field
+    /// * `version` - The version the entry was read at, `None` to update unconditionally
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<D, Error>` - The updated entry
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Validation` - The patch touches a protected field
+    /// * `Error::NotFound` - The entry does not exist
+    /// * `Error::Conflict` - The entry was modified since `version`
+    /// * `Error` - The error returned by the database, e.g. a failed `test` operation
+    async fn update_patch(
+        db: ConnectionData,
+        id: Thing,
+        ops: Vec<PatchOperation>,
+        version: Option<u64>,
+    ) -> Result<D, Error>
+    where
+        Self: Patchable,
+    {
+        check_patch(&ops, Self::PROTECTED_FIELDS)?;
+
+        conditional_update(&db, id, "PATCH $ops", ("ops", ops), version).await
     }
 
     /// Delete an entry from the table
+    ///
+    /// With `SOFT_DELETE` the entry is only marked with `deleted_at` and can be restored
+    /// until it is purged, otherwise it is permanently deleted
     /// 
     /// ## Arguments
     /// 
@@ -152,11 +411,105 @@ where
     /// 
     /// ## Errors
     /// 
+    /// * `Error::NotFound` - The entry does not exist or is already deleted
     /// * `Error` - The error returned by the database
     async fn delete(db: ConnectionData, id: Thing) -> Result<(), Error> {
-        let _: D = db.delete(id).await?;
+        if !Self::SOFT_DELETE {
+            return Self::purge(db, id).await;
+        }
+
+        let mut res = db
+            .query(
+                "IF $id.id != NONE THEN \
+                    (UPDATE $id SET deleted_at = $now WHERE deleted_at = NONE RETURN AFTER) \
+                END",
+            )
+            .bind(("id", &id))
+            .bind(("now", chrono::Utc::now().to_rfc3339()))
+            .await?;
+        let deleted: Option<D> = res.take(0)?;
+
+        deleted
+            .map(|_| ())
+            .ok_or_else(|| Error::NotFound(id.to_string()))
+    }
+
+    /// Restore a soft-deleted entry
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `id` - The id of the entry
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<D, Error>` - The restored entry
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::NotFound` - The entry does not exist, is not deleted or was purged
+    /// * `Error` - The error returned by the database
+    async fn restore(db: ConnectionData, id: Thing) -> Result<D, Error> {
+        let mut res = db
+            .query(
+                "IF $id.id != NONE THEN \
+                    (UPDATE $id SET deleted_at = NONE WHERE deleted_at != NONE RETURN AFTER) \
+                END",
+            )
+            .bind(("id", &id))
+            .await?;
+        let restored: Option<D> = res.take(0)?;
+
+        restored.ok_or_else(|| Error::NotFound(id.to_string()))
+    }
+
+    /// Permanently delete an entry from the table
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `id` - The id of the entry
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<(), Error>` - The result of the operation
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::NotFound` - The entry does not exist
+    /// * `Error` - The error returned by the database
+    async fn purge(db: ConnectionData, id: Thing) -> Result<(), Error> {
+        let deleted: Option<D> = db.delete(id.clone()).await?;
+
+        deleted
+            .map(|_| ())
+            .ok_or_else(|| Error::NotFound(id.to_string()))
+    }
 
+    /// Permanently delete the entries soft-deleted before a date
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `before` - The RFC 3339 date, entries deleted at or after it are kept
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<usize, Error>` - The number of purged entries
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    async fn purge_deleted(db: ConnectionData, before: String) -> Result<usize, Error> {
+        let mut res = db
+            .query(format!(
+                "DELETE FROM {} WHERE deleted_at != NONE AND deleted_at < $before RETURN BEFORE",
+                Self::TABLE
+            ))
+            .bind(("before", before))
+            .await?;
+        let purged: Vec<D> = res.take(0)?;
 
-        Ok(())
+        Ok(purged.len())
     }
 }
@@ -0,0 +1,392 @@
+// models/notification_model.rs
+use backend_derive::Model;
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+use super::{
+    model::{DBConnection, Viewer, Watchable, CRUD},
+    query::{ListQuery, Page},
+    timetable_model::{Lesson, LessonChange, LessonStatus},
+};
+use crate::{prelude::Error, repository::transaction::Transaction};
+
+/// Kind of a change of the timetable worth notifying
+///
+/// ## Variants
+///
+/// * `Cancelled` is a lesson that does not take place anymore
+/// * `RoomChanged` is a lesson moved to another room
+/// * `Substitution` is a lesson given by another teacher
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    Cancelled,
+    RoomChanged,
+    Substitution,
+}
+
+/// Notification Struct, a change of the timetable of a user
+///
+/// ## Fields
+///
+/// * `ID` is the id of the notification
+/// * `User` is the user notified
+/// * `Kind` is the kind of the change
+/// * `Period` is the id of the period of the lesson in WebUntis
+/// * `Date` is the date of the lesson, `yyyy-mm-dd`
+/// * `Start` is the start of the lesson, `yyyy-mm-ddThh:mm:ss`
+/// * `Subjects` are the names of the subjects of the lesson
+/// * `Previous` are the rooms or teachers before the change, empty for a cancellation
+/// * `Current` are the rooms or teachers after the change, empty for a cancellation
+/// * `Text` is the text of the substitution, if any
+/// * `ReadAt` is the date the user read the notification, `None` while unread
+/// * `CreatedAt` is the date of the notification
+#[derive(Debug, Serialize, Deserialize, Clone, Model)]
+#[model(table = "notification")]
+pub struct Notification {
+    #[model(id)]
+    pub id: Thing,
+    #[model(create, index, ty = "record<users>")]
+    pub user: Thing,
+    #[model(
+        create,
+        ty = "string",
+        assert = "$value INSIDE ['cancelled', 'room_changed', 'substitution']"
+    )]
+    pub kind: NotificationKind,
+    #[model(create)]
+    pub period: i64,
+    #[model(create)]
+    pub date: String,
+    #[model(create)]
+    pub start: String,
+    #[model(create)]
+    pub subjects: Vec<String>,
+    #[model(create)]
+    pub previous: Vec<String>,
+    #[model(create)]
+    pub current: Vec<String>,
+    #[model(create)]
+    pub text: Option<String>,
+    #[model(create)]
+    pub read_at: Option<String>,
+    #[model(create, index)]
+    pub created_at: String,
+}
+
+/// Notification Entry Struct, a notification as seen by its user
+///
+/// ## Fields
+///
+/// * `ID` is the id of the notification, without its table
+/// * `Kind` is the kind of the change
+/// * `Period` is the id of the period of the lesson in WebUntis
+/// * `Date` is the date of the lesson, `yyyy-mm-dd`
+/// * `Start` is the start of the lesson, `yyyy-mm-ddThh:mm:ss`
+/// * `Subjects` are the names of the subjects of the lesson
+/// * `Previous` are the rooms or teachers before the change, empty for a cancellation
+/// * `Current` are the rooms or teachers after the change, empty for a cancellation
+/// * `Text` is the text of the substitution, if any
+/// * `Read` is whether the user read the notification
+/// * `CreatedAt` is the date of the notification
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NotificationEntry {
+    pub id: String,
+    pub kind: NotificationKind,
+    pub period: i64,
+    pub date: String,
+    pub start: String,
+    pub subjects: Vec<String>,
+    pub previous: Vec<String>,
+    pub current: Vec<String>,
+    pub text: Option<String>,
+    pub read: bool,
+    pub created_at: String,
+}
+
+impl From<Notification> for NotificationEntry {
+    fn from(notification: Notification) -> Self {
+        Self {
+            id: notification.id.id.to_raw(),
+            kind: notification.kind,
+            period: notification.period,
+            date: notification.date,
+            start: notification.start,
+            subjects: notification.subjects,
+            previous: notification.previous,
+            current: notification.current,
+            text: notification.text,
+            read: notification.read_at.is_some(),
+            created_at: notification.created_at,
+        }
+    }
+}
+
+impl Watchable<Notification> for Notification {
+    /// Users only see their own notifications
+    fn can_watch(viewer: &Viewer, record: &Notification) -> bool {
+        record.user == viewer.user.id
+    }
+}
+
+impl NotificationCreate {
+    /// Build the notification of a lesson
+    fn new(
+        kind: NotificationKind,
+        lesson: &Lesson,
+        previous: &[String],
+        current: &[String],
+        now: &str,
+    ) -> Self {
+        Self {
+            user: lesson.user.clone(),
+            kind,
+            period: lesson.period,
+            date: lesson.date.clone(),
+            start: lesson.start.clone(),
+            subjects: lesson.subjects.clone(),
+            previous: previous.to_vec(),
+            current: current.to_vec(),
+            text: lesson.substitution_text.clone(),
+            read_at: None,
+            created_at: now.to_string(),
+        }
+    }
+}
+
+impl Notification {
+    /// Returns the id of a notification from its key
+    pub fn id_of(id: &str) -> Thing {
+        Thing::from((Self::TABLE, id))
+    }
+
+    /// Build the notifications of a change of a timetable
+    ///
+    /// A lesson is notified when it gets cancelled, moved or substituted, or when it
+    /// appears in the timetable already so. Removed lessons are not notified.
+    ///
+    /// ## Arguments
+    ///
+    /// * `change` - The change
+    /// * `now` - The date of the notifications
+    pub fn of_change(change: &LessonChange, now: &str) -> Vec<NotificationCreate> {
+        let mut notifications = Vec::new();
+        let mut notify =
+            |kind: NotificationKind, lesson: &Lesson, previous: &[String], current: &[String]| {
+                notifications.push(NotificationCreate::new(kind, lesson, previous, current, now))
+            };
+
+        match change {
+            LessonChange::Added(lesson) if lesson.status == LessonStatus::Cancelled => {
+                notify(NotificationKind::Cancelled, lesson, &[], &[])
+            }
+            LessonChange::Added(lesson) => {
+                let (rooms, teachers) = (&lesson.original_rooms, &lesson.original_teachers);
+                if !rooms.is_empty() && *rooms != lesson.rooms {
+                    notify(NotificationKind::RoomChanged, lesson, rooms, &lesson.rooms);
+                }
+                if !teachers.is_empty() && *teachers != lesson.teachers {
+                    notify(NotificationKind::Substitution, lesson, teachers, &lesson.teachers);
+                }
+            }
+            LessonChange::Changed { before, after } if after.status == LessonStatus::Cancelled => {
+                if before.status != LessonStatus::Cancelled {
+                    notify(NotificationKind::Cancelled, after, &[], &[]);
+                }
+            }
+            LessonChange::Changed { before, after } => {
+                if before.rooms != after.rooms {
+                    notify(NotificationKind::RoomChanged, after, &before.rooms, &after.rooms);
+                }
+                if before.teachers != after.teachers {
+                    let (previous, current) = (&before.teachers, &after.teachers);
+                    notify(NotificationKind::Substitution, after, previous, current);
+                }
+            }
+            LessonChange::Removed(_) => {}
+        }
+
+        notifications
+    }
+
+    /// Store new notifications
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `notifications` - The notifications
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Transaction` - A notification cannot be stored, none was
+    /// * `Error` - The error returned by the database
+    pub async fn create_all(
+        db: &DBConnection,
+        notifications: Vec<NotificationCreate>,
+    ) -> Result<(), Error> {
+        if notifications.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = Transaction::new();
+        for notification in notifications {
+            tx.create::<Notification>(Self::TABLE, notification)?;
+        }
+        tx.commit(db).await?;
+
+        Ok(())
+    }
+
+    /// List the notifications of a user, newest first
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user
+    /// * `unread` - Whether to list the unread notifications only
+    /// * `query` - The page to fetch, filters and sort orders are not supported
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<Page<NotificationEntry>, Error>` - The page and the total number of
+    ///   notifications listed
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Validation` - The query has filters or a sort order, or is malformed
+    /// * `Error` - The error returned by the database
+    pub async fn list(
+        db: &DBConnection,
+        user: &Thing,
+        unread: bool,
+        query: ListQuery,
+    ) -> Result<Page<NotificationEntry>, Error> {
+        #[derive(Deserialize)]
+        struct Count {
+            total: u64,
+        }
+
+        query.validate(&[])?;
+        if !query.filters.is_empty() || query.sort.is_some() {
+            return Err(Error::Validation(
+                "notifications cannot be filtered or sorted".to_string(),
+            ));
+        }
+        let start = query.start()?;
+
+        let condition = if unread {
+            "user = $user AND read_at = NONE"
+        } else {
+            "user = $user"
+        };
+        let mut res = db
+            .query(format!(
+                "SELECT * FROM notification WHERE {condition} \
+                ORDER BY created_at DESC LIMIT $limit START $start;\
+                SELECT count() AS total FROM notification WHERE {condition} GROUP ALL;"
+            ))
+            .bind(("user", user))
+            .bind(("limit", query.limit + 1))
+            .bind(("start", start))
+            .await?;
+        let mut listed: Vec<Notification> = res.take(0)?;
+        let total: Option<Count> = res.take(1)?;
+
+        // One extra notification was fetched to know if there is a next page
+        let next_cursor = if listed.len() as u64 > query.limit {
+            listed.truncate(query.limit as usize);
+            Some(ListQuery::encode_cursor(start + query.limit))
+        } else {
+            None
+        };
+
+        Ok(Page {
+            items: listed.into_iter().map(NotificationEntry::from).collect(),
+            total: total.map(|count| count.total).unwrap_or(0),
+            limit: query.limit,
+            offset: start,
+            next_cursor,
+        })
+    }
+
+    /// Mark a notification of a user as read
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user
+    /// * `id` - The id of the notification
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::NotFound` - The user has no such notification
+    /// * `Error` - The error returned by the database
+    pub async fn mark_read(
+        db: &DBConnection,
+        user: &Thing,
+        id: &Thing,
+    ) -> Result<NotificationEntry, Error> {
+        let mut res = db
+            .query(
+                "IF $id.user = $user THEN \
+                    (UPDATE $id SET read_at = read_at ?? $now RETURN AFTER) \
+                END",
+            )
+            .bind(("id", id))
+            .bind(("user", user))
+            .bind(("now", chrono::Utc::now().to_rfc3339()))
+            .await?;
+        let notification: Option<Notification> = res.take(0)?;
+
+        notification
+            .map(NotificationEntry::from)
+            .ok_or_else(|| Error::NotFound(id.to_string()))
+    }
+
+    /// Mark every notification of a user as read
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<usize, Error>` - The number of notifications that were unread
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn mark_all_read(db: &DBConnection, user: &Thing) -> Result<usize, Error> {
+        let mut res = db
+            .query(
+                "UPDATE notification SET read_at = $now \
+                WHERE user = $user AND read_at = NONE RETURN id",
+            )
+            .bind(("user", user))
+            .bind(("now", chrono::Utc::now().to_rfc3339()))
+            .await?;
+        let read: Vec<Thing> = res.take((0, "id"))?;
+
+        Ok(read.len())
+    }
+
+    /// Delete the notifications of a user
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn clear(db: &DBConnection, user: &Thing) -> Result<(), Error> {
+        db.query("DELETE notification WHERE user = $user")
+            .bind(("user", user))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+}
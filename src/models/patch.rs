@@ -0,0 +1,102 @@
+// models/patch.rs
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::prelude::Error;
+
+/// A JSON Patch operation (RFC 6902)
+///
+/// ## Variants
+///
+/// * `Add` adds a value at `path`
+/// * `Remove` removes the value at `path`
+/// * `Replace` replaces the value at `path`
+/// * `Move` moves the value at `from` to `path`
+/// * `Copy` copies the value at `from` to `path`
+/// * `Test` fails the whole patch unless the value at `path` equals `value`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+impl PatchOperation {
+    /// Returns every JSON pointer the operation reads or writes
+    fn pointers(&self) -> Vec<&str> {
+        match self {
+            PatchOperation::Add { path, .. }
+            | PatchOperation::Remove { path }
+            | PatchOperation::Replace { path, .. }
+            | PatchOperation::Test { path, .. } => vec![path],
+            PatchOperation::Move { from, path } | PatchOperation::Copy { from, path } => {
+                vec![from, path]
+            }
+        }
+    }
+}
+
+/// Returns the top-level field a JSON pointer targets, `None` for the whole document
+fn top_level_field(pointer: &str) -> Option<String> {
+    let field = pointer.strip_prefix('/')?.split('/').next()?;
+
+    // Unescape the pointer, `~1` is `/` and `~0` is `~`
+    Some(field.replace("~1", "/").replace("~0", "~")).filter(|field| !field.is_empty())
+}
+
+/// Check that no operation of a patch touches a protected field
+///
+/// Reading a protected field is rejected too, `copy` could leak it into another field
+///
+/// ## Arguments
+///
+/// * `ops` - The patch
+/// * `protected` - The protected fields
+///
+/// ## Errors
+///
+/// * `Error::Validation` - The patch is empty, targets the whole document or a protected field
+pub fn check_patch(ops: &[PatchOperation], protected: &[&str]) -> Result<(), Error> {
+    if ops.is_empty() {
+        return Err(Error::Validation("patch must not be empty".to_string()));
+    }
+
+    for pointer in ops.iter().flat_map(PatchOperation::pointers) {
+        let field = top_level_field(pointer).ok_or_else(|| {
+            Error::Validation(format!("`{pointer}` must target a field of the document"))
+        })?;
+        if protected.contains(&field.as_str()) {
+            return Err(Error::Validation(format!("`{field}` cannot be modified")));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that a merge does not write a protected field
+///
+/// ## Arguments
+///
+/// * `data` - The merged data, serialized
+/// * `protected` - The protected fields
+///
+/// ## Errors
+///
+/// * `Error::Validation` - The data is not an object, is empty or writes a protected field
+pub fn check_merge(data: &Value, protected: &[&str]) -> Result<(), Error> {
+    let Some(fields) = data.as_object() else {
+        return Err(Error::Validation("merge must be an object".to_string()));
+    };
+    if fields.is_empty() {
+        return Err(Error::Validation("merge must not be empty".to_string()));
+    }
+
+    match fields.keys().find(|field| protected.contains(&field.as_str())) {
+        Some(field) => Err(Error::Validation(format!("`{field}` cannot be modified"))),
+        None => Ok(()),
+    }
+}
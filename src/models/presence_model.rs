@@ -0,0 +1,123 @@
+// models/presence_model.rs
+use std::collections::HashMap;
+
+use backend_derive::Model;
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+use super::model::DBConnection;
+use crate::prelude::Error;
+
+/// Presence Struct, when a user was last online
+///
+/// The record shares the id of its user, `presence:⟨id⟩` for `users:⟨id⟩`, and is kept
+/// out of `users` so that going online does not bump the user's version.
+///
+/// ## Fields
+///
+/// * `ID` is the id of the user in the `presence` table
+/// * `User` is the user
+/// * `LastSeen` is the last time the user connected or disconnected
+#[derive(Debug, Serialize, Deserialize, Clone, Model)]
+#[model(table = "presence", no_dto)]
+pub struct Presence {
+    #[model(id)]
+    pub id: Thing,
+    #[model(unique)]
+    pub user: Thing,
+    pub last_seen: String,
+}
+
+/// Presence Status Struct, the presence of a user as seen by others
+///
+/// Invisible users are always offline, without a last seen date.
+///
+/// ## Fields
+///
+/// * `PeerID` is the peer id of the user
+/// * `Online` is whether the user has an open signaling connection
+/// * `LastSeen` is the last time an offline user was online, if known
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PresenceStatus {
+    pub peer_id: String,
+    pub online: bool,
+    pub last_seen: Option<String>,
+}
+
+/// The visibility and last seen date of a user, read by `Presence::last_seen`
+///
+/// ## Fields
+///
+/// * `ID` is the id of the user
+/// * `PeerID` is the peer id of the user
+/// * `IsVisible` is the visibility of the user
+/// * `LastSeen` is the last time the user was online, if they ever were
+#[derive(Debug, Deserialize, Clone)]
+pub struct LastSeen {
+    pub id: Thing,
+    pub peer_id: String,
+    pub is_visible: bool,
+    pub last_seen: Option<String>,
+}
+
+impl Presence {
+    /// Record when a user was seen
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user
+    /// * `seen` - The date the user was seen
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn touch(db: &DBConnection, user: &Thing, seen: &str) -> Result<(), Error> {
+        let id = Thing::from(("presence", user.id.clone()));
+
+        db.query("UPDATE $id SET user = $user, last_seen = $seen")
+            .bind(("id", id))
+            .bind(("user", user))
+            .bind(("seen", seen))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+
+    /// Get the visibility and last seen date of users from their peer ids
+    ///
+    /// Peer ids of unknown or soft-deleted users are missing from the result
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `peer_ids` - The peer ids of the users
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<HashMap<String, LastSeen>, Error>` - The users, by peer id
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn last_seen(
+        db: &DBConnection,
+        peer_ids: &[String],
+    ) -> Result<HashMap<String, LastSeen>, Error> {
+        let mut res = db
+            .query(
+                "SELECT id, peer_id, is_visible, \
+                    (SELECT VALUE last_seen FROM presence WHERE user = $parent.id)[0] AS last_seen \
+                FROM users WHERE peer_id INSIDE $peer_ids AND deleted_at = NONE",
+            )
+            .bind(("peer_ids", peer_ids))
+            .await?;
+        let users: Vec<LastSeen> = res.take(0)?;
+
+        Ok(users
+            .into_iter()
+            .map(|user| (user.peer_id.clone(), user))
+            .collect())
+    }
+}
@@ -0,0 +1,365 @@
+// models/query.rs
+use std::collections::HashMap;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::prelude::Error;
+
+/// Default number of records in a page
+pub const DEFAULT_LIMIT: u64 = 20;
+/// Maximum number of records in a page
+pub const MAX_LIMIT: u64 = 100;
+
+/// Query parameters that are not filters
+const RESERVED_PARAMS: [&str; 4] = ["limit", "offset", "cursor", "sort"];
+
+/// Comparison operator of a filter
+///
+/// ## Variants
+///
+/// * `Eq` is `=`, the default when no operator is given
+/// * `Ne` is `!=`
+/// * `Gt` is `>`
+/// * `Gte` is `>=`
+/// * `Lt` is `<`
+/// * `Lte` is `<=`
+/// * `Contains` is `CONTAINS`, for strings and arrays
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterOp {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    Contains,
+}
+
+impl FilterOp {
+    /// Returns the SurrealQL operator
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            FilterOp::Eq => "=",
+            FilterOp::Ne => "!=",
+            FilterOp::Gt => ">",
+            FilterOp::Gte => ">=",
+            FilterOp::Lt => "<",
+            FilterOp::Lte => "<=",
+            FilterOp::Contains => "CONTAINS",
+        }
+    }
+
+    /// Parse an operator from its query string name
+    fn parse(op: &str) -> Result<Self, Error> {
+        match op {
+            "eq" => Ok(FilterOp::Eq),
+            "ne" => Ok(FilterOp::Ne),
+            "gt" => Ok(FilterOp::Gt),
+            "gte" => Ok(FilterOp::Gte),
+            "lt" => Ok(FilterOp::Lt),
+            "lte" => Ok(FilterOp::Lte),
+            "contains" => Ok(FilterOp::Contains),
+            other => Err(Error::Validation(format!("unknown filter operator `{other}`"))),
+        }
+    }
+}
+
+/// A filter on a field
+///
+/// ## Fields
+///
+/// * `field` is the name of the field
+/// * `op` is the comparison operator
+/// * `value` is the value compared against, always bound as a parameter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Filter {
+    pub field: String,
+    pub op: FilterOp,
+    pub value: Value,
+}
+
+/// Sort direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+/// Sort order of a list
+///
+/// ## Fields
+///
+/// * `field` is the name of the field
+/// * `direction` is the sort direction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sort {
+    pub field: String,
+    pub direction: SortDirection,
+}
+
+/// A typed list query
+///
+/// ## Fields
+///
+/// * `filters` are combined with `AND`
+/// * `sort` is the sort order, by `id` if omitted
+/// * `limit` is the maximum number of records in the page
+/// * `offset` is the number of records to skip
+/// * `cursor` is the opaque cursor of a previous page, takes precedence over `offset`
+/// * `include_deleted` also lists soft-deleted records, for internal callers only, never read
+///   from the query string
+/// * `exclude_ids` are the ids of records hidden from the caller, never read from the query string
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListQuery {
+    pub filters: Vec<Filter>,
+    pub sort: Option<Sort>,
+    pub limit: u64,
+    pub offset: Option<u64>,
+    pub cursor: Option<String>,
+    pub include_deleted: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_ids: Vec<String>,
+}
+
+impl Default for ListQuery {
+    fn default() -> Self {
+        Self {
+            filters: Vec::new(),
+            sort: None,
+            limit: DEFAULT_LIMIT,
+            offset: None,
+            cursor: None,
+            include_deleted: false,
+            exclude_ids: Vec::new(),
+        }
+    }
+}
+
+/// A page of records
+///
+/// ## Fields
+///
+/// * `items` are the records of the page
+/// * `total` is the number of records matching the filters
+/// * `limit` is the maximum number of records in the page
+/// * `offset` is the offset of the page
+/// * `next_cursor` is the cursor of the next page, if there is one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<D> {
+    pub items: Vec<D>,
+    pub total: u64,
+    pub limit: u64,
+    pub offset: u64,
+    pub next_cursor: Option<String>,
+}
+
+/// Contents of an opaque cursor
+#[derive(Debug, Serialize, Deserialize)]
+struct Cursor {
+    offset: u64,
+}
+
+/// Check that a field is a plain identifier the model allows listing on
+fn check_field(field: &str, allowed: &[&str]) -> Result<(), Error> {
+    if field == "id" || allowed.contains(&field) {
+        Ok(())
+    } else {
+        Err(Error::Validation(format!("cannot filter or sort on `{field}`")))
+    }
+}
+
+/// Parse a query string value, keeping it a string unless it is a bool, number or null
+fn parse_value(value: &str) -> Value {
+    match serde_json::from_str::<Value>(value) {
+        Ok(value @ (Value::Bool(_) | Value::Number(_) | Value::Null)) => value,
+        _ => Value::String(value.to_string()),
+    }
+}
+
+impl ListQuery {
+    /// Build a query from query string parameters
+    ///
+    /// `limit`, `offset`, `cursor` and `sort` (`field` or `-field` for descending) are reserved,
+    /// every other parameter is a filter written `field=value` or `field[op]=value`.
+    /// Soft-deleted records are never listed to clients, `include_deleted` is rejected.
+    ///
+    /// ## Arguments
+    ///
+    /// * `params` - The query string parameters
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Validation` - A parameter is malformed or is `include_deleted`
+    pub fn from_params(params: &HashMap<String, String>) -> Result<Self, Error> {
+        if params.contains_key("include_deleted") {
+            return Err(Error::Validation(
+                "include_deleted is not available".to_string(),
+            ));
+        }
+        let number = |key: &str| -> Result<Option<u64>, Error> {
+            params
+                .get(key)
+                .map(|value| {
+                    value
+                        .parse()
+                        .map_err(|_| Error::Validation(format!("{key} must be a positive number")))
+                })
+                .transpose()
+        };
+
+        let sort = params.get("sort").map(|sort| match sort.strip_prefix('-') {
+            Some(field) => Sort {
+                field: field.to_string(),
+                direction: SortDirection::Desc,
+            },
+            None => Sort {
+                field: sort.to_string(),
+                direction: SortDirection::Asc,
+            },
+        });
+
+        let mut filters = params
+            .iter()
+            .filter(|(key, _)| !RESERVED_PARAMS.contains(&key.as_str()))
+            .map(|(key, value)| {
+                let (field, op) = match key.split_once('[') {
+                    Some((field, op)) => {
+                        let op = op.strip_suffix(']').ok_or_else(|| {
+                            Error::Validation(format!("malformed filter `{key}`"))
+                        })?;
+                        (field, FilterOp::parse(op)?)
+                    }
+                    None => (key.as_str(), FilterOp::Eq),
+                };
+
+                Ok(Filter {
+                    field: field.to_string(),
+                    op,
+                    value: parse_value(value),
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        // HashMap iteration order is random, keep the generated query stable
+        filters.sort_by(|a, b| a.field.cmp(&b.field));
+
+        Ok(Self {
+            filters,
+            sort,
+            limit: number("limit")?.unwrap_or(DEFAULT_LIMIT),
+            offset: number("offset")?,
+            cursor: params.get("cursor").cloned(),
+            include_deleted: false,
+            exclude_ids: Vec::new(),
+        })
+    }
+
+    /// Validate the fields against the model's allowed fields and the limit
+    ///
+    /// ## Arguments
+    ///
+    /// * `allowed` - The fields the model allows filtering and sorting on
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Validation` - A field is not allowed or the limit is out of range
+    pub fn validate(&self, allowed: &[&str]) -> Result<(), Error> {
+        if self.limit == 0 || self.limit > MAX_LIMIT {
+            return Err(Error::Validation(format!(
+                "limit must be between 1 and {MAX_LIMIT}"
+            )));
+        }
+        for filter in &self.filters {
+            check_field(&filter.field, allowed)?;
+        }
+        if let Some(sort) = &self.sort {
+            check_field(&sort.field, allowed)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the offset of the page, decoded from the cursor if there is one
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Validation` - The cursor is malformed
+    pub fn start(&self) -> Result<u64, Error> {
+        match &self.cursor {
+            Some(cursor) => URL_SAFE_NO_PAD
+                .decode(cursor)
+                .ok()
+                .and_then(|bytes| serde_json::from_slice::<Cursor>(&bytes).ok())
+                .map(|cursor| cursor.offset)
+                .ok_or_else(|| Error::Validation("cursor is malformed".to_string())),
+            None => Ok(self.offset.unwrap_or(0)),
+        }
+    }
+
+    /// Encode the cursor of the page starting at `offset`
+    ///
+    /// ## Arguments
+    ///
+    /// * `offset` - The offset of the page
+    pub fn encode_cursor(offset: u64) -> String {
+        // Safety: serializing a struct of integers cannot fail
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(&Cursor { offset }).unwrap())
+    }
+
+    /// Returns the `WHERE` clause and the parameters it binds
+    ///
+    /// Field names must have been checked with `validate`, values are always bound
+    ///
+    /// ## Arguments
+    ///
+    /// * `exclude_deleted` - Only match records without `deleted_at`
+    pub fn where_clause(&self, exclude_deleted: bool) -> (String, Vec<(String, Value)>) {
+        if self.filters.is_empty() && !exclude_deleted && self.exclude_ids.is_empty() {
+            return (String::new(), Vec::new());
+        }
+
+        let (mut conditions, mut bindings): (Vec<String>, Vec<(String, Value)>) = self
+            .filters
+            .iter()
+            .enumerate()
+            .map(|(i, filter)| {
+                let param = format!("filter_{i}");
+                (
+                    format!("{} {} ${param}", filter.field, filter.op.as_sql()),
+                    (param, filter.value.clone()),
+                )
+            })
+            .unzip();
+        if exclude_deleted {
+            conditions.push("deleted_at = NONE".to_string());
+        }
+        if !self.exclude_ids.is_empty() {
+            conditions.push("<string> id NOTINSIDE $exclude_ids".to_string());
+            bindings.push(("exclude_ids".to_string(), self.exclude_ids.clone().into()));
+        }
+
+        (format!(" WHERE {}", conditions.join(" AND ")), bindings)
+    }
+
+    /// Returns the `ORDER BY` clause, always ending on `id` so pages are stable
+    ///
+    /// Field names must have been checked with `validate`
+    pub fn order_clause(&self) -> String {
+        match &self.sort {
+            Some(sort) if sort.field != "id" => {
+                let direction = match sort.direction {
+                    SortDirection::Asc => "ASC",
+                    SortDirection::Desc => "DESC",
+                };
+                format!(" ORDER BY {} {direction}, id ASC", sort.field)
+            }
+            Some(Sort {
+                direction: SortDirection::Desc,
+                ..
+            }) => " ORDER BY id DESC".to_string(),
+            _ => " ORDER BY id ASC".to_string(),
+        }
+    }
+}
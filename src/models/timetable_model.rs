@@ -0,0 +1,479 @@
+// models/timetable_model.rs
+use std::collections::HashMap;
+
+use backend_derive::Model;
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+use super::model::{DBConnection, CRUD};
+use crate::{prelude::Error, repository::transaction::Transaction};
+
+/// Status of a lesson
+///
+/// ## Variants
+///
+/// * `Regular` lessons take place as planned
+/// * `Cancelled` lessons do not take place
+/// * `Irregular` lessons are substitutions or additional lessons
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LessonStatus {
+    Regular,
+    Cancelled,
+    Irregular,
+}
+
+/// Lesson Struct, a period of the timetable of a user cached from WebUntis
+///
+/// Dates and times are the local time of the school, as given by WebUntis.
+///
+/// ## Fields
+///
+/// * `ID` is the id of the lesson
+/// * `User` is the user whose timetable the lesson is part of
+/// * `Period` is the id of the period in WebUntis
+/// * `Date` is the date, `yyyy-mm-dd`
+/// * `Start` is the start, `yyyy-mm-ddThh:mm:ss`
+/// * `End` is the end, `yyyy-mm-ddThh:mm:ss`
+/// * `Subjects` are the names of the subjects
+/// * `Teachers` are the names of the teachers
+/// * `Rooms` are the names of the rooms
+/// * `Klassen` are the names of the classes
+/// * `OriginalTeachers` are the names of the teachers being substituted
+/// * `OriginalRooms` are the names of the rooms the lesson was moved from
+/// * `Status` is the status of the lesson
+/// * `Text` is the text of the lesson
+/// * `SubstitutionText` is the text of the substitution
+/// * `Info` is additional information
+/// * `UpdatedAt` is the last time the lesson changed in WebUntis
+#[derive(Debug, Serialize, Deserialize, Clone, Model)]
+#[model(table = "lesson", unique = "user, period")]
+pub struct Lesson {
+    #[model(id)]
+    pub id: Thing,
+    #[model(create, ty = "record<users>")]
+    pub user: Thing,
+    #[model(create)]
+    pub period: i64,
+    #[model(create, patch, index)]
+    pub date: String,
+    #[model(create, patch)]
+    pub start: String,
+    #[model(create, patch)]
+    pub end: String,
+    #[model(create, patch)]
+    pub subjects: Vec<String>,
+    #[model(create, patch)]
+    pub teachers: Vec<String>,
+    #[model(create, patch)]
+    pub rooms: Vec<String>,
+    #[model(create, patch)]
+    pub klassen: Vec<String>,
+    #[model(create, patch)]
+    pub original_teachers: Vec<String>,
+    #[model(create, patch)]
+    pub original_rooms: Vec<String>,
+    #[model(
+        create,
+        patch,
+        ty = "string",
+        assert = "$value INSIDE ['regular', 'cancelled', 'irregular']"
+    )]
+    pub status: LessonStatus,
+    #[model(create, patch)]
+    pub text: Option<String>,
+    #[model(create, patch)]
+    pub substitution_text: Option<String>,
+    #[model(create, patch)]
+    pub info: Option<String>,
+    #[model(create, patch)]
+    pub updated_at: String,
+}
+
+/// A difference between two snapshots of a timetable
+///
+/// ## Variants
+///
+/// * `Added` is a lesson missing from the previous snapshot
+/// * `Changed` is a lesson that differs from the previous snapshot
+/// * `Removed` is a lesson missing from the new snapshot
+#[derive(Debug, Clone)]
+pub enum LessonChange {
+    Added(Lesson),
+    Changed { before: Lesson, after: Lesson },
+    Removed(Lesson),
+}
+
+/// Timetable Sync Struct, the state of the synchronisation of the timetable of a user
+///
+/// The record shares the id of its user, `timetable_sync:⟨id⟩` for `users:⟨id⟩`.
+///
+/// ## Fields
+///
+/// * `ID` is the id of the user in the `timetable_sync` table
+/// * `User` is the user
+/// * `SyncedFrom` is the first day of the last successful synchronisation
+/// * `SyncedTo` is the last day of the last successful synchronisation
+/// * `SyncedAt` is the date of the last successful synchronisation
+/// * `AttemptedAt` is the date of the last synchronisation, successful or not
+/// * `Error` is the reason the last synchronisation failed, if it did
+#[derive(Debug, Serialize, Deserialize, Clone, Model)]
+#[model(table = "timetable_sync", no_dto)]
+pub struct TimetableSync {
+    #[model(id)]
+    pub id: Thing,
+    #[model(unique)]
+    pub user: Thing,
+    pub synced_from: Option<String>,
+    pub synced_to: Option<String>,
+    pub synced_at: Option<String>,
+    pub attempted_at: String,
+    pub error: Option<String>,
+}
+
+/// Timetable Struct, the cached lessons of a user between two days
+///
+/// ## Fields
+///
+/// * `From` is the first day, `yyyy-mm-dd`
+/// * `To` is the last day, included
+/// * `Lessons` are the lessons, by start
+/// * `SyncedAt` is the date of the last successful synchronisation, if any
+/// * `Fresh` is whether the cache covers the days and was synchronised recently
+/// * `Error` is the reason the last synchronisation failed, if it did
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Timetable {
+    pub from: String,
+    pub to: String,
+    pub lessons: Vec<Lesson>,
+    pub synced_at: Option<String>,
+    pub fresh: bool,
+    pub error: Option<String>,
+}
+
+/// Returns whether an RFC 3339 date is less than `max_age` ago
+fn is_recent(date: &str, max_age: Duration) -> bool {
+    chrono::DateTime::parse_from_rfc3339(date)
+        .is_ok_and(|date| Utc::now().signed_duration_since(date) < max_age)
+}
+
+impl Timetable {
+    /// Read the cached timetable of a user between two days
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user
+    /// * `from` - The first day, `yyyy-mm-dd`
+    /// * `to` - The last day, included
+    /// * `max_age` - The age after which the cache is stale
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn of(
+        db: &DBConnection,
+        user: &Thing,
+        from: &str,
+        to: &str,
+        max_age: Duration,
+    ) -> Result<Timetable, Error> {
+        let lessons = Lesson::between(db, user, from, to).await?;
+        let sync = TimetableSync::of(db, user).await?;
+
+        Ok(Timetable {
+            from: from.to_string(),
+            to: to.to_string(),
+            lessons,
+            fresh: sync
+                .as_ref()
+                .is_some_and(|sync| sync.is_fresh(from, to, max_age)),
+            synced_at: sync.as_ref().and_then(|sync| sync.synced_at.clone()),
+            error: sync.and_then(|sync| sync.error),
+        })
+    }
+}
+
+impl LessonChange {
+    /// Returns the lesson as it is now, or as it was before it was removed
+    pub fn lesson(&self) -> &Lesson {
+        match self {
+            LessonChange::Added(lesson) | LessonChange::Removed(lesson) => lesson,
+            LessonChange::Changed { after, .. } => after,
+        }
+    }
+}
+
+impl LessonCreate {
+    /// Returns the fields of a new snapshot that may change for the same period
+    fn patch(self) -> LessonPatch {
+        LessonPatch {
+            date: Some(self.date),
+            start: Some(self.start),
+            end: Some(self.end),
+            subjects: Some(self.subjects),
+            teachers: Some(self.teachers),
+            rooms: Some(self.rooms),
+            klassen: Some(self.klassen),
+            original_teachers: Some(self.original_teachers),
+            original_rooms: Some(self.original_rooms),
+            status: Some(self.status),
+            text: Some(self.text),
+            substitution_text: Some(self.substitution_text),
+            info: Some(self.info),
+            updated_at: Some(self.updated_at),
+        }
+    }
+}
+
+impl Lesson {
+    /// Returns whether the lesson has the content of a new snapshot, ignoring `updated_at`
+    fn matches(&self, lesson: &LessonCreate) -> bool {
+        self.date == lesson.date
+            && self.start == lesson.start
+            && self.end == lesson.end
+            && self.subjects == lesson.subjects
+            && self.teachers == lesson.teachers
+            && self.rooms == lesson.rooms
+            && self.klassen == lesson.klassen
+            && self.original_teachers == lesson.original_teachers
+            && self.original_rooms == lesson.original_rooms
+            && self.status == lesson.status
+            && self.text == lesson.text
+            && self.substitution_text == lesson.substitution_text
+            && self.info == lesson.info
+    }
+
+    /// Get the lessons of a user between two days
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user
+    /// * `from` - The first day, `yyyy-mm-dd`
+    /// * `to` - The last day, included
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<Vec<Lesson>, Error>` - The lessons, by start
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn between(
+        db: &DBConnection,
+        user: &Thing,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<Lesson>, Error> {
+        let mut res = db
+            .query(
+                "SELECT * FROM lesson WHERE user = $user AND date >= $from AND date <= $to \
+                ORDER BY start, period",
+            )
+            .bind(("user", user))
+            .bind(("from", from))
+            .bind(("to", to))
+            .await?;
+        let lessons: Vec<Lesson> = res.take(0)?;
+
+        Ok(lessons)
+    }
+
+    /// Replace the lessons of a user between two days with a new snapshot
+    ///
+    /// Only the differences are written, atomically.
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user
+    /// * `from` - The first day of the snapshot, `yyyy-mm-dd`
+    /// * `to` - The last day of the snapshot, included
+    /// * `lessons` - The lessons of the snapshot
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<Vec<LessonChange>, Error>` - The differences with the previous snapshot
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Transaction` - The differences cannot be written, nothing was
+    /// * `Error` - The error returned by the database
+    pub async fn replace(
+        db: &DBConnection,
+        user: &Thing,
+        from: &str,
+        to: &str,
+        lessons: Vec<LessonCreate>,
+    ) -> Result<Vec<LessonChange>, Error> {
+        // A period may have moved into the snapshot from another day
+        let periods: Vec<i64> = lessons.iter().map(|lesson| lesson.period).collect();
+        let mut res = db
+            .query(
+                "SELECT * FROM lesson WHERE user = $user \
+                AND ((date >= $from AND date <= $to) OR period INSIDE $periods)",
+            )
+            .bind(("user", user))
+            .bind(("from", from))
+            .bind(("to", to))
+            .bind(("periods", periods))
+            .await?;
+        let previous: Vec<Lesson> = res.take(0)?;
+        let mut previous: HashMap<i64, Lesson> = previous
+            .into_iter()
+            .map(|lesson| (lesson.period, lesson))
+            .collect();
+
+        let mut tx = Transaction::new();
+        let mut written = Vec::new();
+        for lesson in lessons {
+            let before = previous.remove(&lesson.period);
+            match before {
+                Some(before) if before.matches(&lesson) => {}
+                Some(before) => {
+                    let after = tx.update_merge::<Lesson>(&before.id, lesson.patch(), None)?;
+                    written.push((Some(before), after));
+                }
+                None => written.push((None, tx.create::<Lesson>(Self::TABLE, lesson)?)),
+            }
+        }
+        let removed: Vec<Lesson> = previous.into_values().collect();
+        let deleted = if !removed.is_empty() {
+            let ids: Vec<&Thing> = removed.iter().map(|lesson| &lesson.id).collect();
+            tx.bind("removed", ids)?;
+            Some(tx.query::<Lesson>("DELETE lesson WHERE id INSIDE $removed RETURN BEFORE"))
+        } else {
+            None
+        };
+        if written.is_empty() && removed.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut committed = tx.commit(db).await?;
+        let mut changes = Vec::with_capacity(written.len() + removed.len());
+        for (before, after) in written {
+            let after = committed.record(after)?;
+            changes.push(match before {
+                Some(before) => LessonChange::Changed { before, after },
+                None => LessonChange::Added(after),
+            });
+        }
+        if let Some(deleted) = deleted {
+            changes.extend(committed.rows(deleted)?.into_iter().map(LessonChange::Removed));
+        }
+
+        Ok(changes)
+    }
+}
+
+impl TimetableSync {
+    /// Returns the id of the synchronisation state of a user
+    pub fn id_of(user: &Thing) -> Thing {
+        Thing::from(("timetable_sync", user.id.clone()))
+    }
+
+    /// Get the synchronisation state of a user
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn of(db: &DBConnection, user: &Thing) -> Result<Option<TimetableSync>, Error> {
+        let sync = db.select(Self::id_of(user)).await?;
+
+        Ok(sync)
+    }
+
+    /// Returns whether the cache covers the days between `from` and `to` and is recent
+    ///
+    /// ## Arguments
+    ///
+    /// * `from` - The first day, `yyyy-mm-dd`
+    /// * `to` - The last day, included
+    /// * `max_age` - The age after which the cache is stale
+    pub fn is_fresh(&self, from: &str, to: &str, max_age: Duration) -> bool {
+        let covered = match (&self.synced_from, &self.synced_to) {
+            (Some(synced_from), Some(synced_to)) => {
+                synced_from.as_str() <= from && to <= synced_to.as_str()
+            }
+            _ => false,
+        };
+        let recent = self
+            .synced_at
+            .as_deref()
+            .is_some_and(|date| is_recent(date, max_age));
+
+        covered && recent
+    }
+
+    /// Returns whether a synchronisation was attempted less than `cooldown` ago
+    pub fn attempted_within(&self, cooldown: Duration) -> bool {
+        is_recent(&self.attempted_at, cooldown)
+    }
+
+    /// Record the outcome of a synchronisation
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user
+    /// * `window` - The first and last day synchronised, `None` if the synchronisation failed
+    /// * `error` - Why the synchronisation failed, `None` if it succeeded
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn record(
+        db: &DBConnection,
+        user: &Thing,
+        window: Option<(&str, &str)>,
+        error: Option<String>,
+    ) -> Result<TimetableSync, Error> {
+        let query = match window {
+            Some(_) => {
+                "UPDATE $id SET user = $user, synced_from = $from, synced_to = $to, \
+                synced_at = $now, attempted_at = $now, error = NONE RETURN AFTER"
+            }
+            None => "UPDATE $id SET user = $user, attempted_at = $now, error = $error RETURN AFTER",
+        };
+        let (from, to) = window.unzip();
+        let mut res = db
+            .query(query)
+            .bind(("id", Self::id_of(user)))
+            .bind(("user", user))
+            .bind(("from", from))
+            .bind(("to", to))
+            .bind(("now", Utc::now().to_rfc3339()))
+            .bind(("error", error))
+            .await?;
+        let sync: Option<TimetableSync> = res.take(0)?;
+
+        sync.ok_or(Error::NoRecord)
+    }
+
+    /// Delete the cached timetable of a user and its synchronisation state
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn clear(db: &DBConnection, user: &Thing) -> Result<(), Error> {
+        db.query("DELETE lesson WHERE user = $user; DELETE $id;")
+            .bind(("user", user))
+            .bind(("id", Self::id_of(user)))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+}
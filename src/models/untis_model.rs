@@ -0,0 +1,257 @@
+// models/untis_model.rs
+use backend_derive::Model;
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+use super::{
+    calendar_model::CalendarToken, model::DBConnection, notification_model::Notification,
+    timetable_model::TimetableSync,
+};
+use crate::{prelude::Error, utils::crypto::Cipher};
+
+/// Untis Account Struct, the WebUntis account linked to a user
+///
+/// The record shares the id of its user, `untis_account:⟨id⟩` for `users:⟨id⟩`.
+/// The credentials are sealed with the application key and bound to the user.
+///
+/// ## Fields
+///
+/// * `ID` is the id of the user in the `untis_account` table
+/// * `User` is the user
+/// * `Server` is the WebUntis server of the school
+/// * `School` is the login name of the school
+/// * `Credentials` are the sealed user name and password, never returned
+/// * `VerifiedAt` is the last time the credentials were accepted
+/// * `LastError` is the reason the last verification failed, if it did
+/// * `LinkedAt` is the date the account was linked
+#[derive(Debug, Serialize, Deserialize, Clone, Model)]
+#[model(table = "untis_account")]
+pub struct UntisAccount {
+    #[model(id)]
+    pub id: Thing,
+    #[model(create, unique)]
+    pub user: Thing,
+    #[model(create)]
+    pub server: String,
+    #[model(create)]
+    pub school: String,
+    #[model(create)]
+    pub credentials: String,
+    #[model(create)]
+    pub verified_at: Option<String>,
+    #[model(create)]
+    pub last_error: Option<String>,
+    #[model(create)]
+    pub linked_at: String,
+}
+
+/// Untis Credentials Struct, the sealed part of an account
+///
+/// ## Fields
+///
+/// * `Username` is the WebUntis user name
+/// * `Password` is the WebUntis password
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UntisCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+/// Untis Link Struct, the public view of a linked account
+///
+/// ## Fields
+///
+/// * `Server` is the WebUntis server of the school
+/// * `School` is the login name of the school
+/// * `VerifiedAt` is the last time the credentials were accepted
+/// * `LastError` is the reason the last verification failed, if it did
+/// * `LinkedAt` is the date the account was linked
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UntisLink {
+    pub server: String,
+    pub school: String,
+    pub verified_at: Option<String>,
+    pub last_error: Option<String>,
+    pub linked_at: String,
+}
+
+impl From<UntisAccount> for UntisLink {
+    fn from(account: UntisAccount) -> Self {
+        Self {
+            server: account.server,
+            school: account.school,
+            verified_at: account.verified_at,
+            last_error: account.last_error,
+            linked_at: account.linked_at,
+        }
+    }
+}
+
+impl UntisAccount {
+    /// Returns the id of the account of a user
+    pub fn id_of(user: &Thing) -> Thing {
+        Thing::from(("untis_account", user.id.clone()))
+    }
+
+    /// Get the account linked to a user
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn of(db: &DBConnection, user: &Thing) -> Result<Option<UntisAccount>, Error> {
+        let account = db.select(Self::id_of(user)).await?;
+
+        Ok(account)
+    }
+
+    /// Get the linked accounts of the active users
+    ///
+    /// The accounts of deleted or inactive users are skipped, their timetables are not kept
+    /// in sync.
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn all(db: &DBConnection) -> Result<Vec<UntisAccount>, Error> {
+        let mut res = db
+            .query(
+                "SELECT * FROM untis_account \
+                WHERE user.deleted_at = NONE AND user.is_inactive = false",
+            )
+            .await?;
+        let accounts: Vec<UntisAccount> = res.take(0)?;
+
+        Ok(accounts)
+    }
+
+    /// Link an account to a user, replacing the linked one, with verified credentials
+    ///
+    /// The timetable cached from the replaced account is deleted.
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `cipher` - The cipher sealing the credentials
+    /// * `user` - The id of the user
+    /// * `server` - The WebUntis server of the school
+    /// * `school` - The login name of the school
+    /// * `credentials` - The credentials, accepted by the server
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Encryption` - The credentials cannot be sealed
+    /// * `Error` - The error returned by the database
+    pub async fn link(
+        db: &DBConnection,
+        cipher: &Cipher,
+        user: &Thing,
+        server: &str,
+        school: &str,
+        credentials: &UntisCredentials,
+    ) -> Result<UntisAccount, Error> {
+        // Safety: serializing two strings cannot fail
+        let secret = serde_json::to_vec(credentials).unwrap();
+        let now = chrono::Utc::now().to_rfc3339();
+        let account = UntisAccountCreate {
+            user: user.clone(),
+            server: server.to_string(),
+            school: school.to_string(),
+            credentials: cipher.seal(&secret, &user.to_string())?,
+            verified_at: Some(now.clone()),
+            last_error: None,
+            linked_at: now,
+        };
+
+        TimetableSync::clear(db, user).await?;
+        let mut res = db
+            .query("UPDATE $id CONTENT $account")
+            .bind(("id", Self::id_of(user)))
+            .bind(("account", account))
+            .await?;
+        let account: Option<UntisAccount> = res.take(0)?;
+
+        account.ok_or(Error::NoRecord)
+    }
+
+    /// Open the sealed credentials
+    ///
+    /// ## Arguments
+    ///
+    /// * `cipher` - The cipher that sealed the credentials
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Encryption` - The credentials were sealed with another key or were tampered with
+    pub fn credentials(&self, cipher: &Cipher) -> Result<UntisCredentials, Error> {
+        let secret = cipher.open(&self.credentials, &self.user.to_string())?;
+
+        serde_json::from_slice(&secret)
+            .map_err(|_| Error::Encryption("the credentials are malformed".to_string()))
+    }
+
+    /// Record the outcome of a verification of the credentials
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user
+    /// * `error` - Why the verification failed, `None` if the credentials were accepted
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::NotFound` - The user has no linked account
+    /// * `Error` - The error returned by the database
+    pub async fn record_verification(
+        db: &DBConnection,
+        user: &Thing,
+        error: Option<String>,
+    ) -> Result<UntisAccount, Error> {
+        let query = match error {
+            None => "UPDATE $id SET verified_at = $now, last_error = NONE RETURN AFTER",
+            Some(_) => "UPDATE $id SET last_error = $error RETURN AFTER",
+        };
+        let id = Self::id_of(user);
+        let mut res = db
+            .query(format!("IF $id.id != NONE THEN ({query}) END"))
+            .bind(("id", &id))
+            .bind(("now", chrono::Utc::now().to_rfc3339()))
+            .bind(("error", error))
+            .await?;
+        let account: Option<UntisAccount> = res.take(0)?;
+
+        account.ok_or_else(|| Error::NotFound(id.to_string()))
+    }
+
+    /// Unlink the account of a user, deleting the timetable cached from it, its notifications
+    /// and its calendar feed
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `user` - The id of the user
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<bool, Error>` - Whether the user had a linked account
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn unlink(db: &DBConnection, user: &Thing) -> Result<bool, Error> {
+        TimetableSync::clear(db, user).await?;
+        Notification::clear(db, user).await?;
+        CalendarToken::revoke(db, user).await?;
+        let account: Option<UntisAccount> = db.delete(Self::id_of(user)).await?;
+
+        Ok(account.is_some())
+    }
+}
@@ -1,13 +1,26 @@
 // models/user_model.rs
-use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use argon2::{
+    password_hash::SaltString, Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+};
+use backend_derive::Model;
+use rand_core::OsRng;
 use serde::{Deserialize, Serialize};
 use surrealdb::sql::Thing;
 
-use super::model::{ConnectionData, DBConnection, CRUD};
+use super::model::{ConnectionData, Viewer, Watchable};
 use crate::prelude::Error;
 
+/// Argon2 hash no password matches, with the parameters of `Argon2::default`
+pub const DUMMY_PASSWORD_HASH: &str = concat!(
+    "$argon2id$v=19$m=19456,t=2,p=1",
+    "$JLWD5VdNZRlUQh4GFuKeww$zYDSqbKmUK5jzTT4lycYWMBfCnAljYk9VyusmaaOtJY"
+);
+
 /// User Struct
 ///
+/// `UserCreate`, `UserPatch`, the schema of the `users` table and the `CRUD` implementation
+/// are generated from the `model` attributes.
+///
 /// ## Fields
 ///
 /// * `ID` is the user's unique identifier
@@ -19,73 +32,123 @@ use crate::prelude::Error;
 /// * `CreationDate` is the user's creation date
 /// * `IsVisible` is the user's visibility
 /// * `IsInactive` is the user's inactivitys
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// * `Version` is incremented on every write, for optimistic concurrency
+/// * `DeletedAt` is the date the user was soft-deleted, if they were
+#[derive(Debug, Serialize, Deserialize, Clone, Model)]
+#[model(table = "users", soft_delete)]
 pub struct User {
+    #[model(id)]
     pub id: Thing,
+    #[model(create, list, unique)]
     pub peer_id: String,
+    #[model(create, patch, list)]
     pub name: String,
+    #[model(create, patch)]
     pub avatar: String,
+    #[model(create, unique, assert = "is::email($value)")]
     pub email: String,
+    #[model(create)]
     pub password_hash: String,
+    #[model(create, list)]
     pub creation_date: String,
+    #[model(create, patch, list)]
     pub is_visible: bool,
+    #[model(create)]
     pub is_inactive: bool,
+    #[model(version)]
+    pub version: u64,
+    pub deleted_at: Option<String>,
 }
 
-/// User Create Struct
+/// User Profile Struct, the view of a user by themselves
 ///
 /// ## Fields
 ///
+/// * `ID` is the user's unique identifier
 /// * `PeerID` is the user's unique identifier
 /// * `Name` is the user's name
 /// * `Avatar` is the user's avatar
 /// * `Email` is the user's email
-/// * `PasswordHash` is the user's password hash
 /// * `CreationDate` is the user's creation date
 /// * `IsVisible` is the user's visibility
-/// * `IsInactive` is the user's inactivitys
+/// * `Version` is the version of the user, also sent as the `ETag`
+/// * `DeletedAt` is the date the user was soft-deleted, if they were
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct UserCreate {
+pub struct UserProfile {
+    pub id: String,
     pub peer_id: String,
     pub name: String,
     pub avatar: String,
     pub email: String,
-    pub password_hash: String,
     pub creation_date: String,
     pub is_visible: bool,
-    pub is_inactive: bool,
+    pub version: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
 }
 
-#[async_trait::async_trait]
-impl CRUD<User, UserCreate> for User {
-    /// Initialize the user table
-    ///
-    /// ## Arguments
-    /// `db` is the database connection
-    ///
-    /// ## Returns
-    /// Initializes the user table with the given databases
-    async fn init_table(db: DBConnection) -> Result<(), Error> {
-        let sql = "DEFINE TABLE users SCHEMAFULL;\
-                DEFINE FIELD email ON users TYPE string ASSERT is::email($value);\
-                DEFINE INDEX email ON TABLE users COLUMNS email UNIQUE;\
-                DEFINE FIELD peer_id ON users TYPE string;\
-                DEFINE INDEX peer_id ON TABLE users COLUMNS peer_id UNIQUE;\
-                DEFINE FIELD name ON users TYPE string;\
-                DEFINE FIELD avatar ON users TYPE string;\
-                DEFINE FIELD password_hash ON users TYPE string;\
-                DEFINE FIELD creation_date ON users TYPE string;\
-                DEFINE FIELD is_visible ON users TYPE bool;\
-                DEFINE FIELD is_inactive ON users TYPE bool;";
-        db.query(sql).await?;
-
-        Ok(())
+impl From<User> for UserProfile {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id.to_string(),
+            peer_id: user.peer_id,
+            name: user.name,
+            avatar: user.avatar,
+            email: user.email,
+            creation_date: user.creation_date,
+            is_visible: user.is_visible,
+            version: user.version,
+            deleted_at: user.deleted_at,
+        }
+    }
+}
+
+/// Public Profile Struct, the view of a user by other users, without their email
+///
+/// ## Fields
+///
+/// * `ID` is the user's unique identifier
+/// * `PeerID` is the user's unique identifier
+/// * `Name` is the user's name
+/// * `Avatar` is the user's avatar
+/// * `CreationDate` is the user's creation date
+/// * `IsVisible` is the user's visibility
+/// * `Version` is the version of the user, also sent as the `ETag`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PublicProfile {
+    pub id: String,
+    pub peer_id: String,
+    pub name: String,
+    pub avatar: String,
+    pub creation_date: String,
+    pub is_visible: bool,
+    pub version: u64,
+}
+
+impl From<User> for PublicProfile {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id.to_string(),
+            peer_id: user.peer_id,
+            name: user.name,
+            avatar: user.avatar,
+            creation_date: user.creation_date,
+            is_visible: user.is_visible,
+            version: user.version,
+        }
+    }
+}
+
+impl Watchable<User> for User {
+    /// Users see their own changes and those of visible users, unless either blocked the other
+    fn can_watch(viewer: &Viewer, record: &User) -> bool {
+        record.id == viewer.user.id || (record.is_visible && !viewer.blocked.contains(&record.id))
     }
 }
 
 #[allow(dead_code)]
 impl User {
-    /// Get a user from their Peer ID
+    /// Get a user from their Peer ID, soft-deleted users are not found
     ///
     /// ## Arguments
     ///
@@ -104,7 +167,7 @@ impl User {
         peer_id: &str,
     ) -> Result<Option<User>, Error> {
         let mut res = db
-            .query("SELECT * FROM users WHERE peer_id=$id")
+            .query("SELECT * FROM users WHERE peer_id=$id AND deleted_at=NONE")
             .bind(("id", peer_id))
             .await?;
         let user = res.take(0)?;
@@ -112,7 +175,7 @@ impl User {
         Ok(user)
     }
 
-    /// Get a user from their email
+    /// Get a user from their email, soft-deleted users are not found
     ///
     /// ## Arguments
     ///
@@ -128,7 +191,34 @@ impl User {
     /// * `Error` - The error returned by the database
     pub async fn get_from_email(db: ConnectionData, email: &str) -> Result<Option<User>, Error> {
         let mut res = db
-            .query("SELECT * FROM users WHERE email=$email")
+            .query("SELECT * FROM users WHERE email=$email AND deleted_at=NONE")
+            .bind(("email", email))
+            .await?;
+        let user = res.take(0)?;
+
+        Ok(user)
+    }
+
+    /// Get a soft-deleted user from their email
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    /// * `email` - The user's email
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<Option<User>, Error>` - The user, `None` if they are not deleted
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The error returned by the database
+    pub async fn get_deleted_from_email(
+        db: ConnectionData,
+        email: &str,
+    ) -> Result<Option<User>, Error> {
+        let mut res = db
+            .query("SELECT * FROM users WHERE email=$email AND deleted_at!=NONE")
             .bind(("email", email))
             .await?;
         let user = res.take(0)?;
@@ -155,4 +245,40 @@ impl User {
 
         argon2.verify_password(password.as_bytes(), &hash)
     }
+
+    /// Verify a password against `DUMMY_PASSWORD_HASH`, which always fails
+    ///
+    /// Called when no user has the email of a login, so that it takes as long as a wrong
+    /// password and does not tell which emails are registered.
+    ///
+    /// ## Arguments
+    ///
+    /// * `password` - The password of the login
+    pub fn verify_dummy_password(password: &str) {
+        if let Ok(hash) = PasswordHash::new(DUMMY_PASSWORD_HASH) {
+            let _ = Argon2::default().verify_password(password.as_bytes(), &hash);
+        }
+    }
+
+    /// Hash a password with Argon2 and a random salt
+    ///
+    /// ## Arguments
+    ///
+    /// * `password` - The plaintext password
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<String, Error>` - The PHC string of the hash
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::PasswordHash` - The password could not be hashed
+    pub fn hash_password(password: &str) -> Result<String, Error> {
+        let salt = SaltString::generate(&mut OsRng);
+
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|_| Error::PasswordHash)
+    }
 }
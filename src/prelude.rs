@@ -11,12 +11,12 @@ pub use crate::error::Error;
 #[macro_export]
 macro_rules! internalError {
     ($l:literal) => {{
-        return Ok(actix_web::web::Json($crate::api::response::Response::new_error(500, $l.to_string())));
+        return Ok($crate::api::response::Response::new_error(500, $l.to_string()));
     }};
     () => {{
-        return Ok(actix_web::web::Json($crate::api::response::Response::new_error(
+        return Ok($crate::api::response::Response::new_error(
             500,
             "Internal Server Error".to_string(),
-        )));
+        ));
     }};
 }
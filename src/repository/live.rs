@@ -0,0 +1,376 @@
+// repository/live.rs
+use std::{
+    collections::{hash_map::Entry, HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use actix_web::rt::{self, task::JoinHandle};
+use futures::StreamExt;
+use log::{error, info};
+use serde::Serialize;
+use serde_json::Value;
+use surrealdb::{sql::Thing, Notification};
+use tokio::sync::{broadcast, oneshot};
+use uuid::Uuid;
+
+use crate::{models::model::DBConnection, prelude::Error};
+
+/// Number of changes kept per table to replay to reconnecting clients
+const HISTORY: usize = 1024;
+
+/// What happened to a record
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeAction {
+    Create,
+    Update,
+    Delete,
+}
+
+/// A change notified by a live query
+///
+/// ## Fields
+///
+/// * `position` is the number of changes of the feed up to and including this one
+/// * `action` is what happened to the record
+/// * `data` is the record, or only its id for a deletion
+/// * `before` is the last known record before the change, `None` for a new record
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub position: u64,
+    pub action: ChangeAction,
+    pub data: Value,
+    pub before: Option<Value>,
+}
+
+/// Get the id of the record of a change
+pub fn record_id(data: &Value) -> Option<String> {
+    // Deletions only carry the id, other changes carry the whole record
+    let id = data.get("id").unwrap_or(data);
+
+    serde_json::from_value::<Thing>(id.clone())
+        .ok()
+        .map(|id| id.to_string())
+}
+
+/// The live query of a table, shared by every subscriber
+struct Feed {
+    generation: u64,
+    subscribers: usize,
+    sender: broadcast::Sender<Arc<Change>>,
+    history: Arc<Mutex<History>>,
+    task: JoinHandle<()>,
+}
+
+/// The last changes of a feed
+#[derive(Default)]
+struct History {
+    position: u64,
+    changes: VecDeque<Arc<Change>>,
+}
+
+/// Hub sharing one `LIVE SELECT` per table between every subscriber
+///
+/// A table's live query starts with its first subscriber and is killed when the last
+/// one disconnects. Changes are numbered per feed so clients can resume after a
+/// reconnection with `Last-Event-ID`.
+///
+/// A feed keeps the last known version of every record of its table, deletions only
+/// carry the id and subscribers still need the record to know who may see them.
+///
+/// ## Fields
+///
+/// * `db` is the database connection
+/// * `epoch` identifies this process, positions of another process are unknown
+/// * `feeds` are the running feeds, by table
+#[derive(Clone)]
+pub struct LiveHub {
+    db: DBConnection,
+    epoch: String,
+    feeds: Arc<Mutex<HashMap<&'static str, Feed>>>,
+    generations: Arc<Mutex<u64>>,
+}
+
+/// A cursor in a feed, sent as the SSE event id
+///
+/// ## Fields
+///
+/// * `epoch` is the epoch of the hub
+/// * `generation` is the generation of the feed
+/// * `position` is the number of changes seen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub epoch: String,
+    pub generation: u64,
+    pub position: u64,
+}
+
+impl Cursor {
+    /// Parse a cursor from a `Last-Event-ID`
+    pub fn parse(id: &str) -> Option<Self> {
+        let mut parts = id.split('-');
+        let cursor = Self {
+            epoch: parts.next()?.to_string(),
+            generation: parts.next()?.parse().ok()?,
+            position: parts.next()?.parse().ok()?,
+        };
+
+        parts.next().is_none().then_some(cursor)
+    }
+}
+
+impl std::fmt::Display for Cursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}-{}", self.epoch, self.generation, self.position)
+    }
+}
+
+/// A subscription to the changes of a table
+///
+/// Dropping it unsubscribes, killing the live query if it was the last subscriber
+///
+/// ## Fields
+///
+/// * `cursor` is the position of the subscription when it was created
+/// * `replay` are the changes missed since `Last-Event-ID`, `None` if they are lost
+/// * `receiver` receives the next changes
+pub struct Subscription {
+    pub cursor: Cursor,
+    pub replay: Option<Vec<Arc<Change>>>,
+    pub receiver: broadcast::Receiver<Arc<Change>>,
+    hub: LiveHub,
+    table: &'static str,
+    generation: u64,
+}
+
+impl LiveHub {
+    /// Create a hub without any running feed
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    pub fn new(db: DBConnection) -> Self {
+        Self {
+            db,
+            epoch: Uuid::new_v4().simple().to_string(),
+            feeds: Arc::default(),
+            generations: Arc::default(),
+        }
+    }
+
+    /// Subscribe to the changes of a table, starting its live query if needed
+    ///
+    /// ## Arguments
+    ///
+    /// * `table` - The table name
+    /// * `last_event_id` - The `Last-Event-ID` of a reconnecting client
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<Subscription, Error>` - The subscription
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The live query cannot be started
+    pub async fn subscribe(
+        &self,
+        table: &'static str,
+        last_event_id: Option<&str>,
+    ) -> Result<Subscription, Error> {
+        loop {
+            if let Some(feed) = self.feeds.lock().unwrap().get_mut(table) {
+                return Ok(self.attach(table, feed, last_event_id));
+            }
+
+            let feed = self.start(table).await?;
+            // Another subscriber may have started the feed meanwhile, theirs wins
+            match self.feeds.lock().unwrap().entry(table) {
+                Entry::Occupied(_) => feed.task.abort(),
+                Entry::Vacant(entry) => {
+                    entry.insert(feed);
+                }
+            }
+        }
+    }
+
+    /// Add a subscriber to a running feed
+    fn attach(
+        &self,
+        table: &'static str,
+        feed: &mut Feed,
+        last_event_id: Option<&str>,
+    ) -> Subscription {
+        feed.subscribers += 1;
+
+        // Subscribe and snapshot under the history lock so no change is missed or repeated
+        let history = feed.history.lock().unwrap();
+        let receiver = feed.sender.subscribe();
+        let cursor = Cursor {
+            epoch: self.epoch.clone(),
+            generation: feed.generation,
+            position: history.position,
+        };
+        let replay = last_event_id
+            .map(|id| {
+                let last = Cursor::parse(id)?;
+                let oldest = history
+                    .changes
+                    .front()
+                    .map(|change| change.position - 1)
+                    .unwrap_or(history.position);
+
+                (last.epoch == cursor.epoch
+                    && last.generation == cursor.generation
+                    && last.position >= oldest
+                    && last.position <= cursor.position)
+                    .then(|| {
+                        history
+                            .changes
+                            .iter()
+                            .filter(|change| change.position > last.position)
+                            .cloned()
+                            .collect()
+                    })
+            })
+            .unwrap_or_else(|| Some(Vec::new()));
+        drop(history);
+
+        Subscription {
+            cursor,
+            replay,
+            receiver,
+            hub: self.clone(),
+            table,
+            generation: feed.generation,
+        }
+    }
+
+    /// Start the live query of a table
+    async fn start(&self, table: &'static str) -> Result<Feed, Error> {
+        let generation = {
+            let mut generations = self.generations.lock().unwrap();
+            *generations += 1;
+            *generations
+        };
+        let (sender, _) = broadcast::channel(HISTORY);
+        let history = Arc::new(Mutex::new(History::default()));
+
+        let (started, start_result) = oneshot::channel();
+
+        info!("📡 Starting live query on {table}");
+        let task = rt::spawn({
+            let db = self.db.clone();
+            let feeds = self.feeds.clone();
+            let sender = sender.clone();
+            let history = history.clone();
+
+            async move {
+                // The stream borrows the connection, so it lives in the task with it
+                let mut stream = match db.select::<Vec<Value>>(table).live().await {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        let _ = started.send(Err(e));
+                        return;
+                    }
+                };
+                // Changes made meanwhile are queued in the stream and applied afterwards
+                let records = match db.select::<Vec<Value>>(table).await {
+                    Ok(records) => {
+                        let _ = started.send(Ok(()));
+                        records
+                    }
+                    Err(e) => {
+                        let _ = started.send(Err(e));
+                        return;
+                    }
+                };
+                let mut records: HashMap<String, Value> = records
+                    .into_iter()
+                    .filter_map(|record| Some((record_id(&record)?, record)))
+                    .collect();
+
+                while let Some(notification) = stream.next().await {
+                    let notification: Notification<Value> = match notification {
+                        Ok(notification) => notification,
+                        Err(e) => {
+                            error!("🔥 Live query on {table} failed: {e}");
+                            continue;
+                        }
+                    };
+                    let action = match notification.action {
+                        surrealdb::Action::Create => ChangeAction::Create,
+                        surrealdb::Action::Update => ChangeAction::Update,
+                        surrealdb::Action::Delete => ChangeAction::Delete,
+                        _ => continue,
+                    };
+                    let before = match (action, record_id(&notification.data)) {
+                        (_, None) => None,
+                        (ChangeAction::Delete, Some(id)) => records.remove(&id),
+                        (_, Some(id)) => records.insert(id, notification.data.clone()),
+                    };
+
+                    let mut history = history.lock().unwrap();
+                    history.position += 1;
+                    let change = Arc::new(Change {
+                        position: history.position,
+                        action,
+                        data: notification.data,
+                        before,
+                    });
+                    if history.changes.len() == HISTORY {
+                        history.changes.pop_front();
+                    }
+                    history.changes.push_back(change.clone());
+                    // Nobody listening is fine, the change stays in the history
+                    let _ = sender.send(change);
+                }
+
+                // The stream ended, let the next subscriber start a new feed
+                error!("🔥 Live query on {table} ended");
+                let mut feeds = feeds.lock().unwrap();
+                if feeds.get(table).is_some_and(|feed| feed.generation == generation) {
+                    feeds.remove(table);
+                }
+            }
+        });
+
+        match start_result.await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => return Err(e.into()),
+            Err(_) => {
+                return Err(Error::Live(format!(
+                    "live query on {table} stopped before starting"
+                )))
+            }
+        }
+
+        Ok(Feed {
+            generation,
+            subscribers: 0,
+            sender,
+            history,
+            task,
+        })
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut feeds = self.hub.feeds.lock().unwrap();
+        let Some(feed) = feeds.get_mut(self.table) else {
+            return;
+        };
+        if feed.generation != self.generation {
+            return;
+        }
+
+        feed.subscribers -= 1;
+        if feed.subscribers == 0 {
+            // Dropping the live stream kills the live query
+            info!("📴 Stopping live query on {}", self.table);
+            if let Some(feed) = feeds.remove(self.table) {
+                feed.task.abort();
+            }
+        }
+    }
+}
@@ -0,0 +1,266 @@
+// repository/migrations.rs
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    models::{
+        calendar_model::CalendarToken, contact_model::Contact, model::DBConnection, model::CRUD,
+        notification_model::Notification, presence_model::Presence,
+        timetable_model::{Lesson, TimetableSync},
+        untis_model::UntisAccount,
+        user_model::User,
+    },
+    prelude::Error,
+};
+
+/// A numbered schema migration
+///
+/// ## Fields
+///
+/// * `version` is the unique, increasing version of the migration
+/// * `name` is a short description of the migration
+/// * `up` is the SurrealQL applying the migration
+/// * `down` is the SurrealQL reverting the migration, `None` if it is irreversible
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: Option<&'static str>,
+}
+
+impl Migration {
+    /// Returns the SHA-256 checksum of the `up` statement
+    pub fn checksum(&self) -> String {
+        format!("{:x}", Sha256::digest(self.up.as_bytes()))
+    }
+}
+
+/// Registry of every migration, ordered by version
+///
+/// Never edit a migration once it has shipped, add a new one instead:
+/// the checksum of applied migrations is verified on boot.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "users_creation_date_index",
+        up: "DEFINE INDEX creation_date ON TABLE users COLUMNS creation_date;",
+        down: Some("REMOVE INDEX creation_date ON TABLE users;"),
+    },
+    Migration {
+        version: 2,
+        name: "users_lowercase_email",
+        up: "UPDATE users SET email = string::lowercase(email);",
+        down: None,
+    },
+    Migration {
+        version: 3,
+        name: "users_version",
+        up: "UPDATE users SET version = 1 WHERE version = NONE;",
+        down: None,
+    },
+];
+
+/// A migration recorded in the `migrations` table
+///
+/// ## Fields
+///
+/// * `version` is the version of the migration
+/// * `name` is the name of the migration
+/// * `checksum` is the checksum of the migration when it was applied
+/// * `applied_at` is the date the migration was applied
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AppliedMigration {
+    pub version: u32,
+    pub name: String,
+    pub checksum: String,
+    pub applied_at: String,
+}
+
+/// What to do with migrations on boot
+///
+/// ## Variants
+///
+/// * `Apply` applies every pending migration
+/// * `Verify` only checks that every migration is applied and unchanged
+/// * `Rollback` reverts every migration above the given version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationMode {
+    Apply,
+    Verify,
+    Rollback(u32),
+}
+
+/// Define the table of every CRUD model
+///
+/// `DEFINE` statements are idempotent, so this runs on every boot
+async fn init_tables(db: &DBConnection) -> Result<(), Error> {
+    db.query(
+        "DEFINE TABLE migrations SCHEMAFULL;\
+        DEFINE FIELD version ON migrations TYPE int;\
+        DEFINE INDEX version ON TABLE migrations COLUMNS version UNIQUE;\
+        DEFINE FIELD name ON migrations TYPE string;\
+        DEFINE FIELD checksum ON migrations TYPE string;\
+        DEFINE FIELD applied_at ON migrations TYPE string;",
+    )
+    .await?
+    .check()?;
+
+    User::init_table(db.clone()).await?;
+    Presence::init_table(db.clone()).await?;
+    Contact::init_table(db.clone()).await?;
+    UntisAccount::init_table(db.clone()).await?;
+    Lesson::init_table(db.clone()).await?;
+    TimetableSync::init_table(db.clone()).await?;
+    Notification::init_table(db.clone()).await?;
+    CalendarToken::init_table(db.clone()).await?;
+
+    Ok(())
+}
+
+/// Get the applied migrations, ordered by version
+async fn applied(db: &DBConnection) -> Result<Vec<AppliedMigration>, Error> {
+    let mut res = db
+        .query("SELECT * FROM migrations ORDER BY version ASC")
+        .await?;
+    let applied = res.take(0)?;
+
+    Ok(applied)
+}
+
+/// Check that every applied migration is registered and unchanged
+fn verify_checksums(applied: &[AppliedMigration]) -> Result<(), Error> {
+    for record in applied {
+        let migration = MIGRATIONS
+            .iter()
+            .find(|m| m.version == record.version)
+            .ok_or_else(|| {
+                Error::Migration(format!(
+                    "migration {} ({}) is applied but not registered",
+                    record.version, record.name
+                ))
+            })?;
+
+        if migration.checksum() != record.checksum {
+            return Err(Error::Migration(format!(
+                "migration {} ({}) was modified after being applied",
+                record.version, record.name
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Apply a single migration and record it, atomically
+async fn apply(db: &DBConnection, migration: &Migration) -> Result<(), Error> {
+    info!("⬆️ Applying migration {} ({})", migration.version, migration.name);
+
+    let record = AppliedMigration {
+        version: migration.version,
+        name: migration.name.to_string(),
+        checksum: migration.checksum(),
+        applied_at: chrono::Utc::now().to_rfc3339(),
+    };
+
+    db.query(format!(
+        "BEGIN TRANSACTION;\
+        {}\
+        CREATE type::thing('migrations', $version) CONTENT $record;\
+        COMMIT TRANSACTION;",
+        migration.up
+    ))
+    .bind(("version", migration.version))
+    .bind(("record", record))
+    .await?
+    .check()?;
+
+    Ok(())
+}
+
+/// Revert a single migration and delete its record, atomically
+async fn revert(db: &DBConnection, migration: &Migration) -> Result<(), Error> {
+    let down = migration.down.ok_or_else(|| {
+        Error::Migration(format!(
+            "migration {} ({}) is irreversible",
+            migration.version, migration.name
+        ))
+    })?;
+
+    info!("⬇️ Reverting migration {} ({})", migration.version, migration.name);
+
+    db.query(format!(
+        "BEGIN TRANSACTION;\
+        {down}\
+        DELETE type::thing('migrations', $version);\
+        COMMIT TRANSACTION;"
+    ))
+    .bind(("version", migration.version))
+    .await?
+    .check()?;
+
+    Ok(())
+}
+
+/// Bootstrap the schema and run the migrations
+///
+/// ## Arguments
+///
+/// * `db` - The database connection
+/// * `mode` - What to do with pending or applied migrations, `Verify` never writes
+///
+/// ## Returns
+///
+/// * `Result<(), Error>` - The result of the operation
+///
+/// ## Errors
+///
+/// * `Error::Migration` - A migration is modified, missing, pending in `Verify` mode or irreversible
+/// * `Error` - The error returned by the database
+pub async fn run(db: &DBConnection, mode: MigrationMode) -> Result<(), Error> {
+    if mode != MigrationMode::Verify {
+        info!("🗂️ Bootstrapping schema...");
+        init_tables(db).await?;
+    }
+
+    let applied = applied(db).await?;
+    verify_checksums(&applied)?;
+
+    let is_applied = |m: &Migration| applied.iter().any(|a| a.version == m.version);
+
+    match mode {
+        MigrationMode::Apply => {
+            for migration in MIGRATIONS.iter().filter(|m| !is_applied(m)) {
+                apply(db, migration).await?;
+            }
+            info!("✅ Schema is up to date");
+        }
+        MigrationMode::Verify => {
+            let pending: Vec<String> = MIGRATIONS
+                .iter()
+                .filter(|m| !is_applied(m))
+                .map(|m| m.version.to_string())
+                .collect();
+            if !pending.is_empty() {
+                return Err(Error::Migration(format!(
+                    "pending migrations: {}",
+                    pending.join(", ")
+                )));
+            }
+            info!("✅ Schema is up to date");
+        }
+        MigrationMode::Rollback(target) => {
+            for migration in MIGRATIONS
+                .iter()
+                .rev()
+                .filter(|m| m.version > target && is_applied(m))
+            {
+                revert(db, migration).await?;
+            }
+            warn!("Rolled back to migration {target}");
+        }
+    }
+
+    Ok(())
+}
@@ -1,2 +1,5 @@
 // repository/mod.rs
-pub mod surrealdb_repo;
\ No newline at end of file
+pub mod live;
+pub mod migrations;
+pub mod surrealdb_repo;
+pub mod transaction;
@@ -1,81 +1,143 @@
 use log::info;
 use surrealdb::{
-    engine::remote::ws::Client, engine::remote::ws::Ws, opt::auth::Root, sql::Value, Error, Surreal,
+    engine::any::{self, Any},
+    opt::auth::Root,
+    sql::Value,
+    Error, Surreal,
 };
 
-use crate::utils::env::get_env_or;
+use crate::config::DatabaseConfig;
 
 pub trait Creatable: Into<Value> {}
-pub trait Patchable: Into<Value> {}
+
+/// A model that can be partially updated with `CRUD::update_merge` and `CRUD::update_patch`
+///
+/// ## Constants
+///
+/// * `PROTECTED_FIELDS` are the fields merges and patches may never read or write
+pub trait Patchable {
+    const PROTECTED_FIELDS: &'static [&'static str];
+}
+
+/// A record carrying the version maintained by the CRUD layer, see `model::version_field`
+///
+/// ## Methods
+///
+/// * `version` returns the version of the record, incremented on every write
+pub trait Versioned {
+    fn version(&self) -> u64;
+}
 
 /// A repository for the SurrealDB
-/// 
+///
 /// ## Fields
-/// 
+///
 /// DB is the main database
-/// 
+///
 /// SessionDB is the database for sessions
 #[derive(Clone)]
 pub struct SurrealDBRepo {
-    pub db: Surreal<Client>,
-    pub session_db: Surreal<Client>,
+    pub db: Surreal<Any>,
+    pub session_db: Surreal<Any>,
 }
 
 impl SurrealDBRepo {
-    /// Initializes the SurrealDB connection
-    /// 
+    /// Initializes the SurrealDB connection from the configuration
+    ///
+    /// The location accepts any engine address (`ws://`, `wss://`, `http://`, `mem://`),
+    /// a bare `host:port` is treated as `ws://host:port`
+    ///
+    /// ## Arguments
+    ///
+    /// * `config` - The database configuration
+    ///
     /// ## Returns
     /// Returns a SurrealDBRepo instance
-    /// 
+    ///
     /// ## Errors
-    /// Panics if the database connection fails
-    pub async fn init() -> Result<Self, Error> {
+    /// Returns an error if the database connection fails
+    pub async fn init(config: &DatabaseConfig) -> Result<Self, Error> {
+        Self::connect(
+            &config.location,
+            &config.username,
+            &config.password,
+            &config.namespace,
+            &config.database,
+        )
+        .await
+    }
+
+    /// Initializes an in-process, in-memory SurrealDB
+    ///
+    /// ## Arguments
+    ///
+    /// * `namespace` - The namespace to use
+    /// * `database` - The database to use
+    ///
+    /// ## Returns
+    /// Returns a SurrealDBRepo instance
+    ///
+    /// ## Errors
+    /// Returns an error if the database cannot be started
+    pub async fn in_memory(namespace: &str, database: &str) -> Result<Self, Error> {
+        Self::connect("mem://", "", "", namespace, database).await
+    }
+
+    /// Connects both the main and the session database
+    ///
+    /// ## Arguments
+    ///
+    /// * `location` - The address of the database
+    /// * `username` - The root username, ignored by embedded engines
+    /// * `password` - The root password, ignored by embedded engines
+    /// * `namespace` - The namespace to use
+    /// * `database` - The database to use
+    ///
+    /// ## Returns
+    /// Returns a SurrealDBRepo instance
+    ///
+    /// ## Errors
+    /// Returns an error if the connection, the sign in or the namespace selection fails
+    pub async fn connect(
+        location: &str,
+        username: &str,
+        password: &str,
+        namespace: &str,
+        database: &str,
+    ) -> Result<Self, Error> {
         info!("🦋 Connecting database...");
 
-        let db_location = get_env_or("DB_LOCATION", "127.0.0.1:8000");
+        let location = if location.contains("://") || location == "memory" {
+            location.to_string()
+        } else {
+            format!("ws://{location}")
+        };
+
+        let db = Self::open(&location, username, password, namespace, database).await?;
+        let session_db = Self::open(&location, username, password, namespace, database).await?;
 
-        let db = Surreal::new::<Ws>(db_location.clone())
-            .await
-            .expect("DB to connect");
+        Ok(Self { db, session_db })
+    }
 
-        let db_user = get_env_or("DB_USERNAME", "root");
-        let db_pass = get_env_or("DB_PASSWORD", "root");
+    /// Opens a single connection, signs in and selects the namespace and database
+    async fn open(
+        location: &str,
+        username: &str,
+        password: &str,
+        namespace: &str,
+        database: &str,
+    ) -> Result<Surreal<Any>, Error> {
+        let db = any::connect(location).await?;
 
-        info!("📖 Signing in...");
+        // Embedded engines run without authentication
+        if !(location.starts_with("mem://") || location == "memory") {
+            info!("📖 Signing in...");
 
-        db.signin(Root {
-            username: db_user.as_str(),
-            password: db_pass.as_str(),
-        })
-        .await
-        .expect("DB Credentials to be correct");
-
-        let db_namespace = get_env_or("DB_NAMESPACE", "test");
-        let db_database = get_env_or("DB_DATABASE", "test");
-
-        db.use_ns(db_namespace.clone())
-            .use_db(db_database.clone())
-            .await
-            .expect("using namespace and db to work");
-
-        let session_db = Surreal::new::<Ws>(db_location)
-            .await
-            .expect("DB to connect");
-
-        session_db
-            .signin(Root {
-                username: db_user.as_str(),
-                password: db_pass.as_str(),
-            })
-            .await
-            .expect("DB Credentials to be correct");
-
-        session_db
-            .use_ns(db_namespace)
-            .use_db(db_database)
-            .await
-            .expect("using namespace and db to work");
+            db.signin(Root { username, password }).await?;
+        }
 
-        Ok(Self { db, session_db })
+        db.use_ns(namespace).use_db(database).await?;
+
+        Ok(db)
     }
 }
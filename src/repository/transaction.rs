@@ -0,0 +1,308 @@
+// repository/transaction.rs
+use std::{collections::HashMap, marker::PhantomData};
+
+use serde::{de::DeserializeOwned, Serialize};
+use surrealdb::{
+    sql::{self, Thing, Value},
+    Response,
+};
+
+use super::surrealdb_repo::Patchable;
+use crate::{
+    models::{model::DBConnection, patch::check_merge},
+    prelude::Error,
+};
+
+/// Prefix of the error a statement throws when its record does not exist
+const NOT_FOUND: &str = "tx_not_found:";
+/// Prefix of the error a statement throws when its record is at another version
+const CONFLICT: &str = "tx_conflict:";
+/// Message SurrealDB gives the statements cancelled by another one failing
+const CANCELLED: &str = "not executed due to a failed transaction";
+
+/// Handle to a statement returning a single record
+///
+/// ## Generic Types
+///
+/// * `D` - The data type of the record
+pub struct Record<D> {
+    index: usize,
+    data: PhantomData<fn() -> D>,
+}
+
+/// Handle to a raw statement returning rows
+///
+/// ## Generic Types
+///
+/// * `T` - The type of a row
+pub struct Rows<T> {
+    index: usize,
+    data: PhantomData<fn() -> T>,
+}
+
+/// Builder of a transaction running several CRUD-style statements atomically
+///
+/// Every method queues a statement and returns a typed handle to read its result from the
+/// `Committed` transaction. If any statement fails, none of them is applied.
+///
+/// ## Example
+///
+/// ```ignore
+/// let mut tx = Transaction::new();
+/// let user = tx.create::<User>(User::TABLE, user_data)?;
+/// let settings = tx.create::<Settings>(Settings::TABLE, settings_data)?;
+/// let mut committed = tx.commit(&db).await?;
+/// let user = committed.record(user)?;
+/// ```
+#[derive(Default)]
+pub struct Transaction {
+    statements: Vec<String>,
+    bindings: HashMap<String, Value>,
+}
+
+/// The results of a committed transaction
+pub struct Committed {
+    response: Response,
+}
+
+impl Transaction {
+    /// Create an empty transaction
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bind a value under a name unique to the transaction
+    fn bind_value(&mut self, name: &str, value: impl Serialize) -> Result<String, Error> {
+        let name = format!("tx_{name}_{}", self.statements.len());
+        let value = sql::to_value(value)
+            .map_err(|e| Error::Validation(format!("cannot bind {name}, {e}")))?;
+        self.bindings.insert(name.clone(), value);
+
+        Ok(format!("${name}"))
+    }
+
+    /// Queue a statement and return its index
+    fn push(&mut self, statement: String) -> usize {
+        self.statements.push(statement);
+        self.statements.len() - 1
+    }
+
+    /// Queue a statement failing the transaction unless the record exists
+    /// and, when given, is at the expected version
+    fn push_guarded(
+        &mut self,
+        target: &str,
+        version: Option<u64>,
+        statement: String,
+    ) -> Result<usize, Error> {
+        let version = self.bind_value("version", version)?;
+
+        Ok(self.push(format!(
+            "IF {target}.id = NONE {{ THROW '{NOT_FOUND}' + <string> {target} }} \
+            ELSE IF {version} != NONE AND {target}.version != {version} {{ \
+                THROW '{CONFLICT}' + <string> {target} \
+            }} ELSE {{ {statement} }}"
+        )))
+    }
+
+    /// Queue the creation of a record with a random id
+    ///
+    /// ## Arguments
+    ///
+    /// * `tb` - The table name
+    /// * `data` - The content of the record
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<Record<D>, Error>` - The handle to the created record
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Validation` - The data cannot be serialized
+    pub fn create<D>(&mut self, tb: &str, data: impl Serialize) -> Result<Record<D>, Error> {
+        let tb = self.bind_value("table", tb)?;
+        let data = self.bind_value("data", data)?;
+        let index = self.push(format!("CREATE type::table({tb}) CONTENT {data}"));
+
+        Ok(Record::new(index))
+    }
+
+    /// Queue a merge into an existing record
+    ///
+    /// ## Arguments
+    ///
+    /// * `id` - The id of the record
+    /// * `data` - The fields to change, must not write a protected field
+    /// * `version` - The version the record was read at, `None` to update unconditionally
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<Record<D>, Error>` - The handle to the updated record
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Validation` - The data cannot be serialized or writes a protected field
+    pub fn update_merge<D: Patchable>(
+        &mut self,
+        id: &Thing,
+        data: impl Serialize,
+        version: Option<u64>,
+    ) -> Result<Record<D>, Error> {
+        let data = serde_json::to_value(data)
+            .map_err(|e| Error::Validation(format!("merge is not valid JSON, {e}")))?;
+        check_merge(&data, D::PROTECTED_FIELDS)?;
+
+        let id = self.bind_value("id", id)?;
+        let data = self.bind_value("data", data)?;
+        let index =
+            self.push_guarded(&id, version, format!("UPDATE {id} MERGE {data} RETURN AFTER"))?;
+
+        Ok(Record::new(index))
+    }
+
+    /// Queue a raw SurrealQL statement
+    ///
+    /// ## Arguments
+    ///
+    /// * `statement` - A single statement, using parameters bound with `bind`
+    ///
+    /// ## Returns
+    ///
+    /// * `Rows<T>` - The handle to the rows returned by the statement
+    pub fn query<T>(&mut self, statement: impl Into<String>) -> Rows<T> {
+        let index = self.push(statement.into());
+
+        Rows {
+            index,
+            data: PhantomData,
+        }
+    }
+
+    /// Bind a parameter used by raw statements
+    ///
+    /// Names starting with `tx_` are reserved for the builder
+    ///
+    /// ## Arguments
+    ///
+    /// * `name` - The name of the parameter, without `$`
+    /// * `value` - The value of the parameter
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Validation` - The name is reserved or the value cannot be serialized
+    pub fn bind(&mut self, name: &str, value: impl Serialize) -> Result<&mut Self, Error> {
+        if name.starts_with("tx_") {
+            return Err(Error::Validation(format!("parameter name `{name}` is reserved")));
+        }
+        let value = sql::to_value(value)
+            .map_err(|e| Error::Validation(format!("cannot bind {name}, {e}")))?;
+        self.bindings.insert(name.to_string(), value);
+
+        Ok(self)
+    }
+
+    /// Run every queued statement in a single transaction
+    ///
+    /// ## Arguments
+    ///
+    /// * `db` - The database connection
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<Committed, Error>` - The results of the statements
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::NotFound` - A record to update or delete does not exist
+    /// * `Error::Conflict` - A record is at another version, or a unique index is violated
+    /// * `Error::Transaction` - Another statement failed, nothing was applied
+    pub async fn commit(self, db: &DBConnection) -> Result<Committed, Error> {
+        if self.statements.is_empty() {
+            return Err(Error::Transaction("transaction has no statement".to_string()));
+        }
+
+        let sql = format!(
+            "BEGIN TRANSACTION;{};COMMIT TRANSACTION;",
+            self.statements.join(";")
+        );
+        let mut response = db.query(sql).bind(self.bindings).await?;
+
+        let mut errors: Vec<(usize, surrealdb::Error)> =
+            response.take_errors().into_iter().collect();
+        errors.sort_by_key(|(index, _)| *index);
+
+        // Every statement reports the failure, only one of them caused it
+        let cause = errors
+            .iter()
+            .position(|(_, e)| !e.to_string().contains(CANCELLED))
+            .map(|position| errors.swap_remove(position))
+            .or_else(|| errors.into_iter().next());
+
+        match cause {
+            Some((index, e)) => Err(statement_error(index, e)),
+            None => Ok(Committed { response }),
+        }
+    }
+}
+
+/// Map the error of the statement that failed a transaction to the crate error
+fn statement_error(index: usize, e: surrealdb::Error) -> Error {
+    let message = e.to_string();
+    let thrown = |prefix: &str| {
+        message
+            .split_once(prefix)
+            .map(|(_, id)| id.trim_end_matches(['\'', '"']).to_string())
+    };
+
+    if let Some(id) = thrown(NOT_FOUND) {
+        Error::NotFound(id)
+    } else if let Some(id) = thrown(CONFLICT) {
+        Error::Conflict(format!("{id} was modified concurrently"))
+    } else if message.contains("already contains") {
+        Error::Surreal(e).map_index_conflict()
+    } else {
+        Error::Transaction(format!("statement {index} failed, {message}"))
+    }
+}
+
+impl<D> Record<D> {
+    fn new(index: usize) -> Self {
+        Self {
+            index,
+            data: PhantomData,
+        }
+    }
+}
+
+impl Committed {
+    /// Take the record returned by a statement
+    ///
+    /// ## Arguments
+    ///
+    /// * `record` - The handle returned when the statement was queued
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::NoRecord` - The statement returned no record
+    /// * `Error` - The record cannot be deserialized
+    pub fn record<D: DeserializeOwned>(&mut self, record: Record<D>) -> Result<D, Error> {
+        let res: Option<D> = self.response.take(record.index)?;
+
+        res.ok_or(Error::NoRecord)
+    }
+
+    /// Take the rows returned by a raw statement
+    ///
+    /// ## Arguments
+    ///
+    /// * `rows` - The handle returned when the statement was queued
+    ///
+    /// ## Errors
+    ///
+    /// * `Error` - The rows cannot be deserialized
+    pub fn rows<T: DeserializeOwned>(&mut self, rows: Rows<T>) -> Result<Vec<T>, Error> {
+        let res: Vec<T> = self.response.take(rows.index)?;
+
+        Ok(res)
+    }
+}
@@ -0,0 +1,121 @@
+// signaling/hub.rs
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use actix_ws::{CloseCode, CloseReason, Session};
+use log::info;
+use uuid::Uuid;
+
+use super::message::ServerMessage;
+use crate::prelude::Error;
+
+/// A connected peer
+///
+/// ## Fields
+///
+/// * `connection` identifies the connection, a peer reconnecting replaces it
+/// * `session` is the WebSocket session of the connection
+struct Peer {
+    connection: Uuid,
+    session: Session,
+}
+
+/// Registry of the connected peers, relaying signaling messages between them
+///
+/// A peer id has at most one connection, the newest one wins
+#[derive(Clone, Default)]
+pub struct SignalingHub {
+    peers: Arc<Mutex<HashMap<String, Peer>>>,
+}
+
+/// Serialize a server message to the text of a WebSocket frame
+pub fn to_text(message: &ServerMessage) -> String {
+    serde_json::to_string(message).expect("server messages to serialize")
+}
+
+impl SignalingHub {
+    /// Create a hub without any connected peer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the connection of a peer, closing its previous connection
+    ///
+    /// ## Arguments
+    ///
+    /// * `peer_id` - The peer id of the connected user
+    /// * `session` - The WebSocket session of the connection
+    ///
+    /// ## Returns
+    ///
+    /// * `Uuid` - The id of the connection, to pass to `disconnect`
+    pub async fn connect(&self, peer_id: &str, session: Session) -> Uuid {
+        let connection = Uuid::new_v4();
+        let previous = self
+            .peers
+            .lock()
+            .unwrap()
+            .insert(peer_id.to_string(), Peer { connection, session });
+
+        if let Some(previous) = previous {
+            info!("🔌 Peer {peer_id} reconnected, closing its previous connection");
+            let reason = CloseReason {
+                code: CloseCode::Policy,
+                description: Some("replaced by a new connection".to_string()),
+            };
+            let _ = previous.session.close(Some(reason)).await;
+        }
+
+        connection
+    }
+
+    /// Unregister the connection of a peer, unless it was already replaced
+    ///
+    /// ## Arguments
+    ///
+    /// * `peer_id` - The peer id of the connected user
+    /// * `connection` - The id returned by `connect`
+    ///
+    /// ## Returns
+    ///
+    /// * `bool` - Whether the peer was unregistered, i.e. has no connection left
+    pub fn disconnect(&self, peer_id: &str, connection: Uuid) -> bool {
+        let mut peers = self.peers.lock().unwrap();
+        let current = peers
+            .get(peer_id)
+            .is_some_and(|peer| peer.connection == connection);
+        if current {
+            peers.remove(peer_id);
+        }
+
+        current
+    }
+
+    /// Send a message to a connected peer
+    ///
+    /// ## Arguments
+    ///
+    /// * `to` - The peer id of the recipient
+    /// * `message` - The message
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::NotFound` - The recipient is not connected
+    pub async fn relay(&self, to: &str, message: &ServerMessage) -> Result<(), Error> {
+        let not_connected = || Error::NotFound(format!("peer {to} is not connected"));
+        let session = self
+            .peers
+            .lock()
+            .unwrap()
+            .get(to)
+            .map(|peer| peer.session.clone());
+        let mut session = session.ok_or_else(not_connected)?;
+
+        session
+            .text(to_text(message))
+            .await
+            .map_err(|_| not_connected())
+    }
+}
@@ -0,0 +1,137 @@
+// signaling/message.rs
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::Error;
+
+/// An ICE candidate, as produced by `RTCIceCandidate.toJSON()`
+///
+/// ## Fields
+///
+/// * `candidate` is the candidate line, empty for the end of candidates
+/// * `sdp_mid` is the media stream identification tag
+/// * `sdp_m_line_index` is the index of the media description
+/// * `username_fragment` is the ICE username fragment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IceCandidate {
+    pub candidate: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sdp_mid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sdp_m_line_index: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username_fragment: Option<String>,
+}
+
+/// A message sent by a client, addressed to another peer
+///
+/// ## Variants
+///
+/// * `Offer` carries the SDP offer of the sender
+/// * `Answer` carries the SDP answer of the sender
+/// * `IceCandidate` carries an ICE candidate of the sender
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Offer { to: String, sdp: String },
+    Answer { to: String, sdp: String },
+    IceCandidate { to: String, candidate: IceCandidate },
+}
+
+/// A message sent by the server
+///
+/// ## Variants
+///
+/// * `Welcome` is sent once connected, with the peer id the client is reachable at
+/// * `Offer`, `Answer` and `IceCandidate` are relayed from the peer `from`
+/// * `Error` reports a message that was not relayed, with the code of the crate `Error`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Welcome { peer_id: String },
+    Offer { from: String, sdp: String },
+    Answer { from: String, sdp: String },
+    IceCandidate { from: String, candidate: IceCandidate },
+    Error { code: String, message: String },
+}
+
+impl ClientMessage {
+    /// Check the message can be relayed
+    ///
+    /// ## Arguments
+    ///
+    /// * `sender` - The peer id of the sender
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Validation` - The recipient or the payload is invalid
+    pub fn validate(&self, sender: &str) -> Result<(), Error> {
+        let to = match self {
+            ClientMessage::Offer { to, .. }
+            | ClientMessage::Answer { to, .. }
+            | ClientMessage::IceCandidate { to, .. } => to,
+        };
+        if to.trim().is_empty() {
+            return Err(Error::Validation("`to` must not be empty".to_string()));
+        }
+        if to == sender {
+            return Err(Error::Validation("cannot signal yourself".to_string()));
+        }
+
+        match self {
+            ClientMessage::Offer { sdp, .. } | ClientMessage::Answer { sdp, .. } => {
+                // Every session description starts with its protocol version
+                if !sdp.starts_with("v=0") {
+                    return Err(Error::Validation(
+                        "`sdp` is not a session description".to_string(),
+                    ));
+                }
+            }
+            ClientMessage::IceCandidate { candidate, .. } => {
+                let is_end = candidate.candidate.is_empty();
+                if !is_end && !candidate.candidate.starts_with("candidate:") {
+                    return Err(Error::Validation(
+                        "`candidate` is not an ICE candidate".to_string(),
+                    ));
+                }
+                if !is_end && candidate.sdp_mid.is_none() && candidate.sdp_m_line_index.is_none() {
+                    return Err(Error::Validation(
+                        "`sdpMid` or `sdpMLineIndex` is required".to_string(),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Turn the message into the one relayed to its recipient
+    ///
+    /// ## Arguments
+    ///
+    /// * `sender` - The peer id of the sender
+    ///
+    /// ## Returns
+    ///
+    /// * `(String, ServerMessage)` - The peer id of the recipient and the relayed message
+    pub fn relay_from(self, sender: &str) -> (String, ServerMessage) {
+        let from = sender.to_string();
+
+        match self {
+            ClientMessage::Offer { to, sdp } => (to, ServerMessage::Offer { from, sdp }),
+            ClientMessage::Answer { to, sdp } => (to, ServerMessage::Answer { from, sdp }),
+            ClientMessage::IceCandidate { to, candidate } => {
+                (to, ServerMessage::IceCandidate { from, candidate })
+            }
+        }
+    }
+}
+
+impl From<&Error> for ServerMessage {
+    fn from(e: &Error) -> Self {
+        ServerMessage::Error {
+            code: e.code().to_string(),
+            message: e.to_string(),
+        }
+    }
+}
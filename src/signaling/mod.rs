@@ -0,0 +1,5 @@
+// signaling/mod.rs
+pub mod hub;
+pub mod message;
+pub mod presence;
+pub mod rate_limit;
@@ -0,0 +1,122 @@
+// signaling/presence.rs
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::broadcast;
+
+use crate::models::presence_model::PresenceStatus;
+
+/// Number of presence changes buffered for slow subscribers
+const CAPACITY: usize = 256;
+
+/// Tracker of the users who are online, i.e. have an open signaling connection
+///
+/// Changes are broadcast as seen by others: an invisible user going online or offline is
+/// not a change, while a user of changing visibility goes online or offline.
+///
+/// ## Fields
+///
+/// * `peers` are the visibilities of the online users, by peer id
+/// * `sender` broadcasts the presence changes
+#[derive(Clone)]
+pub struct PresenceTracker {
+    peers: Arc<Mutex<HashMap<String, bool>>>,
+    sender: broadcast::Sender<PresenceStatus>,
+}
+
+impl Default for PresenceTracker {
+    fn default() -> Self {
+        Self {
+            peers: Arc::default(),
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl PresenceTracker {
+    /// Create a tracker without any online user
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update the state of a peer, broadcasting the change if others see one
+    ///
+    /// A state is the visibility of the user if they are online, `None` if they are offline
+    ///
+    /// ## Arguments
+    ///
+    /// * `peer_id` - The peer id of the user
+    /// * `update` - Returns the new state from the current one
+    /// * `last_seen` - The last time the user was seen, sent if they appear offline
+    fn apply(
+        &self,
+        peer_id: &str,
+        update: impl FnOnce(Option<bool>) -> Option<bool>,
+        last_seen: Option<String>,
+    ) {
+        let mut peers = self.peers.lock().unwrap();
+        let current = peers.get(peer_id).copied();
+        let state = update(current);
+        let before = current == Some(true);
+        match state {
+            Some(visible) => peers.insert(peer_id.to_string(), visible),
+            None => peers.remove(peer_id),
+        };
+
+        let online = state == Some(true);
+        if before != online {
+            // Nobody listening is fine
+            let _ = self.sender.send(PresenceStatus {
+                peer_id: peer_id.to_string(),
+                online,
+                last_seen: if online { None } else { last_seen },
+            });
+        }
+    }
+
+    /// Mark a user online
+    ///
+    /// ## Arguments
+    ///
+    /// * `peer_id` - The peer id of the user
+    /// * `visible` - Whether the user is visible
+    pub fn connect(&self, peer_id: &str, visible: bool) {
+        self.apply(peer_id, |_| Some(visible), None);
+    }
+
+    /// Mark a user offline
+    ///
+    /// ## Arguments
+    ///
+    /// * `peer_id` - The peer id of the user
+    /// * `last_seen` - The date the user disconnected
+    pub fn disconnect(&self, peer_id: &str, last_seen: String) {
+        self.apply(peer_id, |_| None, Some(last_seen));
+    }
+
+    /// Update the visibility of a user, if they are online
+    ///
+    /// ## Arguments
+    ///
+    /// * `peer_id` - The peer id of the user
+    /// * `visible` - Whether the user is visible
+    pub fn set_visible(&self, peer_id: &str, visible: bool) {
+        self.apply(peer_id, |state| state.map(|_| visible), None);
+    }
+
+    /// Whether a user is online, visible or not
+    ///
+    /// ## Arguments
+    ///
+    /// * `peer_id` - The peer id of the user
+    pub fn is_online(&self, peer_id: &str) -> bool {
+        self.peers.lock().unwrap().contains_key(peer_id)
+    }
+
+    /// Subscribe to the presence changes of every user
+    pub fn subscribe(&self) -> broadcast::Receiver<PresenceStatus> {
+        self.sender.subscribe()
+    }
+}
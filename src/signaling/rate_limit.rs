@@ -0,0 +1,54 @@
+// signaling/rate_limit.rs
+use std::time::Instant;
+
+/// Token bucket limiting the messages of a single connection
+///
+/// ## Fields
+///
+/// * `capacity` is the number of messages allowed in a burst
+/// * `per_second` is the number of messages replenished every second
+/// * `tokens` is the number of messages currently allowed
+/// * `updated` is when `tokens` was last replenished
+#[derive(Debug)]
+pub struct RateLimiter {
+    capacity: f64,
+    per_second: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// Create a full bucket
+    ///
+    /// ## Arguments
+    ///
+    /// * `per_second` - The number of messages replenished every second
+    /// * `burst_size` - The number of messages allowed in a burst
+    pub fn new(per_second: u32, burst_size: u32) -> Self {
+        Self {
+            capacity: burst_size.into(),
+            per_second: per_second.into(),
+            tokens: burst_size.into(),
+            updated: Instant::now(),
+        }
+    }
+
+    /// Take a token if one is available
+    ///
+    /// ## Returns
+    ///
+    /// * `bool` - Whether the message is allowed
+    pub fn check(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.capacity);
+        self.updated = now;
+
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+
+        true
+    }
+}
@@ -0,0 +1,93 @@
+// tests/auth.rs
+use actix_web::{http::StatusCode, test};
+use argon2::PasswordHash;
+use serde_json::json;
+
+use super::{login_as, register, request, TestContext, PASSWORD};
+use crate::{
+    api::response::Response,
+    models::user_model::{User, UserProfile, DUMMY_PASSWORD_HASH},
+};
+
+#[actix_web::test]
+async fn register_returns_profile_without_password() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+
+    let res = register(&app, "Alice@Example.com", PASSWORD).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let body: serde_json::Value = test::read_body_json(res).await;
+    assert_eq!(body["code"], 201);
+    assert_eq!(body["data"]["email"], "alice@example.com");
+    assert!(body["data"].get("password_hash").is_none());
+}
+
+#[actix_web::test]
+async fn register_rejects_weak_password() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+
+    let res = register(&app, "alice@example.com", "password").await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn register_duplicate_email_conflicts() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+
+    register(&app, "alice@example.com", PASSWORD).await;
+    let res = register(&app, "alice@example.com", PASSWORD).await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+}
+
+#[actix_web::test]
+async fn login_rejects_wrong_password() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+
+    register(&app, "alice@example.com", PASSWORD).await;
+    let req = request()
+        .method(actix_web::http::Method::POST)
+        .uri("/auth/login")
+        .set_json(json!({ "email": "alice@example.com", "password": "Wr0ngPassword" }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn session_lasts_until_logout() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+    let cookie = login_as(&app, "alice@example.com").await;
+
+    let req = request().uri("/users/me").cookie(cookie.clone()).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Response<UserProfile> = test::read_body_json(res).await;
+    assert_eq!(body.data.unwrap().email, "alice@example.com");
+
+    let req = request()
+        .method(actix_web::http::Method::POST)
+        .uri("/auth/logout")
+        .cookie(cookie.clone())
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let req = request().uri("/users/me").cookie(cookie).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn dummy_password_hash_has_the_default_parameters() {
+    // `$argon2id$v=19$m=...,t=...,p=...`, without the salt and the hash
+    let parameters = |hash: &str| hash.rsplitn(3, '$').nth(2).unwrap().to_string();
+    let hash = User::hash_password(PASSWORD).unwrap();
+
+    assert_eq!(parameters(DUMMY_PASSWORD_HASH), parameters(&hash));
+    assert!(PasswordHash::new(DUMMY_PASSWORD_HASH).is_ok());
+}
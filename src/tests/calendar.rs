@@ -0,0 +1,203 @@
+// tests/calendar.rs
+use actix_web::{
+    http::{header, Method, StatusCode},
+    test,
+    web::Data,
+};
+use serde_json::json;
+
+use super::{
+    login_as, request,
+    untis::{mock, PASSWORD, SCHOOL, USER},
+    TestContext,
+};
+use crate::{
+    api::{response::Response, timetable::CalendarFeed},
+    jobs::timetable::sync,
+    models::{
+        calendar_model::CalendarToken, model::CRUD, untis_model::UntisAccount, user_model::User,
+    },
+    utils::ics::{calendar, escape, fold, Event},
+};
+
+/// The values of a property of the events of a calendar, e.g. their `UID`s
+fn values<'a>(calendar: &'a str, property: &str) -> Vec<&'a str> {
+    let prefix = format!("{property}:");
+    calendar
+        .lines()
+        .filter_map(|line| line.strip_prefix(&prefix))
+        .collect()
+}
+
+#[test]
+fn calendars_are_escaped_and_folded() {
+    assert_eq!(escape("Room 1, 2; A\\B\nC"), "Room 1\\, 2\\; A\\\\B\\nC");
+
+    let line = format!("DESCRIPTION:{}", "é".repeat(80));
+    let folded = fold(&line);
+    assert!(folded.ends_with("\r\n"));
+    let parts: Vec<&str> = folded.trim_end_matches("\r\n").split("\r\n").collect();
+    assert_eq!(parts.len(), 3);
+    assert!(parts.iter().all(|part| part.len() <= 75));
+    assert!(parts[1..].iter().all(|part| part.starts_with(' ')));
+    let unfolded: String = parts.iter().map(|part| part.trim_start_matches(' ')).collect();
+    assert_eq!(unfolded, line);
+
+    let event = Event {
+        uid: "alice-1@example.com".to_string(),
+        stamp: "20240301T120000Z".to_string(),
+        sequence: 3,
+        start: "20240304T070000Z".to_string(),
+        end: "20240304T074500Z".to_string(),
+        summary: "Mathematics".to_string(),
+        location: None,
+        description: None,
+        cancelled: true,
+    };
+    let document = calendar("Timetable", "Europe/Berlin", &[event]);
+    assert!(document.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+    assert!(document.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+    assert!(document.contains("\r\nX-WR-TIMEZONE:Europe/Berlin\r\n"));
+    assert!(document.contains("\r\nSEQUENCE:3\r\n"));
+    assert!(document.contains("\r\nDTSTART:20240304T070000Z\r\nDTEND:20240304T074500Z\r\n"));
+    assert!(document.contains("\r\nSTATUS:CANCELLED\r\n"));
+    assert!(!document.contains("LOCATION"));
+    // Times are UTC, so no time zone needs to be defined
+    assert!(!document.contains("TZID"));
+}
+
+#[actix_web::test]
+async fn timetables_are_exported_as_calendars() {
+    let srv = mock();
+    // Nine hours ahead of UTC all year
+    let ctx =
+        TestContext::configured(|config| config.untis.timezone = "Asia/Tokyo".to_string()).await;
+    let db = &ctx.state.surreal.db;
+    let config = &ctx.state.config.untis;
+    let app = ctx.init().await;
+    let cookie = login_as(&app, "alice@example.com").await;
+
+    // A feed needs a linked account
+    let req = request()
+        .method(Method::POST)
+        .uri("/timetable/calendar")
+        .cookie(cookie.clone())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+    let req = request()
+        .method(Method::PUT)
+        .uri("/untis/account")
+        .cookie(cookie.clone())
+        .set_json(json!({
+            "server": format!("http://{}", srv.addr()),
+            "school": SCHOOL,
+            "username": USER,
+            "password": PASSWORD,
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = request()
+        .method(Method::POST)
+        .uri("/timetable/refresh")
+        .cookie(cookie.clone())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = request()
+        .method(Method::POST)
+        .uri("/timetable/calendar")
+        .cookie(cookie.clone())
+        .to_request();
+    let res: Response<CalendarFeed> = test::call_and_read_body_json(&app, req).await;
+    let feed = res.data.unwrap();
+    assert_eq!(feed.path, format!("/timetable.ics?token={}", feed.token));
+
+    // The feed needs no session
+    let req = request().uri(&feed.path).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let content_type = res.headers().get(header::CONTENT_TYPE).unwrap();
+    assert_eq!(content_type, "text/calendar; charset=utf-8");
+    let body = test::read_body(res).await;
+    let first = String::from_utf8(body.to_vec()).unwrap();
+    assert_eq!(first.matches("BEGIN:VEVENT").count(), 3);
+    assert_eq!(first.matches("STATUS:CANCELLED").count(), 1);
+    assert!(first.contains("\r\nSUMMARY:Mathematics\r\n"));
+    assert!(first.contains("\r\nLOCATION:102\r\n"));
+    // The first lesson starts at 08:00 at school
+    let starts = values(&first, "DTSTART");
+    assert_eq!(starts.len(), 3);
+    assert!(starts.iter().all(|start| start.ends_with('Z')));
+    assert!(starts.iter().any(|start| start.ends_with("T230000Z")));
+
+    // Another synchronisation keeps the UIDs
+    let id = User::get_from_email(Data::new(db.clone()), "alice@example.com")
+        .await
+        .unwrap()
+        .unwrap()
+        .id;
+    let account = UntisAccount::of(db, &id).await.unwrap().unwrap();
+    let cipher = config.cipher().unwrap();
+    sync(db, config, &cipher, &ctx.state.syncs, &account).await.unwrap();
+    let req = request().uri(&feed.path).to_request();
+    let body = test::call_and_read_body(&app, req).await;
+    let second = String::from_utf8(body.to_vec()).unwrap();
+    assert_eq!(values(&second, "UID"), values(&first, "UID"));
+    let prefix = format!("{}-", id.id.to_raw());
+    assert!(values(&first, "UID").iter().all(|uid| uid.starts_with(&prefix)));
+    // Unchanged lessons keep their sequence
+    assert_eq!(values(&second, "SEQUENCE"), values(&first, "SEQUENCE"));
+    assert_eq!(values(&first, "SEQUENCE").len(), 3);
+
+    // A new feed revokes the previous one
+    let req = request()
+        .method(Method::POST)
+        .uri("/timetable/calendar")
+        .cookie(cookie.clone())
+        .to_request();
+    let res: Response<CalendarFeed> = test::call_and_read_body_json(&app, req).await;
+    let rotated = res.data.unwrap();
+    let req = request().uri(&feed.path).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    let req = request().uri(&rotated.path).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = request()
+        .method(Method::DELETE)
+        .uri("/timetable/calendar")
+        .cookie(cookie.clone())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = request().uri(&rotated.path).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    let req = request()
+        .method(Method::DELETE)
+        .uri("/timetable/calendar")
+        .cookie(cookie)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn feeds_of_deleted_users_are_not_found() {
+    let ctx = TestContext::new().await;
+    let db = &ctx.state.surreal.db;
+    let app = ctx.init().await;
+    login_as(&app, "alice@example.com").await;
+    let id = User::get_from_email(Data::new(db.clone()), "alice@example.com")
+        .await
+        .unwrap()
+        .unwrap()
+        .id;
+    let token = CalendarToken::rotate(db, &id).await.unwrap();
+    let path = format!("/timetable.ics?token={token}");
+
+    let req = request().uri(&path).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    // Soft-deleted, the token is kept for a restore but no longer works
+    User::delete(Data::new(db.clone()), id).await.unwrap();
+    let req = request().uri(&path).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+}
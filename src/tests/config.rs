@@ -0,0 +1,67 @@
+// tests/config.rs
+use std::collections::HashMap;
+
+use crate::config::AppConfig;
+
+#[test]
+fn toml_file_overrides_defaults() {
+    let config: AppConfig = toml::from_str(
+        r#"
+        port = 9000
+
+        [database]
+        location = "mem://"
+
+        [cors]
+        allowed_origins = ["https://app.example.com"]
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.port, 9000);
+    assert_eq!(config.database.location, "mem://");
+    assert_eq!(config.database.namespace, "test");
+    assert_eq!(config.cors.allowed_origins, vec!["https://app.example.com"]);
+}
+
+#[test]
+fn every_invalid_value_is_reported() {
+    let mut config = AppConfig::default();
+    config.tls.enabled = false;
+    config.proxy.reverse_proxy = Some([127, 0, 0, 1].into());
+    assert!(config.errors().is_empty());
+
+    config.session.cookie_key = Some("too short".to_string());
+    config.rate_limit.burst_size = 0;
+    config.untis.key = Some("dG9vIHNob3J0".to_string());
+    config.untis.sync_days_before = u32::MAX;
+    config.untis.sync_days_ahead = 365;
+    config.untis.allowed_hosts = vec!["https://nessa.webuntis.com".to_string()];
+    config.untis.timezone = "Europe/Atlantis".to_string();
+    let message = config.errors().join("; ");
+
+    assert!(message.contains("cookie key"));
+    assert!(message.contains("burst size"));
+    assert!(message.contains("Untis encryption key"));
+    assert!(message.contains("Untis sync window"));
+    assert!(message.contains("Untis allowed host `https://nessa.webuntis.com`"));
+    assert!(message.contains("Untis time zone `Europe/Atlantis`"));
+}
+
+#[test]
+fn every_invalid_variable_is_reported() {
+    let vars: HashMap<String, String> = [
+        ("PORT", "eighty"),
+        ("RATE_LIMIT_BURST_SIZE", "-1"),
+        ("TLS_ENABLED", "false"),
+        ("UNTIS_SYNC_DAYS_AHEAD", "365"),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_string(), value.to_string()))
+    .collect();
+    let message = AppConfig::from_vars(&vars).unwrap_err().to_string();
+
+    assert!(message.contains("PORT has an invalid value `eighty`"));
+    assert!(message.contains("RATE_LIMIT_BURST_SIZE has an invalid value `-1`"));
+    assert!(message.contains("Untis sync window"));
+}
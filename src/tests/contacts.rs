@@ -0,0 +1,199 @@
+// tests/contacts.rs
+use actix_http::Request;
+use actix_web::{
+    body::MessageBody,
+    cookie::Cookie,
+    dev::{Service, ServiceResponse},
+    http::{Method, StatusCode},
+    test,
+};
+use serde_json::{json, Value};
+
+use super::{login_as, request, TestContext};
+use crate::{
+    api::response::Response,
+    models::{
+        contact_model::{ContactEntry, ContactStatus},
+        user_model::{PublicProfile, UserProfile},
+    },
+};
+
+/// Get the profile of the logged in user
+async fn me<S, B>(app: &S, cookie: &Cookie<'static>) -> UserProfile
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let req = request()
+        .uri("/users/me")
+        .cookie(cookie.clone())
+        .to_request();
+    let res: Response<UserProfile> = test::call_and_read_body_json(app, req).await;
+
+    res.data.unwrap()
+}
+
+/// Send a request as the logged in user and return its status
+async fn send<S, B>(
+    app: &S,
+    cookie: &Cookie<'static>,
+    method: Method,
+    uri: &str,
+    body: Option<Value>,
+) -> StatusCode
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let req = request().method(method).uri(uri).cookie(cookie.clone());
+    let req = match body {
+        Some(body) => req.set_json(body),
+        None => req,
+    };
+
+    test::call_service(app, req.to_request()).await.status()
+}
+
+/// List the contacts of the logged in user
+async fn list<S, B>(app: &S, cookie: &Cookie<'static>, kind: &str) -> Vec<ContactEntry>
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let req = request()
+        .uri(&format!("/contacts?kind={kind}"))
+        .cookie(cookie.clone())
+        .to_request();
+    let res: Response<Vec<ContactEntry>> = test::call_and_read_body_json(app, req).await;
+
+    res.data.unwrap()
+}
+
+#[actix_web::test]
+async fn accepted_requests_become_contacts() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+    let alice = login_as(&app, "alice@example.com").await;
+    let bob = login_as(&app, "bob@example.com").await;
+    let alice_id = me(&app, &alice).await.peer_id;
+    let bob_id = me(&app, &bob).await.peer_id;
+
+    let req = request()
+        .method(Method::POST)
+        .uri("/contacts/requests")
+        .cookie(alice.clone())
+        .set_json(json!({ "peer_id": bob_id }))
+        .to_request();
+    let res: Response<ContactEntry> = test::call_and_read_body_json(&app, req).await;
+    let entry = res.data.unwrap();
+    assert_eq!(entry.user.peer_id, bob_id);
+    assert_eq!(entry.status, ContactStatus::Pending);
+
+    let body = json!({ "peer_id": bob_id });
+    let status = send(&app, &alice, Method::POST, "/contacts/requests", Some(body)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let incoming = list(&app, &bob, "incoming").await;
+    assert_eq!(incoming.len(), 1);
+    assert_eq!(incoming[0].user.peer_id, alice_id);
+    assert_eq!(list(&app, &alice, "outgoing").await.len(), 1);
+    assert!(list(&app, &alice, "accepted").await.is_empty());
+
+    let uri = format!("/contacts/requests/{alice_id}/accept");
+    assert_eq!(send(&app, &bob, Method::POST, &uri, None).await, StatusCode::OK);
+
+    let contacts = list(&app, &alice, "accepted").await;
+    assert_eq!(contacts.len(), 1);
+    assert_eq!(contacts[0].user.peer_id, bob_id);
+    assert_eq!(contacts[0].status, ContactStatus::Accepted);
+    assert_eq!(list(&app, &bob, "accepted").await[0].user.peer_id, alice_id);
+    assert!(list(&app, &bob, "incoming").await.is_empty());
+
+    // Either user can remove the contact
+    let uri = format!("/contacts/{alice_id}");
+    assert_eq!(send(&app, &bob, Method::DELETE, &uri, None).await, StatusCode::OK);
+    assert!(list(&app, &alice, "accepted").await.is_empty());
+    assert_eq!(
+        send(&app, &bob, Method::DELETE, &uri, None).await,
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[actix_web::test]
+async fn requests_can_be_declined_and_cancelled() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+    let alice = login_as(&app, "alice@example.com").await;
+    let bob = login_as(&app, "bob@example.com").await;
+    let alice_id = me(&app, &alice).await.peer_id;
+    let bob_id = me(&app, &bob).await.peer_id;
+    let body = json!({ "peer_id": bob_id });
+
+    let status = send(&app, &alice, Method::POST, "/contacts/requests", Some(body.clone())).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let uri = format!("/contacts/requests/{alice_id}/decline");
+    assert_eq!(send(&app, &bob, Method::POST, &uri, None).await, StatusCode::OK);
+    assert!(list(&app, &alice, "outgoing").await.is_empty());
+    assert_eq!(
+        send(&app, &bob, Method::POST, &uri, None).await,
+        StatusCode::NOT_FOUND
+    );
+
+    let status = send(&app, &alice, Method::POST, "/contacts/requests", Some(body)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let uri = format!("/contacts/requests/{bob_id}");
+    assert_eq!(send(&app, &alice, Method::DELETE, &uri, None).await, StatusCode::OK);
+    assert!(list(&app, &bob, "incoming").await.is_empty());
+
+    let body = json!({ "peer_id": alice_id });
+    let status = send(&app, &alice, Method::POST, "/contacts/requests", Some(body)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let status = send(&app, &alice, Method::GET, "/contacts?kind=friends", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn blocked_users_cannot_find_each_other() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+    let alice = login_as(&app, "alice@example.com").await;
+    let bob = login_as(&app, "bob@example.com").await;
+    let alice_id = me(&app, &alice).await.peer_id;
+    let bob_profile = me(&app, &bob).await;
+    let bob_id = bob_profile.peer_id.clone();
+    let bob_key = bob_profile.id.split_once(':').unwrap().1.to_string();
+
+    let body = json!({ "peer_id": bob_id });
+    let status = send(&app, &alice, Method::POST, "/contacts/requests", Some(body.clone())).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let uri = format!("/contacts/requests/{alice_id}/accept");
+    assert_eq!(send(&app, &bob, Method::POST, &uri, None).await, StatusCode::OK);
+
+    let block = json!({ "peer_id": alice_id });
+    let status = send(&app, &bob, Method::POST, "/contacts/blocks", Some(block)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert!(list(&app, &alice, "accepted").await.is_empty());
+    assert_eq!(list(&app, &bob, "blocked").await[0].user.peer_id, alice_id);
+
+    // Neither a lookup by peer id nor by id finds the other user
+    let req = request()
+        .uri(&format!("/users?peer_id={bob_id}"))
+        .cookie(alice.clone())
+        .to_request();
+    let res: Response<Vec<PublicProfile>> = test::call_and_read_body_json(&app, req).await;
+    assert!(res.data.unwrap().is_empty());
+    let uri = format!("/users/{bob_key}");
+    assert_eq!(
+        send(&app, &alice, Method::GET, &uri, None).await,
+        StatusCode::NOT_FOUND
+    );
+    let status = send(&app, &alice, Method::POST, "/contacts/requests", Some(body.clone())).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let uri = format!("/contacts/blocks/{alice_id}");
+    assert_eq!(send(&app, &bob, Method::DELETE, &uri, None).await, StatusCode::OK);
+    let uri = format!("/users/{bob_key}");
+    assert_eq!(send(&app, &alice, Method::GET, &uri, None).await, StatusCode::OK);
+    let status = send(&app, &alice, Method::POST, "/contacts/requests", Some(body)).await;
+    assert_eq!(status, StatusCode::CREATED);
+}
@@ -0,0 +1,66 @@
+// tests/crud.rs
+use actix_web::{http::StatusCode, test};
+
+use super::{login_as, register, request, TestContext, PASSWORD};
+use crate::{api::response::Response, models::user_model::PublicProfile};
+
+#[actix_web::test]
+async fn list_paginates_with_cursor() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+    let cookie = login_as(&app, "alice@example.com").await;
+    for name in ["bob", "carol"] {
+        register(&app, &format!("{name}@example.com"), PASSWORD).await;
+    }
+
+    let req = request()
+        .uri("/users?sort=peer_id&limit=2")
+        .cookie(cookie.clone())
+        .to_request();
+    let page: Response<Vec<PublicProfile>> = test::call_and_read_body_json(&app, req).await;
+    let pagination = page.pagination.unwrap();
+    let mut peer_ids: Vec<String> = page.data.unwrap().into_iter().map(|u| u.peer_id).collect();
+    assert_eq!(peer_ids.len(), 2);
+    assert_eq!(pagination.total, 3);
+
+    let req = request()
+        .uri(&format!(
+            "/users?sort=peer_id&limit=2&cursor={}",
+            pagination.next_cursor.unwrap()
+        ))
+        .cookie(cookie)
+        .to_request();
+    let page: Response<Vec<PublicProfile>> = test::call_and_read_body_json(&app, req).await;
+    let last: Vec<String> = page.data.unwrap().into_iter().map(|u| u.peer_id).collect();
+    assert_eq!(last.len(), 1);
+    assert!(page.pagination.unwrap().next_cursor.is_none());
+
+    // The pages follow each other in order
+    peer_ids.extend(last);
+    let mut sorted = peer_ids.clone();
+    sorted.sort();
+    sorted.dedup();
+    assert_eq!(peer_ids, sorted);
+}
+
+#[actix_web::test]
+async fn list_filters_and_rejects_hidden_fields() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+    let cookie = login_as(&app, "alice@example.com").await;
+    register(&app, "bob@example.com", PASSWORD).await;
+
+    let req = request()
+        .uri("/users?name[contains]=Test")
+        .cookie(cookie.clone())
+        .to_request();
+    let page: Response<Vec<PublicProfile>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page.data.unwrap().len(), 2);
+
+    // Emails cannot be looked up
+    for uri in ["/users?email=bob@example.com", "/users?password_hash[contains]=argon2"] {
+        let req = request().uri(uri).cookie(cookie.clone()).to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{uri}");
+    }
+}
@@ -0,0 +1,68 @@
+// tests/errors.rs
+use actix_web::{
+    get,
+    http::{header, Method, StatusCode},
+    test, App,
+};
+
+use super::{register, request, TestContext};
+use crate::{
+    api::response::{ApiResult, Response},
+    error::ProblemDetails,
+    internalError,
+};
+
+#[get("/broken")]
+async fn broken() -> ApiResult<()> {
+    internalError!("Database Error")
+}
+
+#[actix_web::test]
+async fn errors_are_problem_json_with_code() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+
+    let res = register(&app, "not-an-email", "Sup3rSecret").await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        res.headers().get(header::CONTENT_TYPE).unwrap(),
+        "application/problem+json"
+    );
+
+    let problem: ProblemDetails = test::read_body_json(res).await;
+    assert_eq!(problem.status, 400);
+    assert_eq!(problem.title, "Bad Request");
+    assert_eq!(problem.code, "validation_failed");
+}
+
+#[actix_web::test]
+async fn malformed_json_is_a_descriptive_bad_request() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+
+    let req = request()
+        .method(Method::POST)
+        .uri("/auth/login")
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_payload(r#"{ "email": "alice@example.com" }"#)
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let problem: ProblemDetails = test::read_body_json(res).await;
+    assert_eq!(problem.code, "validation_failed");
+    assert!(problem.detail.contains("password"));
+}
+
+#[actix_web::test]
+async fn internal_error_macro_returns_a_500_response() {
+    let app = test::init_service(App::new().service(broken)).await;
+
+    let res = test::call_service(&app, request().uri("/broken").to_request()).await;
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let body: Response<()> = test::read_body_json(res).await;
+    assert_eq!(body.code, 500);
+    assert!(!body.success);
+    assert_eq!(body.message, "Database Error");
+}
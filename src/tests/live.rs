@@ -0,0 +1,139 @@
+// tests/live.rs
+use std::{future::poll_fn, pin::Pin, time::Duration};
+
+use actix_web::{
+    body::MessageBody,
+    http::{Method, StatusCode},
+    rt, test,
+};
+use serde_json::json;
+
+use super::{login_as, register, request, TestContext, PASSWORD};
+use crate::{api::response::Response, models::user_model::UserProfile};
+
+/// Read the event stream until an event named `name` arrives, returning everything read
+pub async fn read_until<B>(body: &mut Pin<Box<B>>, name: &str) -> String
+where
+    B: MessageBody,
+    B::Error: std::fmt::Debug,
+{
+    let mut text = String::new();
+    let read = async {
+        while !text.contains(&format!("event: {name}\n")) {
+            let chunk = poll_fn(|cx| body.as_mut().poll_next(cx))
+                .await
+                .expect("event stream to stay open")
+                .expect("event stream to be readable");
+            text.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    };
+    rt::time::timeout(Duration::from_secs(5), read)
+        .await
+        .unwrap_or_else(|_| panic!("no {name} event was received"));
+
+    text
+}
+
+#[actix_web::test]
+async fn live_streams_created_users() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+    let cookie = login_as(&app, "alice@example.com").await;
+
+    let req = request().uri("/users/live").cookie(cookie).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let mut body = Box::pin(res.into_body());
+
+    let ready = read_until(&mut body, "ready").await;
+    assert!(ready.contains("id: "));
+
+    let res = register(&app, "bob@example.com", PASSWORD).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let created = read_until(&mut body, "create").await;
+    assert!(created.contains(r#""action":"create""#));
+    assert!(created.contains(r#""name":"Test User""#));
+    assert!(!created.contains("bob@example.com"));
+    assert!(!created.contains("password_hash"));
+}
+
+#[actix_web::test]
+async fn live_deletes_users_who_become_invisible() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+    let alice = login_as(&app, "alice@example.com").await;
+    let bob = login_as(&app, "bob@example.com").await;
+    let req = request().uri("/users/me").cookie(bob.clone()).to_request();
+    let res: Response<UserProfile> = test::call_and_read_body_json(&app, req).await;
+    let bob_id = res.data.unwrap().id;
+
+    let req = request().uri("/users/live").cookie(alice).to_request();
+    let res = test::call_service(&app, req).await;
+    let mut body = Box::pin(res.into_body());
+    read_until(&mut body, "ready").await;
+
+    for visible in [false, true] {
+        let req = request()
+            .method(Method::PATCH)
+            .uri("/users/me")
+            .cookie(bob.clone())
+            .set_json(json!({ "is_visible": visible }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+
+    // Hidden users are deleted from the view of others, then come back as an update
+    let events = read_until(&mut body, "update").await;
+    let deleted = events.find("event: delete\n").expect("a delete event");
+    assert!(events[deleted..].contains(&bob_id));
+    assert!(!events[..deleted].contains("event: update\n"));
+}
+
+#[actix_web::test]
+async fn live_hides_blocked_users() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+    let alice = login_as(&app, "alice@example.com").await;
+    let bob = login_as(&app, "bob@example.com").await;
+    let req = request().uri("/users/me").cookie(bob.clone()).to_request();
+    let res: Response<UserProfile> = test::call_and_read_body_json(&app, req).await;
+    let bob_peer_id = res.data.unwrap().peer_id;
+
+    let req = request()
+        .method(Method::POST)
+        .uri("/contacts/blocks")
+        .cookie(alice.clone())
+        .set_json(json!({ "peer_id": bob_peer_id }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+
+    let req = request().uri("/users/live").cookie(alice).to_request();
+    let res = test::call_service(&app, req).await;
+    let mut body = Box::pin(res.into_body());
+    read_until(&mut body, "ready").await;
+
+    let req = request()
+        .method(Method::PATCH)
+        .uri("/users/me")
+        .cookie(bob)
+        .set_json(json!({ "name": "Bob" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let res = register(&app, "carol@example.com", PASSWORD).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    // Changes arrive in order, Bob's would come before Carol's creation
+    let events = read_until(&mut body, "create").await;
+    assert!(!events.contains(r#""name":"Bob""#));
+}
+
+#[actix_web::test]
+async fn live_requires_a_session() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+
+    let req = request().uri("/users/live").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,119 @@
+// tests/middleware.rs
+use actix_web::{
+    http::{header, Method, StatusCode},
+    test,
+};
+
+use super::{request, TestContext};
+use crate::{app::request_line, config::RateLimitConfig, cors::origin_matches};
+
+const ORIGIN: &str = "http://localhost:3000";
+
+#[actix_web::test]
+async fn cors_preflight_allows_frontend() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+
+    let req = request()
+        .method(Method::OPTIONS)
+        .uri("/health")
+        .insert_header((header::ORIGIN, ORIGIN))
+        .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "GET"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+        ORIGIN
+    );
+}
+
+#[actix_web::test]
+async fn error_responses_carry_cors_headers() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+
+    let req = request()
+        .uri("/users/me")
+        .insert_header((header::ORIGIN, ORIGIN))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        res.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+        ORIGIN
+    );
+}
+
+#[actix_web::test]
+async fn governor_limits_bursts() {
+    let ctx = TestContext::with_rate_limit(RateLimitConfig {
+        per_second: 60,
+        burst_size: 2,
+    })
+    .await;
+    let app = ctx.init().await;
+
+    for _ in 0..2 {
+        let res = test::call_service(&app, request().uri("/health").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    let res = test::call_service(&app, request().uri("/health").to_request()).await;
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[test]
+fn wildcard_origins_only_match_subdomains() {
+    let pattern = "https://*.example.com";
+
+    assert!(origin_matches(pattern, "https://staging.example.com"));
+    assert!(origin_matches(pattern, "https://a.b.example.com"));
+    assert!(!origin_matches(pattern, "https://example.com"));
+    assert!(!origin_matches(pattern, "http://staging.example.com"));
+    assert!(!origin_matches(pattern, "https://staging.example.com.evil.io"));
+    assert!(!origin_matches(pattern, "https://evilexample.com"));
+}
+
+#[actix_web::test]
+async fn cors_allows_configured_wildcard_origin() {
+    let ctx = TestContext::configured(|config| {
+        config.cors.allowed_origins = vec!["https://*.example.com".to_string()];
+    })
+    .await;
+    let app = ctx.init().await;
+
+    let preflight = |origin: &'static str| {
+        request()
+            .method(Method::OPTIONS)
+            .uri("/health")
+            .insert_header((header::ORIGIN, origin))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "GET"))
+            .to_request()
+    };
+
+    let res = test::call_service(&app, preflight("https://staging.example.com")).await;
+    assert_eq!(
+        res.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+        "https://staging.example.com"
+    );
+
+    let res = test::call_service(&app, preflight("http://localhost:3000")).await;
+    assert!(res.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+}
+
+#[test]
+fn access_log_masks_secret_parameters() {
+    let req = request()
+        .uri("/timetable.ics?token=secret&from=2024-03-04")
+        .to_srv_request();
+    assert_eq!(
+        request_line(&req),
+        "GET /timetable.ics?token=***&from=2024-03-04 HTTP/1.1"
+    );
+
+    let req = request().uri("/health").to_srv_request();
+    assert_eq!(request_line(&req), "GET /health HTTP/1.1");
+}
@@ -0,0 +1,211 @@
+// tests/mod.rs
+mod auth;
+mod calendar;
+mod config;
+mod contacts;
+mod crud;
+mod errors;
+mod live;
+mod middleware;
+mod notifications;
+mod presence;
+mod signaling;
+mod timetable;
+mod transaction;
+mod untis;
+mod users;
+
+use actix_http::Request;
+use actix_web::{
+    body::MessageBody,
+    cookie::{Cookie, Key},
+    dev::{Service, ServiceResponse},
+    http::StatusCode,
+    test::{self, TestRequest},
+    web::Data,
+};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    app::{create_app, AppState},
+    config::{AppConfig, RateLimitConfig, UntisConfig},
+    jobs::timetable::SyncLocks,
+    repository::{
+        live::LiveHub,
+        migrations::{self, MigrationMode},
+        surrealdb_repo::SurrealDBRepo,
+    },
+    signaling::{hub::SignalingHub, presence::PresenceTracker},
+    utils::env::get_env_or,
+};
+
+/// Address every test request comes from, the Governor needs a peer address
+pub const PEER_ADDR: &str = "127.0.0.1:54321";
+
+/// A password accepted by the registration rules
+pub const PASSWORD: &str = "Sup3rSecret";
+
+/// Key encrypting the WebUntis credentials stored by the tests, 32 zero bytes
+pub const UNTIS_KEY: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=";
+
+/// Rate limit high enough to never interfere with a test
+const TEST_RATE_LIMIT: RateLimitConfig = RateLimitConfig {
+    per_second: 1,
+    burst_size: 1000,
+};
+
+/// Everything a test needs to build the App against a throwaway database
+///
+/// ## Fields
+///
+/// * `state` is the state passed to `create_app`
+pub struct TestContext {
+    pub state: AppState,
+}
+
+impl TestContext {
+    /// Create a context with an isolated namespace and database
+    ///
+    /// Uses an in-memory SurrealDB unless `TEST_DB_LOCATION` points to a server
+    pub async fn new() -> Self {
+        Self::configured(|_| {}).await
+    }
+
+    /// Create a context with a custom Governor rate limit
+    ///
+    /// ## Arguments
+    ///
+    /// * `rate_limit` - The rate limit of the App
+    pub async fn with_rate_limit(rate_limit: RateLimitConfig) -> Self {
+        Self::configured(|config| config.rate_limit = rate_limit).await
+    }
+
+    /// Create a context from the test configuration changed by a closure
+    ///
+    /// ## Arguments
+    ///
+    /// * `configure` - Changes the test configuration, e.g. to lower a limit
+    pub async fn configured(configure: impl FnOnce(&mut AppConfig)) -> Self {
+        let mut config = AppConfig::default();
+        config.session.cookie_secure = false;
+        config.tls.enabled = false;
+        config.rate_limit = TEST_RATE_LIMIT;
+        config.untis = UntisConfig {
+            key: Some(UNTIS_KEY.to_string()),
+            ..untis::config()
+        };
+        config.proxy.reverse_proxy = Some([127, 0, 0, 1].into());
+        configure(&mut config);
+
+        Self::with_config(config).await
+    }
+
+    /// Create a context from a custom configuration
+    ///
+    /// The database settings of the configuration are replaced by the throwaway database
+    ///
+    /// ## Arguments
+    ///
+    /// * `config` - The configuration of the App
+    pub async fn with_config(mut config: AppConfig) -> Self {
+        let name = format!("test_{}", Uuid::new_v4().simple());
+        config.database.location = get_env_or("TEST_DB_LOCATION", "mem://");
+        config.database.username = get_env_or("TEST_DB_USERNAME", "root");
+        config.database.password = get_env_or("TEST_DB_PASSWORD", "root");
+        config.database.namespace = name.clone();
+        config.database.database = name;
+
+        let surreal = SurrealDBRepo::init(&config.database)
+            .await
+            .expect("test database to connect");
+
+        migrations::run(&surreal.db, MigrationMode::Apply)
+            .await
+            .expect("migrations to apply");
+
+        Self {
+            state: AppState {
+                live: Data::new(LiveHub::new(surreal.db.clone())),
+                signaling: Data::new(SignalingHub::new()),
+                presence: Data::new(PresenceTracker::new()),
+                syncs: Data::new(SyncLocks::new()),
+                surreal,
+                cookie_key: Key::generate(),
+                config: Data::new(config),
+            },
+        }
+    }
+
+    /// Initialize the App as a service that test requests can be sent to
+    pub async fn init(
+        &self,
+    ) -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = actix_web::Error>
+    {
+        test::init_service(create_app(&self.state)).await
+    }
+
+    /// Serve the App on a local port, for clients that need a real connection like WebSockets
+    pub fn start(&self) -> actix_test::TestServer {
+        let state = self.state.clone();
+
+        actix_test::start(move || create_app(&state))
+    }
+}
+
+/// Build a test request coming from `PEER_ADDR`
+pub fn request() -> TestRequest {
+    TestRequest::default().peer_addr(PEER_ADDR.parse().unwrap())
+}
+
+/// Register a user through `POST /auth/register`
+///
+/// ## Arguments
+///
+/// * `app` - The initialized App
+/// * `email` - The user's email
+/// * `password` - The user's password
+pub async fn register<S, B>(app: &S, email: &str, password: &str) -> ServiceResponse<B>
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let req = request()
+        .method(actix_web::http::Method::POST)
+        .uri("/auth/register")
+        .set_json(json!({ "name": "Test User", "email": email, "password": password }))
+        .to_request();
+
+    test::call_service(app, req).await
+}
+
+/// Register and log a user in, returning their session cookie
+///
+/// ## Arguments
+///
+/// * `app` - The initialized App
+/// * `email` - The user's email
+pub async fn login_as<S, B>(app: &S, email: &str) -> Cookie<'static>
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let res = register(app, email, PASSWORD).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let req = request()
+        .method(actix_web::http::Method::POST)
+        .uri("/auth/login")
+        .set_json(json!({ "email": email, "password": PASSWORD }))
+        .to_request();
+    let res = test::call_service(app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let cookie = res
+        .response()
+        .cookies()
+        .find(|cookie| cookie.name() == "id")
+        .expect("session cookie to be set");
+
+    cookie.into_owned()
+}
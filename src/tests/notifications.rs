@@ -0,0 +1,243 @@
+// tests/notifications.rs
+use actix_web::{
+    http::{Method, StatusCode},
+    test,
+    web::Data,
+};
+use futures::future::join;
+use surrealdb::sql::Thing;
+
+use super::{
+    live::read_until,
+    login_as, request,
+    untis::{mock, PASSWORD, SCHOOL, USER},
+    TestContext,
+};
+use crate::{
+    api::response::Response,
+    jobs::timetable::{sync, window},
+    models::{
+        notification_model::{
+            Notification, NotificationCreate, NotificationEntry, NotificationKind,
+        },
+        timetable_model::{Lesson, LessonChange, LessonStatus},
+        untis_model::{UntisAccount, UntisCredentials},
+        user_model::User,
+    },
+};
+
+/// A regular lesson in room 101 with Smi
+fn lesson() -> Lesson {
+    Lesson {
+        id: Thing::from(("lesson", "1")),
+        user: Thing::from(("users", "alice")),
+        period: 1,
+        date: "2024-03-04".to_string(),
+        start: "2024-03-04T08:00:00".to_string(),
+        end: "2024-03-04T08:45:00".to_string(),
+        subjects: vec!["Mathematics".to_string()],
+        teachers: vec!["Smi".to_string()],
+        rooms: vec!["101".to_string()],
+        klassen: vec!["5a".to_string()],
+        original_teachers: Vec::new(),
+        original_rooms: Vec::new(),
+        status: LessonStatus::Regular,
+        text: None,
+        substitution_text: None,
+        info: None,
+        updated_at: chrono::Utc::now().to_rfc3339(),
+    }
+}
+
+/// The kinds of the notifications of a change
+fn kinds(change: LessonChange) -> Vec<NotificationKind> {
+    Notification::of_change(&change, "2024-03-01T12:00:00Z")
+        .into_iter()
+        .map(|notification| notification.kind)
+        .collect()
+}
+
+/// A notification of a room change for a user
+fn room_changed(user: &Thing) -> NotificationCreate {
+    NotificationCreate {
+        user: user.clone(),
+        kind: NotificationKind::RoomChanged,
+        period: 1,
+        date: "2024-03-04".to_string(),
+        start: "2024-03-04T08:00:00".to_string(),
+        subjects: vec!["Mathematics".to_string()],
+        previous: vec!["101".to_string()],
+        current: vec!["102".to_string()],
+        text: None,
+        read_at: None,
+        created_at: chrono::Utc::now().to_rfc3339(),
+    }
+}
+
+#[test]
+fn changes_are_typed() {
+    let mut cancelled = lesson();
+    cancelled.status = LessonStatus::Cancelled;
+    assert_eq!(kinds(LessonChange::Added(cancelled.clone())), [NotificationKind::Cancelled]);
+    let change = LessonChange::Changed {
+        before: lesson(),
+        after: cancelled.clone(),
+    };
+    assert_eq!(kinds(change), [NotificationKind::Cancelled]);
+    let change = LessonChange::Changed {
+        before: cancelled.clone(),
+        after: cancelled,
+    };
+    assert!(kinds(change).is_empty());
+
+    let mut substituted = lesson();
+    substituted.teachers = vec!["Doe".to_string()];
+    substituted.rooms = vec!["102".to_string()];
+    let change = LessonChange::Changed {
+        before: lesson(),
+        after: substituted.clone(),
+    };
+    assert_eq!(
+        kinds(change),
+        [NotificationKind::RoomChanged, NotificationKind::Substitution]
+    );
+
+    substituted.original_teachers = vec!["Smi".to_string()];
+    assert_eq!(kinds(LessonChange::Added(substituted)), [NotificationKind::Substitution]);
+    assert!(kinds(LessonChange::Added(lesson())).is_empty());
+    assert!(kinds(LessonChange::Removed(lesson())).is_empty());
+}
+
+#[actix_web::test]
+async fn notifications_are_listed_and_read() {
+    let ctx = TestContext::new().await;
+    let db = &ctx.state.surreal.db;
+    let app = ctx.init().await;
+    let alice = login_as(&app, "alice@example.com").await;
+    let bob = login_as(&app, "bob@example.com").await;
+    let id = User::get_from_email(Data::new(db.clone()), "alice@example.com")
+        .await
+        .unwrap()
+        .unwrap()
+        .id;
+    let notifications = vec![room_changed(&id), room_changed(&id)];
+    Notification::create_all(db, notifications).await.unwrap();
+
+    let req = request().uri("/notifications").cookie(alice.clone()).to_request();
+    let res: Response<Vec<NotificationEntry>> = test::call_and_read_body_json(&app, req).await;
+    let listed = res.data.unwrap();
+    assert_eq!(listed.len(), 2);
+    assert!(listed.iter().all(|notification| !notification.read));
+    let req = request().uri("/notifications").cookie(bob.clone()).to_request();
+    let res: Response<Vec<NotificationEntry>> = test::call_and_read_body_json(&app, req).await;
+    assert!(res.data.unwrap().is_empty());
+
+    // Only their user may read a notification
+    let uri = format!("/notifications/{}/read", listed[0].id);
+    let req = request().method(Method::POST).uri(&uri).cookie(bob).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    let req = request().method(Method::POST).uri(&uri).cookie(alice.clone()).to_request();
+    let res: Response<NotificationEntry> = test::call_and_read_body_json(&app, req).await;
+    assert!(res.data.unwrap().read);
+
+    let req = request()
+        .uri("/notifications?unread=true")
+        .cookie(alice.clone())
+        .to_request();
+    let res: Response<Vec<NotificationEntry>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(res.data.unwrap().len(), 1);
+
+    let req = request()
+        .method(Method::POST)
+        .uri("/notifications/read")
+        .cookie(alice.clone())
+        .to_request();
+    let res: Response<usize> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(res.data, Some(1));
+
+    let req = request()
+        .uri("/notifications?unread=true")
+        .cookie(alice.clone())
+        .to_request();
+    let res: Response<Vec<NotificationEntry>> = test::call_and_read_body_json(&app, req).await;
+    assert!(res.data.unwrap().is_empty());
+
+    let req = request()
+        .uri("/notifications?unread=maybe")
+        .cookie(alice)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn notifications_are_streamed() {
+    let ctx = TestContext::new().await;
+    let db = &ctx.state.surreal.db;
+    let app = ctx.init().await;
+    let cookie = login_as(&app, "alice@example.com").await;
+    let id = User::get_from_email(Data::new(db.clone()), "alice@example.com")
+        .await
+        .unwrap()
+        .unwrap()
+        .id;
+
+    let req = request().uri("/notifications/live").cookie(cookie).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let mut body = Box::pin(res.into_body());
+    read_until(&mut body, "ready").await;
+
+    Notification::create_all(db, vec![room_changed(&id)]).await.unwrap();
+    let created = read_until(&mut body, "create").await;
+    assert!(created.contains(r#""kind":"room_changed""#));
+    assert!(!created.contains("users:"));
+}
+
+#[actix_web::test]
+async fn upcoming_changes_found_by_the_sync_are_notified() {
+    let srv = mock();
+    let ctx = TestContext::new().await;
+    let db = &ctx.state.surreal.db;
+    let config = &ctx.state.config.untis;
+    let cipher = config.cipher().unwrap();
+    let syncs = &ctx.state.syncs;
+    let user = Thing::from(("users", "alice"));
+    let credentials = UntisCredentials {
+        username: USER.to_string(),
+        password: PASSWORD.to_string(),
+    };
+    let server = format!("http://{}", srv.addr());
+    let account = UntisAccount::link(db, &cipher, &user, &server, SCHOOL, &credentials)
+        .await
+        .unwrap();
+
+    // The first snapshot is not notified, though it has changes
+    sync(db, config, &cipher, syncs, &account).await.unwrap();
+    let listed = Notification::list(db, &user, false, Default::default()).await.unwrap();
+    assert_eq!(listed.total, 0);
+
+    // The last lesson of the window is cancelled, pretend it was not
+    let (_, last) = window(config).unwrap();
+    db.query("UPDATE lesson SET status = 'regular' WHERE date = $last")
+        .bind(("last", last.to_string()))
+        .await
+        .unwrap()
+        .check()
+        .unwrap();
+    // Overlapping synchronisations find the change once
+    let (first, second) = join(
+        sync(db, config, &cipher, syncs, &account),
+        sync(db, config, &cipher, syncs, &account),
+    )
+    .await;
+    assert_eq!(first.unwrap().len() + second.unwrap().len(), 1);
+
+    let listed = Notification::list(db, &user, true, Default::default()).await.unwrap();
+    assert_eq!(listed.total, 1);
+    assert_eq!(listed.items[0].kind, NotificationKind::Cancelled);
+    assert_eq!(listed.items[0].date, last.to_string());
+
+    assert!(UntisAccount::unlink(db, &user).await.unwrap());
+    let listed = Notification::list(db, &user, false, Default::default()).await.unwrap();
+    assert_eq!(listed.total, 0);
+}
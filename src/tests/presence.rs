@@ -0,0 +1,179 @@
+// tests/presence.rs
+use std::time::Duration;
+
+use actix_test::TestServer;
+use actix_web::{body::BodyStream, cookie::Cookie, http::StatusCode, rt};
+use futures::SinkExt;
+use serde_json::json;
+
+use super::{
+    live::read_until,
+    signaling::{befriend, login_on, open, peer_id_of, register_on},
+    TestContext,
+};
+use crate::{api::response::Response, models::presence_model::PresenceStatus};
+
+/// Get the presence of users through `GET /presence`
+async fn presence(
+    srv: &TestServer,
+    cookie: &Cookie<'static>,
+    peer_ids: &str,
+) -> Vec<PresenceStatus> {
+    let mut res = srv
+        .get(format!("/presence?peer_ids={peer_ids}"))
+        .cookie(cookie.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Response<Vec<PresenceStatus>> = res.json().await.unwrap();
+
+    body.data.unwrap()
+}
+
+/// Poll the presence of a user until it is the expected one
+async fn wait_for(
+    srv: &TestServer,
+    cookie: &Cookie<'static>,
+    peer_id: &str,
+    online: bool,
+) -> PresenceStatus {
+    for _ in 0..50 {
+        let status = presence(srv, cookie, peer_id).await.remove(0);
+        if status.online == online {
+            return status;
+        }
+        rt::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    panic!("{peer_id} never became {}", if online { "online" } else { "offline" });
+}
+
+#[actix_web::test]
+async fn presence_follows_the_signaling_connection() {
+    let ctx = TestContext::new().await;
+    let srv = ctx.start();
+    register_on(&srv, "bob@example.com").await;
+    let bob_cookie = login_on(&srv, "bob@example.com").await;
+    let (mut bob, bob_id) = open(&srv, bob_cookie.clone()).await;
+    register_on(&srv, "alice@example.com").await;
+    let alice = login_on(&srv, "alice@example.com").await;
+
+    // Only contacts may appear online
+    let status = presence(&srv, &alice, &bob_id).await.remove(0);
+    assert!(!status.online);
+    befriend(&srv, &alice, &bob_cookie).await;
+
+    let statuses = presence(&srv, &alice, &format!("{bob_id},nobody")).await;
+    assert_eq!(
+        statuses,
+        vec![
+            PresenceStatus {
+                peer_id: bob_id.clone(),
+                online: true,
+                last_seen: None,
+            },
+            PresenceStatus {
+                peer_id: "nobody".to_string(),
+                online: false,
+                last_seen: None,
+            },
+        ]
+    );
+
+    bob.close().await.unwrap();
+    let status = wait_for(&srv, &alice, &bob_id, false).await;
+    assert!(status.last_seen.is_some());
+
+    let res = srv
+        .get("/presence?peer_ids=,")
+        .cookie(alice)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn invisible_users_appear_offline() {
+    let ctx = TestContext::new().await;
+    let srv = ctx.start();
+    register_on(&srv, "bob@example.com").await;
+    let bob = login_on(&srv, "bob@example.com").await;
+    let res = srv
+        .patch("/users/me")
+        .cookie(bob.clone())
+        .send_json(&json!({ "is_visible": false }))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let (_ws, bob_id) = open(&srv, bob.clone()).await;
+    register_on(&srv, "alice@example.com").await;
+    let alice = login_on(&srv, "alice@example.com").await;
+    befriend(&srv, &alice, &bob).await;
+
+    let status = presence(&srv, &alice, &bob_id).await.remove(0);
+    assert!(!status.online);
+    assert_eq!(status.last_seen, None);
+
+    // Users always see their own presence
+    let status = presence(&srv, &bob, &bob_id).await.remove(0);
+    assert!(status.online);
+}
+
+#[actix_web::test]
+async fn live_presence_pushes_changes() {
+    let ctx = TestContext::new().await;
+    let srv = ctx.start();
+    register_on(&srv, "bob@example.com").await;
+    let bob_cookie = login_on(&srv, "bob@example.com").await;
+    let (mut bob, bob_id) = open(&srv, bob_cookie.clone()).await;
+    register_on(&srv, "alice@example.com").await;
+    let alice = login_on(&srv, "alice@example.com").await;
+    befriend(&srv, &alice, &bob_cookie).await;
+
+    let res = srv
+        .get(format!("/presence/live?peer_ids={bob_id}"))
+        .cookie(alice)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let mut events = Box::pin(BodyStream::new(res));
+    assert!(read_until(&mut events, "presence").await.contains(r#""online":true"#));
+
+    bob.close().await.unwrap();
+    assert!(read_until(&mut events, "presence").await.contains(r#""online":false"#));
+}
+
+#[actix_web::test]
+async fn live_presence_follows_blocks() {
+    let ctx = TestContext::new().await;
+    let srv = ctx.start();
+    register_on(&srv, "bob@example.com").await;
+    let bob = login_on(&srv, "bob@example.com").await;
+    let (_ws, bob_id) = open(&srv, bob.clone()).await;
+    register_on(&srv, "alice@example.com").await;
+    let alice = login_on(&srv, "alice@example.com").await;
+    befriend(&srv, &alice, &bob).await;
+
+    let res = srv
+        .get(format!("/presence/live?peer_ids={bob_id}"))
+        .cookie(alice.clone())
+        .send()
+        .await
+        .unwrap();
+    let mut events = Box::pin(BodyStream::new(res));
+    assert!(read_until(&mut events, "presence").await.contains(r#""online":true"#));
+
+    // Bob is still connected, but no longer visible to Alice
+    let alice_id = peer_id_of(&srv, &alice).await;
+    let res = srv
+        .post("/contacts/blocks")
+        .cookie(bob)
+        .send_json(&json!({ "peer_id": alice_id }))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    assert!(read_until(&mut events, "presence").await.contains(r#""online":false"#));
+}
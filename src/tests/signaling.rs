@@ -0,0 +1,342 @@
+// tests/signaling.rs
+use std::time::Duration;
+
+use actix_test::TestServer;
+use actix_web::{cookie::Cookie, http::StatusCode, rt};
+use awc::{
+    error::WsClientError,
+    ws::{Codec, Frame, Message},
+    BoxedSocket,
+};
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+
+use super::{TestContext, PASSWORD};
+use crate::{
+    api::response::Response,
+    models::user_model::UserProfile,
+    signaling::{
+        message::{IceCandidate, ServerMessage},
+        rate_limit::RateLimiter,
+    },
+};
+
+pub type WebSocket = actix_codec::Framed<BoxedSocket, Codec>;
+
+/// A minimal session description
+const SDP: &str = "v=0\r\no=- 0 0 IN IP4 127.0.0.1\r\ns=-\r\nt=0 0\r\n";
+
+/// Register a user on the server
+pub async fn register_on(srv: &TestServer, email: &str) {
+    let user = json!({ "name": "Test User", "email": email, "password": PASSWORD });
+    let res = srv.post("/auth/register").send_json(&user).await.unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+}
+
+/// Log a registered user in on the server, returning their session cookie
+pub async fn login_on(srv: &TestServer, email: &str) -> Cookie<'static> {
+    let credentials = json!({ "email": email, "password": PASSWORD });
+    let res = srv.post("/auth/login").send_json(&credentials).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    res.cookies()
+        .unwrap()
+        .iter()
+        .find(|cookie| cookie.name() == "id")
+        .expect("session cookie to be set")
+        .clone()
+        .into_owned()
+}
+
+/// Open the signaling WebSocket of a logged in user
+///
+/// ## Returns
+///
+/// * `(WebSocket, String)` - The connection and the peer id it is reachable at
+pub async fn open(srv: &TestServer, cookie: Cookie<'static>) -> (WebSocket, String) {
+    let (_, mut ws) = awc::Client::new()
+        .ws(srv.url("/signaling"))
+        .cookie(cookie)
+        .connect()
+        .await
+        .expect("WebSocket to open");
+
+    match receive(&mut ws).await {
+        ServerMessage::Welcome { peer_id } => (ws, peer_id),
+        other => panic!("expected a welcome, got {other:?}"),
+    }
+}
+
+/// Register a user on the server, then open their signaling WebSocket
+pub async fn connect(srv: &TestServer, email: &str) -> (WebSocket, String) {
+    register_on(srv, email).await;
+    let cookie = login_on(srv, email).await;
+
+    open(srv, cookie).await
+}
+
+/// Get the peer id of a logged in user
+pub async fn peer_id_of(srv: &TestServer, cookie: &Cookie<'static>) -> String {
+    let mut res = srv.get("/users/me").cookie(cookie.clone()).send().await.unwrap();
+    let body: Response<UserProfile> = res.json().await.unwrap();
+
+    body.data.unwrap().peer_id
+}
+
+/// Make two users contacts, the first one sending the request
+pub async fn befriend(srv: &TestServer, requester: &Cookie<'static>, requested: &Cookie<'static>) {
+    let requester_id = peer_id_of(srv, requester).await;
+    let requested_id = peer_id_of(srv, requested).await;
+
+    let res = srv
+        .post("/contacts/requests")
+        .cookie(requester.clone())
+        .send_json(&json!({ "peer_id": requested_id }))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let res = srv
+        .post(format!("/contacts/requests/{requester_id}/accept"))
+        .cookie(requested.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+/// Register two users who are contacts, then open their signaling WebSockets
+async fn connect_contacts(
+    srv: &TestServer,
+    requester: &str,
+    requested: &str,
+) -> ((WebSocket, String), (WebSocket, String)) {
+    register_on(srv, requester).await;
+    register_on(srv, requested).await;
+    let requester = login_on(srv, requester).await;
+    let requested = login_on(srv, requested).await;
+    befriend(srv, &requester, &requested).await;
+
+    (open(srv, requester).await, open(srv, requested).await)
+}
+
+/// Send a JSON message
+async fn send(ws: &mut WebSocket, message: Value) {
+    ws.send(Message::Text(message.to_string().into())).await.unwrap();
+}
+
+/// Receive the next server message, skipping heartbeats
+pub async fn receive(ws: &mut WebSocket) -> ServerMessage {
+    loop {
+        let frame = rt::time::timeout(Duration::from_secs(5), ws.next())
+            .await
+            .expect("a message to be received")
+            .expect("WebSocket to stay open")
+            .expect("frame to be valid");
+
+        match frame {
+            Frame::Text(text) => return serde_json::from_slice(&text).unwrap(),
+            Frame::Ping(_) | Frame::Pong(_) => continue,
+            other => panic!("unexpected frame {other:?}"),
+        }
+    }
+}
+
+/// Receive the next message, expecting an error
+async fn receive_error(ws: &mut WebSocket) -> String {
+    match receive(ws).await {
+        ServerMessage::Error { code, .. } => code,
+        other => panic!("expected an error, got {other:?}"),
+    }
+}
+
+#[actix_web::test]
+async fn relays_offer_answer_and_candidates() {
+    let ctx = TestContext::new().await;
+    let srv = ctx.start();
+    let ((mut alice, alice_id), (mut bob, bob_id)) =
+        connect_contacts(&srv, "alice@example.com", "bob@example.com").await;
+
+    send(&mut alice, json!({ "type": "offer", "to": bob_id, "sdp": SDP })).await;
+    assert_eq!(
+        receive(&mut bob).await,
+        ServerMessage::Offer {
+            from: alice_id.clone(),
+            sdp: SDP.to_string()
+        }
+    );
+
+    send(&mut bob, json!({ "type": "answer", "to": alice_id, "sdp": SDP })).await;
+    assert_eq!(
+        receive(&mut alice).await,
+        ServerMessage::Answer {
+            from: bob_id.clone(),
+            sdp: SDP.to_string()
+        }
+    );
+
+    let candidate = "candidate:1 1 UDP 2122252543 192.168.1.2 49203 typ host";
+    send(
+        &mut bob,
+        json!({
+            "type": "ice_candidate",
+            "to": alice_id,
+            "candidate": { "candidate": candidate, "sdpMid": "0", "sdpMLineIndex": 0 }
+        }),
+    )
+    .await;
+    assert_eq!(
+        receive(&mut alice).await,
+        ServerMessage::IceCandidate {
+            from: bob_id,
+            candidate: IceCandidate {
+                candidate: candidate.to_string(),
+                sdp_mid: Some("0".to_string()),
+                sdp_m_line_index: Some(0),
+                username_fragment: None,
+            }
+        }
+    );
+}
+
+#[actix_web::test]
+async fn rejects_invalid_messages() {
+    let ctx = TestContext::new().await;
+    let srv = ctx.start();
+    let (mut alice, alice_id) = connect(&srv, "alice@example.com").await;
+    let (_bob, bob_id) = connect(&srv, "bob@example.com").await;
+
+    alice.send(Message::Text("not json".into())).await.unwrap();
+    assert_eq!(receive_error(&mut alice).await, "validation_failed");
+
+    send(&mut alice, json!({ "type": "offer", "to": alice_id, "sdp": SDP })).await;
+    assert_eq!(receive_error(&mut alice).await, "validation_failed");
+
+    send(&mut alice, json!({ "type": "offer", "to": bob_id, "sdp": "hello" })).await;
+    assert_eq!(receive_error(&mut alice).await, "validation_failed");
+
+    send(&mut alice, json!({ "type": "offer", "to": "nobody", "sdp": SDP })).await;
+    assert_eq!(receive_error(&mut alice).await, "not_found");
+}
+
+#[actix_web::test]
+async fn blocked_peers_look_offline() {
+    let ctx = TestContext::new().await;
+    let srv = ctx.start();
+    let (mut alice, alice_id) = connect(&srv, "alice@example.com").await;
+    register_on(&srv, "bob@example.com").await;
+    let bob = login_on(&srv, "bob@example.com").await;
+    let (_bob_ws, bob_id) = open(&srv, bob.clone()).await;
+
+    let res = srv
+        .post("/contacts/blocks")
+        .cookie(bob)
+        .send_json(&json!({ "peer_id": alice_id }))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    send(&mut alice, json!({ "type": "offer", "to": bob_id, "sdp": SDP })).await;
+    assert_eq!(receive_error(&mut alice).await, "not_found");
+}
+
+#[actix_web::test]
+async fn peers_who_are_not_contacts_look_offline() {
+    let ctx = TestContext::new().await;
+    let srv = ctx.start();
+    let (mut alice, _) = connect(&srv, "alice@example.com").await;
+    let (_bob, bob_id) = connect(&srv, "bob@example.com").await;
+
+    send(&mut alice, json!({ "type": "offer", "to": bob_id, "sdp": SDP })).await;
+    assert_eq!(receive_error(&mut alice).await, "not_found");
+}
+
+#[actix_web::test]
+async fn invisible_peers_look_offline() {
+    let ctx = TestContext::new().await;
+    let srv = ctx.start();
+    register_on(&srv, "alice@example.com").await;
+    register_on(&srv, "bob@example.com").await;
+    let alice = login_on(&srv, "alice@example.com").await;
+    let bob = login_on(&srv, "bob@example.com").await;
+    befriend(&srv, &alice, &bob).await;
+    let bob_id = peer_id_of(&srv, &bob).await;
+    let (mut alice, _) = open(&srv, alice).await;
+
+    send(&mut alice, json!({ "type": "offer", "to": bob_id, "sdp": SDP })).await;
+    let offline = receive(&mut alice).await;
+    assert!(matches!(offline, ServerMessage::Error { .. }));
+
+    let res = srv
+        .patch("/users/me")
+        .cookie(bob.clone())
+        .send_json(&json!({ "is_visible": false }))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let (_bob, _) = open(&srv, bob).await;
+
+    // Online but invisible, the contact gets the same error as an offline one
+    send(&mut alice, json!({ "type": "offer", "to": bob_id, "sdp": SDP })).await;
+    assert_eq!(receive(&mut alice).await, offline);
+}
+
+#[actix_web::test]
+async fn rate_limits_each_connection() {
+    let ctx = TestContext::configured(|config| {
+        config.signaling.messages_per_second = 1;
+        config.signaling.burst_size = 2;
+    })
+    .await;
+    let srv = ctx.start();
+    let ((mut alice, _), (mut bob, bob_id)) =
+        connect_contacts(&srv, "alice@example.com", "bob@example.com").await;
+
+    for _ in 0..3 {
+        send(&mut alice, json!({ "type": "offer", "to": bob_id, "sdp": SDP })).await;
+    }
+    assert_eq!(receive_error(&mut alice).await, "rate_limited");
+
+    // The other connection has its own budget
+    let alice_id = match receive(&mut bob).await {
+        ServerMessage::Offer { from, .. } => from,
+        other => panic!("expected an offer, got {other:?}"),
+    };
+    send(&mut bob, json!({ "type": "answer", "to": alice_id, "sdp": SDP })).await;
+    assert!(matches!(receive(&mut alice).await, ServerMessage::Answer { .. }));
+}
+
+#[actix_web::test]
+async fn reconnecting_replaces_the_previous_connection() {
+    let ctx = TestContext::new().await;
+    let srv = ctx.start();
+    let (mut first, peer_id) = connect(&srv, "alice@example.com").await;
+
+    let cookie = login_on(&srv, "alice@example.com").await;
+    let (_second, second_id) = open(&srv, cookie).await;
+    assert_eq!(second_id, peer_id);
+
+    let frame = rt::time::timeout(Duration::from_secs(5), first.next())
+        .await
+        .expect("the first connection to be closed");
+    assert!(matches!(frame, Some(Ok(Frame::Close(_))) | None));
+}
+
+#[actix_web::test]
+async fn signaling_requires_a_session() {
+    let ctx = TestContext::new().await;
+    let srv = ctx.start();
+
+    let res = awc::Client::new().ws(srv.url("/signaling")).connect().await;
+    assert!(matches!(
+        res,
+        Err(WsClientError::InvalidResponseStatus(StatusCode::UNAUTHORIZED))
+    ));
+}
+
+#[test]
+fn rate_limiter_allows_a_burst() {
+    let mut limiter = RateLimiter::new(1, 3);
+
+    assert!((0..3).all(|_| limiter.check()));
+    assert!(!limiter.check());
+}
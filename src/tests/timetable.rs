@@ -0,0 +1,226 @@
+// tests/timetable.rs
+use actix_web::{
+    http::{Method, StatusCode},
+    test,
+    web::Data,
+};
+use chrono::Days;
+use serde_json::json;
+use surrealdb::sql::Thing;
+
+use super::{
+    login_as, request,
+    untis::{mock, PASSWORD, SCHOOL, USER},
+    TestContext,
+};
+use crate::{
+    api::response::Response,
+    jobs::timetable::window,
+    models::{
+        model::CRUD,
+        timetable_model::{Lesson, LessonChange, LessonCreate, LessonStatus, Timetable},
+        untis_model::{UntisAccount, UntisCredentials},
+        user_model::User,
+    },
+};
+
+/// A regular lesson of `users:alice` on the 4th of March 2024
+fn lesson(period: i64, start: &str, room: &str) -> LessonCreate {
+    LessonCreate {
+        user: Thing::from(("users", "alice")),
+        period,
+        date: "2024-03-04".to_string(),
+        start: format!("2024-03-04T{start}:00"),
+        end: format!("2024-03-04T{start}:45"),
+        subjects: vec!["Mathematics".to_string()],
+        teachers: vec!["Smi".to_string()],
+        rooms: vec![room.to_string()],
+        klassen: vec!["5a".to_string()],
+        original_teachers: Vec::new(),
+        original_rooms: Vec::new(),
+        status: LessonStatus::Regular,
+        text: None,
+        substitution_text: None,
+        info: None,
+        updated_at: chrono::Utc::now().to_rfc3339(),
+    }
+}
+
+#[actix_web::test]
+async fn snapshots_are_diffed() {
+    let ctx = TestContext::new().await;
+    let db = &ctx.state.surreal.db;
+    let alice = Thing::from(("users", "alice"));
+    let (from, to) = ("2024-03-04", "2024-03-04");
+
+    let first = vec![lesson(1, "08", "101"), lesson(2, "09", "101"), lesson(3, "10", "101")];
+    let changes = Lesson::replace(db, &alice, from, to, first.clone()).await.unwrap();
+    assert_eq!(changes.len(), 3);
+    assert!(changes.iter().all(|c| matches!(c, LessonChange::Added(_))));
+
+    // The same snapshot changes nothing, even fetched later
+    let changes = Lesson::replace(db, &alice, from, to, first).await.unwrap();
+    assert!(changes.is_empty(), "{changes:?}");
+
+    let mut moved = lesson(2, "09", "102");
+    moved.original_rooms = vec!["101".to_string()];
+    let second = vec![lesson(1, "08", "101"), moved, lesson(4, "11", "101")];
+    let changes = Lesson::replace(db, &alice, from, to, second).await.unwrap();
+    assert_eq!(changes.len(), 3);
+    for change in changes {
+        match change {
+            LessonChange::Changed { before, after } => {
+                assert_eq!((before.period, after.period), (2, 2));
+                assert_eq!((before.id, before.rooms), (after.id, vec!["101".to_string()]));
+                assert_eq!(after.rooms, vec!["102".to_string()]);
+            }
+            LessonChange::Added(lesson) => assert_eq!(lesson.period, 4),
+            LessonChange::Removed(lesson) => assert_eq!(lesson.period, 3),
+        }
+    }
+
+    let lessons = Lesson::between(db, &alice, from, to).await.unwrap();
+    let periods: Vec<i64> = lessons.iter().map(|lesson| lesson.period).collect();
+    assert_eq!(periods, vec![1, 2, 4]);
+}
+
+#[actix_web::test]
+async fn timetables_are_served_from_the_cache() {
+    let srv = mock();
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+    let cookie = login_as(&app, "alice@example.com").await;
+
+    let req = request().uri("/timetable").cookie(cookie.clone()).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+    let req = request()
+        .method(Method::PUT)
+        .uri("/untis/account")
+        .cookie(cookie.clone())
+        .set_json(json!({
+            "server": format!("http://{}", srv.addr()),
+            "school": SCHOOL,
+            "username": USER,
+            "password": PASSWORD,
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    // Nothing is cached until the first synchronisation
+    let (first, last) = window(&ctx.state.config.untis).unwrap();
+    let uri = format!("/timetable?from={first}&to={last}");
+    let req = request().uri(&uri).cookie(cookie.clone()).to_request();
+    let res: Response<Timetable> = test::call_and_read_body_json(&app, req).await;
+    let timetable = res.data.unwrap();
+    assert!(timetable.lessons.is_empty() && !timetable.fresh);
+    assert_eq!(timetable.synced_at, None);
+
+    let req = request()
+        .method(Method::POST)
+        .uri(&format!("/timetable/refresh?from={first}&to={last}"))
+        .cookie(cookie.clone())
+        .to_request();
+    let res: Response<Timetable> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(res.message, "Timetable refreshed");
+    let timetable = res.data.unwrap();
+    assert!(timetable.fresh && timetable.synced_at.is_some());
+    let lessons = &timetable.lessons;
+    assert_eq!(lessons.len(), 3);
+    assert_eq!(lessons[0].subjects, vec!["Mathematics"]);
+    assert_eq!(lessons[1].status, LessonStatus::Irregular);
+    assert_eq!(lessons[1].rooms, vec!["102"]);
+    assert_eq!(lessons[1].original_rooms, vec!["101"]);
+    assert_eq!(lessons[2].status, LessonStatus::Cancelled);
+    assert_eq!(lessons[2].date, last.to_string());
+
+    // A second refresh right away does not ask WebUntis
+    let req = request()
+        .method(Method::POST)
+        .uri("/timetable/refresh")
+        .cookie(cookie.clone())
+        .to_request();
+    let res: Response<Timetable> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(res.message, "Timetable was refreshed recently");
+    assert!(res.data.unwrap().fresh);
+
+    // Days outside of the synchronised window are stale
+    let before = first - Days::new(1);
+    let req = request()
+        .uri(&format!("/timetable?from={before}&to={first}"))
+        .cookie(cookie.clone())
+        .to_request();
+    let res: Response<Timetable> = test::call_and_read_body_json(&app, req).await;
+    let timetable = res.data.unwrap();
+    assert_eq!((timetable.lessons.len(), timetable.fresh), (2, false));
+
+    let req = request()
+        .method(Method::DELETE)
+        .uri("/untis/account")
+        .cookie(cookie.clone())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let mut res = ctx.state.surreal.db.query("SELECT VALUE id FROM lesson").await.unwrap();
+    let ids: Vec<Thing> = res.take(0).unwrap();
+    assert!(ids.is_empty());
+}
+
+#[actix_web::test]
+async fn malformed_days_are_rejected() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+    let cookie = login_as(&app, "alice@example.com").await;
+
+    for query in [
+        "from=yesterday",
+        "from=2024-03-04&to=2024-03-03",
+        "from=2024-01-01&to=2024-12-31",
+    ] {
+        let req = request()
+            .uri(&format!("/timetable?{query}"))
+            .cookie(cookie.clone())
+            .to_request();
+        let status = test::call_service(&app, req).await.status();
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{query}");
+    }
+}
+
+#[actix_web::test]
+async fn only_accounts_of_active_users_are_synced() {
+    let srv = mock();
+    let ctx = TestContext::new().await;
+    let db = &ctx.state.surreal.db;
+    let cipher = ctx.state.config.untis.cipher().unwrap();
+    let app = ctx.init().await;
+    let credentials = UntisCredentials {
+        username: USER.to_string(),
+        password: PASSWORD.to_string(),
+    };
+    let server = format!("http://{}", srv.addr());
+    let mut ids = Vec::new();
+    for email in ["alice@example.com", "bob@example.com", "carol@example.com"] {
+        login_as(&app, email).await;
+        let id = User::get_from_email(Data::new(db.clone()), email)
+            .await
+            .unwrap()
+            .unwrap()
+            .id;
+        UntisAccount::link(db, &cipher, &id, &server, SCHOOL, &credentials)
+            .await
+            .unwrap();
+        ids.push(id);
+    }
+    assert_eq!(UntisAccount::all(db).await.unwrap().len(), 3);
+
+    // Bob is deactivated and Carol deleted, their accounts stay linked
+    db.query("UPDATE $id SET is_inactive = true")
+        .bind(("id", &ids[1]))
+        .await
+        .unwrap()
+        .check()
+        .unwrap();
+    User::delete(Data::new(db.clone()), ids[2].clone()).await.unwrap();
+    let accounts = UntisAccount::all(db).await.unwrap();
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(accounts[0].user, ids[0]);
+}
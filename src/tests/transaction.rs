@@ -0,0 +1,121 @@
+// tests/transaction.rs
+use actix_web::web::Data;
+use serde::Deserialize;
+
+use super::TestContext;
+use crate::{
+    models::{
+        model::CRUD,
+        user_model::{User, UserCreate},
+    },
+    prelude::Error,
+    repository::transaction::Transaction,
+};
+
+/// A user with a random peer id and a placeholder password hash
+fn user(email: &str) -> UserCreate {
+    UserCreate {
+        peer_id: uuid::Uuid::new_v4().to_string(),
+        name: "Test User".to_string(),
+        avatar: String::new(),
+        email: email.to_string(),
+        password_hash: "hash".to_string(),
+        creation_date: chrono::Utc::now().to_rfc3339(),
+        is_visible: true,
+        is_inactive: false,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Count {
+    total: u64,
+}
+
+#[actix_web::test]
+async fn commit_returns_typed_results() {
+    let ctx = TestContext::new().await;
+    let db = &ctx.state.surreal.db;
+
+    let mut tx = Transaction::new();
+    let alice = tx.create::<User>(User::TABLE, user("alice@example.com")).unwrap();
+    let bob = tx.create::<User>(User::TABLE, user("bob@example.com")).unwrap();
+    let count = tx.query::<Count>("SELECT count() AS total FROM users GROUP ALL");
+    let mut committed = tx.commit(db).await.unwrap();
+
+    assert_eq!(committed.record(alice).unwrap().email, "alice@example.com");
+    assert_eq!(committed.record(bob).unwrap().version, 1);
+    assert_eq!(committed.rows(count).unwrap()[0].total, 2);
+}
+
+#[actix_web::test]
+async fn failed_statement_rolls_back_the_transaction() {
+    let ctx = TestContext::new().await;
+    let db = &ctx.state.surreal.db;
+
+    let mut tx = Transaction::new();
+    tx.create::<User>(User::TABLE, user("alice@example.com")).unwrap();
+    tx.create::<User>(User::TABLE, user("alice@example.com")).unwrap();
+    let err = tx.commit(db).await.err().unwrap();
+    assert!(matches!(err, Error::Conflict(_)), "{err:?}");
+
+    let mut tx = Transaction::new();
+    tx.create::<User>(User::TABLE, user("bob@example.com")).unwrap();
+    tx.update_merge::<User>(
+        &surrealdb::sql::thing("users:missing").unwrap(),
+        serde_json::json!({ "name": "Nobody" }),
+        None,
+    )
+    .unwrap();
+    let err = tx.commit(db).await.err().unwrap();
+    assert!(matches!(err, Error::NotFound(_)), "{err:?}");
+
+    let page = User::list(Data::new(db.clone()), Default::default())
+        .await
+        .unwrap();
+    assert_eq!(page.total, 0);
+}
+
+#[actix_web::test]
+async fn stale_version_rolls_back_the_transaction() {
+    let ctx = TestContext::new().await;
+    let db = &ctx.state.surreal.db;
+    let alice = User::create(
+        Data::new(db.clone()),
+        User::TABLE.to_string(),
+        user("alice@example.com"),
+    )
+    .await
+    .unwrap();
+
+    let mut tx = Transaction::new();
+    tx.update_merge::<User>(&alice.id, serde_json::json!({ "name": "Alice" }), Some(1))
+        .unwrap();
+    tx.update_merge::<User>(&alice.id, serde_json::json!({ "name": "Mallory" }), Some(1))
+        .unwrap();
+    let err = tx.commit(db).await.err().unwrap();
+    assert!(matches!(err, Error::Conflict(_)), "{err:?}");
+
+    let alice = User::get_from_id(Data::new(db.clone()), alice.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(alice.name, "Test User");
+    assert_eq!(alice.version, 1);
+}
+
+#[test]
+fn merges_cannot_write_protected_fields() {
+    let id = surrealdb::sql::thing("users:alice").unwrap();
+
+    let mut tx = Transaction::new();
+    let err = tx
+        .update_merge::<User>(&id, serde_json::json!({ "email": "mallory@example.com" }), None)
+        .err()
+        .unwrap();
+    assert!(matches!(err, Error::Validation(_)), "{err:?}");
+    let err = tx
+        .update_merge::<User>(&id, serde_json::json!({ "version": 7 }), None)
+        .err()
+        .unwrap();
+    assert!(matches!(err, Error::Validation(_)), "{err:?}");
+}
@@ -0,0 +1,294 @@
+// tests/users.rs
+use actix_web::{
+    http::{header, Method, StatusCode},
+    test,
+    web::Data,
+};
+use serde_json::json;
+use surrealdb::sql::Thing;
+
+use super::{login_as, register, request, TestContext, PASSWORD};
+use crate::{
+    api::response::Response,
+    jobs::purge::purge,
+    models::{
+        calendar_model::CalendarToken,
+        contact_model::Contact,
+        model::CRUD,
+        query::ListQuery,
+        user_model::{PublicProfile, User, UserProfile},
+    },
+};
+
+#[actix_web::test]
+async fn merge_updates_own_profile() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+    let cookie = login_as(&app, "alice@example.com").await;
+
+    let req = request()
+        .method(Method::PATCH)
+        .uri("/users/me")
+        .cookie(cookie.clone())
+        .set_json(json!({ "name": "Alice", "is_visible": false }))
+        .to_request();
+    let res: Response<UserProfile> = test::call_and_read_body_json(&app, req).await;
+    let profile = res.data.unwrap();
+    assert_eq!(profile.name, "Alice");
+    assert!(!profile.is_visible);
+    assert_eq!(profile.email, "alice@example.com");
+
+    let req = request()
+        .method(Method::PATCH)
+        .uri("/users/me")
+        .cookie(cookie)
+        .set_json(json!({ "email": "mallory@example.com" }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn json_patch_rejects_protected_fields() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+    let cookie = login_as(&app, "alice@example.com").await;
+
+    let req = request()
+        .method(Method::PATCH)
+        .uri("/users/me")
+        .cookie(cookie.clone())
+        .insert_header((header::CONTENT_TYPE, "application/json-patch+json"))
+        .set_payload(json!([{ "op": "replace", "path": "/name", "value": "Alice" }]).to_string())
+        .to_request();
+    let res: Response<UserProfile> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(res.data.unwrap().name, "Alice");
+
+    for op in [
+        json!({ "op": "replace", "path": "/is_inactive", "value": true }),
+        json!({ "op": "copy", "from": "/password_hash", "path": "/avatar" }),
+        json!({ "op": "replace", "path": "", "value": {} }),
+    ] {
+        let req = request()
+            .method(Method::PATCH)
+            .uri("/users/me")
+            .cookie(cookie.clone())
+            .insert_header((header::CONTENT_TYPE, "application/json-patch+json"))
+            .set_payload(json!([op]).to_string())
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    // Patches that do not apply or break the schema are the client's fault
+    for op in [
+        json!({ "op": "test", "path": "/name", "value": "Bob" }),
+        json!({ "op": "replace", "path": "/is_visible", "value": "yes" }),
+    ] {
+        let req = request()
+            .method(Method::PATCH)
+            .uri("/users/me")
+            .cookie(cookie.clone())
+            .insert_header((header::CONTENT_TYPE, "application/json-patch+json"))
+            .set_payload(json!([op]).to_string())
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{op}");
+    }
+}
+
+#[actix_web::test]
+async fn invisible_users_are_hidden() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+    let alice = login_as(&app, "alice@example.com").await;
+    let bob = login_as(&app, "bob@example.com").await;
+
+    let req = request().uri("/users/me").cookie(bob.clone()).to_request();
+    let res: Response<UserProfile> = test::call_and_read_body_json(&app, req).await;
+    let bob_uri = format!("/users/{}", res.data.unwrap().id.split_once(':').unwrap().1);
+
+    // Other users get the public profile, without the email
+    let req = request().uri(&bob_uri).cookie(alice.clone()).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(res).await;
+    assert!(body["data"]["peer_id"].is_string());
+    assert!(body["data"].get("email").is_none());
+
+    let req = request()
+        .method(Method::PATCH)
+        .uri("/users/me")
+        .cookie(bob)
+        .set_json(json!({ "is_visible": false }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = request().uri(&bob_uri).cookie(alice.clone()).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+    for (uri, listed) in [("/users", 1), ("/users?is_visible=false", 0)] {
+        let req = request().uri(uri).cookie(alice.clone()).to_request();
+        let page: Response<Vec<PublicProfile>> = test::call_and_read_body_json(&app, req).await;
+        let users = page.data.unwrap();
+        assert_eq!(users.len(), listed, "{uri}");
+        assert!(users.iter().all(|user| user.is_visible));
+    }
+}
+
+#[actix_web::test]
+async fn stale_if_match_is_a_conflict() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+    let cookie = login_as(&app, "alice@example.com").await;
+
+    let req = request().uri("/users/me").cookie(cookie.clone()).to_request();
+    let res = test::call_service(&app, req).await;
+    let etag = res.headers().get(header::ETAG).unwrap().clone();
+    assert_eq!(etag, "\"1\"");
+
+    let merge = |name: &str| {
+        request()
+            .method(Method::PATCH)
+            .uri("/users/me")
+            .cookie(cookie.clone())
+            .insert_header((header::IF_MATCH, etag.clone()))
+            .set_json(json!({ "name": name }))
+            .to_request()
+    };
+
+    let res = test::call_service(&app, merge("Alice")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get(header::ETAG).unwrap(), "\"2\"");
+
+    let res = test::call_service(&app, merge("Mallory")).await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+
+    let req = request().uri("/users/me").cookie(cookie).to_request();
+    let res: Response<UserProfile> = test::call_and_read_body_json(&app, req).await;
+    let profile = res.data.unwrap();
+    assert_eq!(profile.name, "Alice");
+    assert_eq!(profile.version, 2);
+}
+
+#[actix_web::test]
+async fn deleted_account_is_hidden_until_restored() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+    let cookie = login_as(&app, "alice@example.com").await;
+    let bob = login_as(&app, "bob@example.com").await;
+
+    let req = request()
+        .method(Method::DELETE)
+        .uri("/users/me")
+        .cookie(cookie.clone())
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let req = request().uri("/users/me").cookie(cookie).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let req = request().uri("/users").cookie(bob.clone()).to_request();
+    let page: Response<Vec<PublicProfile>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page.data.unwrap().len(), 1);
+
+    // Only internal callers may list deleted accounts
+    let req = request()
+        .uri("/users?include_deleted=true")
+        .cookie(bob)
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let query = ListQuery {
+        include_deleted: true,
+        ..Default::default()
+    };
+    let page = User::list(Data::new(ctx.state.surreal.db.clone()), query).await.unwrap();
+    assert_eq!(page.total, 2);
+
+    let credentials = json!({ "email": "alice@example.com", "password": PASSWORD });
+    let req = request()
+        .method(Method::POST)
+        .uri("/auth/login")
+        .set_json(&credentials)
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let req = request()
+        .method(Method::POST)
+        .uri("/auth/restore")
+        .set_json(&credentials)
+        .to_request();
+    let res: Response<UserProfile> = test::call_and_read_body_json(&app, req).await;
+    assert!(res.data.unwrap().deleted_at.is_none());
+
+    let req = request()
+        .method(Method::POST)
+        .uri("/auth/login")
+        .set_json(&credentials)
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn purge_removes_deleted_accounts_past_retention() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+    let cookie = login_as(&app, "alice@example.com").await;
+
+    let req = request()
+        .method(Method::DELETE)
+        .uri("/users/me")
+        .cookie(cookie)
+        .to_request();
+    test::call_service(&app, req).await;
+
+    let db = &ctx.state.surreal.db;
+    assert_eq!(purge(db, 30).await.unwrap(), 0);
+    assert_eq!(purge(db, 0).await.unwrap(), 1);
+
+    let res = register(&app, "alice@example.com", PASSWORD).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+}
+
+#[actix_web::test]
+async fn purge_removes_the_records_of_purged_accounts() {
+    let ctx = TestContext::new().await;
+    let app = ctx.init().await;
+    let db = &ctx.state.surreal.db;
+    let cookie = login_as(&app, "alice@example.com").await;
+    login_as(&app, "bob@example.com").await;
+    let mut ids = Vec::new();
+    for email in ["alice@example.com", "bob@example.com"] {
+        let user = User::get_from_email(Data::new(db.clone()), email).await.unwrap();
+        ids.push(user.unwrap().id);
+    }
+    let (alice, bob) = (&ids[0], &ids[1]);
+    Contact::request(db, alice, bob).await.unwrap();
+    Contact::accept(db, bob, alice).await.unwrap();
+    CalendarToken::rotate(db, alice).await.unwrap();
+
+    let req = request()
+        .method(Method::DELETE)
+        .uri("/users/me")
+        .cookie(cookie)
+        .to_request();
+    test::call_service(&app, req).await;
+    assert_eq!(purge(db, 0).await.unwrap(), 1);
+
+    let mut res = db
+        .query("SELECT VALUE id FROM contact; SELECT VALUE id FROM calendar_token;")
+        .await
+        .unwrap();
+    let contacts: Vec<Thing> = res.take(0).unwrap();
+    let tokens: Vec<Thing> = res.take(1).unwrap();
+    assert!(contacts.is_empty());
+    assert!(tokens.is_empty());
+    // The other user is kept
+    let bob = User::get_from_id(Data::new(db.clone()), bob.clone()).await.unwrap();
+    assert!(bob.is_some());
+}
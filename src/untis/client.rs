@@ -0,0 +1,385 @@
+// untis/client.rs
+use std::{net::IpAddr, time::Duration};
+
+use chrono::NaiveDate;
+use reqwest::{header, redirect, Url};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use super::types::{to_date, Element, Klasse, Period, Room, Session, Subject, Teacher};
+use crate::{config::UntisConfig, prelude::Error};
+
+/// JSON-RPC error code of a login with a wrong user name or password
+const BAD_CREDENTIALS: i64 = -8504;
+/// JSON-RPC error code of a request without a valid session
+const NOT_AUTHENTICATED: i64 = -8520;
+
+/// A JSON-RPC 2.0 request
+#[derive(Serialize)]
+struct RpcRequest<'a, P> {
+    id: String,
+    method: &'a str,
+    params: P,
+    jsonrpc: &'static str,
+}
+
+/// A JSON-RPC 2.0 response, `result` is `null` if there is an `error`
+#[derive(Deserialize)]
+struct RpcResponse {
+    #[serde(default)]
+    result: Value,
+    #[serde(default)]
+    error: Option<RpcError>,
+}
+
+/// A JSON-RPC 2.0 error
+#[derive(Debug, Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    /// Convert the error of a method to the crate error
+    fn into_error(self, method: &str) -> Error {
+        match self.code {
+            BAD_CREDENTIALS => {
+                Error::Validation("the WebUntis credentials were rejected".to_string())
+            }
+            NOT_AUTHENTICATED => {
+                Error::UntisError(format!("{method} needs a session, authenticate again"))
+            }
+            code => Error::UntisError(format!("{method} failed with {code}, {}", self.message)),
+        }
+    }
+}
+
+/// Check whether a host matches an allowed host pattern
+///
+/// A pattern is either an exact host (`nessa.webuntis.com`) or a wildcard subdomain
+/// (`*.webuntis.com`), which matches any subdomain depth but not the bare domain itself.
+/// Wildcards never match IP addresses or `localhost`.
+///
+/// ## Arguments
+///
+/// * `pattern` - The allowed host pattern
+/// * `host` - The host of the server, lowercase
+/// * `internal` - Whether the host is an IP address or `localhost`
+fn host_matches(pattern: &str, host: &str, internal: bool) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => {
+            let suffix = format!(".{}", domain.to_ascii_lowercase());
+            !internal && host.len() > suffix.len() && host.ends_with(&suffix)
+        }
+        None => pattern.eq_ignore_ascii_case(host),
+    }
+}
+
+/// Returns the base URL of a WebUntis server, if the configuration allows to contact it
+///
+/// Users choose the server of their account, which must not let them reach the internal
+/// network: only HTTPS servers matching `allowed_hosts` are allowed, IP addresses and
+/// `localhost` only if they are listed as such. Plain HTTP needs `allow_http`.
+///
+/// ## Arguments
+///
+/// * `server` - The WebUntis server, HTTPS unless a scheme is given
+/// * `config` - The WebUntis client configuration
+///
+/// ## Errors
+///
+/// * `Error::Validation` - The server is not a valid URL or is not allowed
+fn allowed_server(server: &str, config: &UntisConfig) -> Result<String, Error> {
+    let url = if server.contains("://") {
+        Url::parse(server)
+    } else {
+        Url::parse(&format!("https://{server}"))
+    }
+    .map_err(|_| Error::Validation(format!("the WebUntis server `{server}` is not valid")))?;
+
+    match url.scheme() {
+        "https" => {}
+        "http" if config.allow_http => {}
+        _ => {
+            return Err(Error::Validation(
+                "the WebUntis server must use HTTPS".to_string(),
+            ))
+        }
+    }
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err(Error::Validation(
+            "the WebUntis server must not contain credentials".to_string(),
+        ));
+    }
+    let host = url.host_str().unwrap_or_default();
+    let internal = host.trim_matches(['[', ']']).parse::<IpAddr>().is_ok()
+        || host == "localhost"
+        || host.ends_with(".localhost");
+    if !config
+        .allowed_hosts
+        .iter()
+        .any(|pattern| host_matches(pattern, host, internal))
+    {
+        return Err(Error::Validation(format!(
+            "the WebUntis server `{host}` is not allowed"
+        )));
+    }
+
+    Ok(url.origin().ascii_serialization())
+}
+
+/// Client of the WebUntis JSON-RPC API of a school
+///
+/// A client holds at most one session, opened by `authenticate` and closed by `logout`.
+/// Sessions expire after some inactivity, the methods then fail until the client
+/// authenticates again.
+///
+/// ## Fields
+///
+/// * `http` is the HTTP client
+/// * `server` is the base URL of the WebUntis server
+/// * `school` is the login name of the school
+/// * `client_name` identifies the application to the server
+/// * `session` is the current session, if authenticated
+pub struct UntisClient {
+    http: reqwest::Client,
+    server: String,
+    school: String,
+    client_name: String,
+    session: Option<Session>,
+}
+
+impl UntisClient {
+    /// Create a client without session
+    ///
+    /// ## Arguments
+    ///
+    /// * `server` - The WebUntis server, e.g. `nessa.webuntis.com`, HTTPS unless a scheme is given
+    /// * `school` - The login name of the school
+    /// * `config` - The WebUntis client configuration
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Validation` - The server or school is empty, or the server is not allowed
+    /// * `Error::UntisError` - The HTTP client cannot be built
+    pub fn new(server: &str, school: &str, config: &UntisConfig) -> Result<Self, Error> {
+        let server = server.trim().trim_end_matches('/');
+        if server.is_empty() || school.trim().is_empty() {
+            return Err(Error::Validation(
+                "the WebUntis server and school must not be empty".to_string(),
+            ));
+        }
+        let server = allowed_server(server, config)?;
+
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .redirect(redirect::Policy::none())
+            .build()
+            .map_err(|e| Error::UntisError(format!("cannot build the HTTP client, {e}")))?;
+
+        Ok(Self {
+            http,
+            server,
+            school: school.trim().to_string(),
+            client_name: config.client_name.clone(),
+            session: None,
+        })
+    }
+
+    /// Returns the current session
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::UntisError` - The client is not authenticated
+    fn require_session(&self) -> Result<&Session, Error> {
+        self.session
+            .as_ref()
+            .ok_or_else(|| Error::UntisError("the client is not authenticated".to_string()))
+    }
+
+    /// Call a JSON-RPC method, with the session cookie if there is a session
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::UntisError` - The server cannot be reached, or answers with an error or
+    ///   a response that does not deserialize
+    async fn call<P, R>(&self, method: &str, params: P) -> Result<R, Error>
+    where
+        P: Serialize,
+        R: DeserializeOwned,
+    {
+        let mut req = self
+            .http
+            .post(format!("{}/WebUntis/jsonrpc.do", self.server))
+            .query(&[("school", &self.school)])
+            .json(&RpcRequest {
+                id: Uuid::new_v4().to_string(),
+                method,
+                params,
+                jsonrpc: "2.0",
+            });
+        if let Some(session) = &self.session {
+            req = req.header(header::COOKIE, format!("JSESSIONID={}", session.session_id));
+        }
+
+        let res = req
+            .send()
+            .await
+            .map_err(|e| Error::UntisError(format!("{method} failed, {e}")))?;
+        let status = res.status();
+        if !status.is_success() {
+            return Err(Error::UntisError(format!("{method} returned {status}")));
+        }
+        let body: RpcResponse = res
+            .json()
+            .await
+            .map_err(|e| Error::UntisError(format!("{method} returned invalid JSON, {e}")))?;
+
+        if let Some(error) = body.error {
+            return Err(error.into_error(method));
+        }
+        serde_json::from_value(body.result)
+            .map_err(|e| Error::UntisError(format!("{method} returned an invalid result, {e}")))
+    }
+
+    /// Log in, replacing the current session
+    ///
+    /// ## Arguments
+    ///
+    /// * `user` - The WebUntis user name
+    /// * `password` - The WebUntis password
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<&Session, Error>` - The new session
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Validation` - The credentials were rejected
+    /// * `Error::UntisError` - The login failed
+    pub async fn authenticate(&mut self, user: &str, password: &str) -> Result<&Session, Error> {
+        self.session = None;
+        let params = json!({ "user": user, "password": password, "client": self.client_name });
+        let session: Session = self.call("authenticate", params).await?;
+
+        Ok(self.session.insert(session))
+    }
+
+    /// Get the timetable of an element
+    ///
+    /// ## Arguments
+    ///
+    /// * `element` - The class, teacher, subject, room or student
+    /// * `from` - The first day
+    /// * `to` - The last day, included
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<Vec<Period>, Error>` - The periods, in no particular order
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Validation` - `to` is before `from`
+    /// * `Error::UntisError` - The client is not authenticated or the request failed
+    pub async fn timetable(
+        &self,
+        element: Element,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<Period>, Error> {
+        if to < from {
+            return Err(Error::Validation(
+                "the timetable must end after it starts".to_string(),
+            ));
+        }
+        self.require_session()?;
+
+        let params = json!({
+            "id": element.id,
+            "type": element.kind,
+            "startDate": to_date(from),
+            "endDate": to_date(to),
+        });
+
+        self.call("getTimetable", params).await
+    }
+
+    /// Get the timetable of the logged in student or teacher
+    ///
+    /// ## Arguments
+    ///
+    /// * `from` - The first day
+    /// * `to` - The last day, included
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Validation` - `to` is before `from`, or the account has no timetable
+    /// * `Error::UntisError` - The client is not authenticated or the request failed
+    pub async fn own_timetable(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<Period>, Error> {
+        let element = self.require_session()?.person().ok_or_else(|| {
+            Error::Validation("the WebUntis account is not a student or teacher".to_string())
+        })?;
+
+        self.timetable(element, from, to).await
+    }
+
+    /// Get the subjects of the school
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::UntisError` - The client is not authenticated or the request failed
+    pub async fn subjects(&self) -> Result<Vec<Subject>, Error> {
+        self.require_session()?;
+        self.call("getSubjects", json!({})).await
+    }
+
+    /// Get the teachers of the school
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::UntisError` - The client is not authenticated or the request failed
+    pub async fn teachers(&self) -> Result<Vec<Teacher>, Error> {
+        self.require_session()?;
+        self.call("getTeachers", json!({})).await
+    }
+
+    /// Get the rooms of the school
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::UntisError` - The client is not authenticated or the request failed
+    pub async fn rooms(&self) -> Result<Vec<Room>, Error> {
+        self.require_session()?;
+        self.call("getRooms", json!({})).await
+    }
+
+    /// Get the classes of the school
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::UntisError` - The client is not authenticated or the request failed
+    pub async fn klassen(&self) -> Result<Vec<Klasse>, Error> {
+        self.require_session()?;
+        self.call("getKlassen", json!({})).await
+    }
+
+    /// Log out, the session is dropped even if the request fails
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::UntisError` - The request failed
+    pub async fn logout(&mut self) -> Result<(), Error> {
+        if self.session.is_none() {
+            return Ok(());
+        }
+        let result = self.call("logout", json!({})).await;
+        self.session = None;
+
+        result
+    }
+}
@@ -0,0 +1,3 @@
+// untis/mod.rs
+pub mod client;
+pub mod types;
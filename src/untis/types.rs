@@ -0,0 +1,249 @@
+// untis/types.rs
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+/// Type of a timetable element
+///
+/// ## Variants
+///
+/// * `Klasse` is a class
+/// * `Teacher` is a teacher
+/// * `Subject` is a subject
+/// * `Room` is a room
+/// * `Student` is a student
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "u8", into = "u8")]
+pub enum ElementType {
+    Klasse,
+    Teacher,
+    Subject,
+    Room,
+    Student,
+}
+
+impl From<ElementType> for u8 {
+    fn from(kind: ElementType) -> Self {
+        match kind {
+            ElementType::Klasse => 1,
+            ElementType::Teacher => 2,
+            ElementType::Subject => 3,
+            ElementType::Room => 4,
+            ElementType::Student => 5,
+        }
+    }
+}
+
+impl TryFrom<u8> for ElementType {
+    type Error = String;
+
+    fn try_from(kind: u8) -> Result<Self, Self::Error> {
+        match kind {
+            1 => Ok(ElementType::Klasse),
+            2 => Ok(ElementType::Teacher),
+            3 => Ok(ElementType::Subject),
+            4 => Ok(ElementType::Room),
+            5 => Ok(ElementType::Student),
+            other => Err(format!("unknown element type {other}")),
+        }
+    }
+}
+
+/// An element whose timetable can be fetched
+///
+/// ## Fields
+///
+/// * `id` is the id of the element
+/// * `type` is the type of the element
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Element {
+    pub id: i64,
+    #[serde(rename = "type")]
+    pub kind: ElementType,
+}
+
+/// An authenticated WebUntis session, returned by `authenticate`
+///
+/// ## Fields
+///
+/// * `session_id` is sent back as the `JSESSIONID` cookie
+/// * `person_type` is the type of the logged in person, if they are a teacher or student
+/// * `person_id` is the id of the logged in person
+/// * `klasse_id` is the id of the class of a student
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub session_id: String,
+    #[serde(default)]
+    pub person_type: Option<ElementType>,
+    #[serde(default)]
+    pub person_id: Option<i64>,
+    #[serde(default)]
+    pub klasse_id: Option<i64>,
+}
+
+impl Session {
+    /// Returns the element of the logged in person, whose timetable is their own
+    pub fn person(&self) -> Option<Element> {
+        match (self.person_type, self.person_id) {
+            (Some(kind), Some(id)) if id > 0 => Some(Element { id, kind }),
+            _ => None,
+        }
+    }
+}
+
+/// A subject
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Subject {
+    pub id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub long_name: String,
+    #[serde(default)]
+    pub alternate_name: Option<String>,
+    #[serde(default = "active")]
+    pub active: bool,
+}
+
+/// A teacher
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Teacher {
+    pub id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub fore_name: String,
+    #[serde(default)]
+    pub long_name: String,
+    #[serde(default = "active")]
+    pub active: bool,
+}
+
+/// A room
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Room {
+    pub id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub long_name: String,
+    #[serde(default)]
+    pub building: Option<String>,
+    #[serde(default = "active")]
+    pub active: bool,
+}
+
+/// A class
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Klasse {
+    pub id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub long_name: String,
+    #[serde(default = "active")]
+    pub active: bool,
+}
+
+/// Elements are active unless WebUntis says otherwise
+fn active() -> bool {
+    true
+}
+
+/// A reference from a period to one of its elements
+///
+/// ## Fields
+///
+/// * `id` is the id of the element
+/// * `orgid` is the id of the element originally planned, e.g. the teacher being substituted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ElementRef {
+    pub id: i64,
+    #[serde(default)]
+    pub orgid: Option<i64>,
+}
+
+/// Deviation of a period from the regular timetable
+///
+/// ## Variants
+///
+/// * `Cancelled` periods do not take place
+/// * `Irregular` periods are substitutions or additional periods
+/// * `Regular` periods follow the timetable, WebUntis sends an empty or unknown code for them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PeriodCode {
+    Cancelled,
+    Irregular,
+    #[serde(other)]
+    Regular,
+}
+
+/// A period of a timetable
+///
+/// ## Fields
+///
+/// * `id` is the id of the period
+/// * `date` is the date, written `yyyymmdd`
+/// * `start_time` is the start time, written `hhmm`
+/// * `end_time` is the end time, written `hhmm`
+/// * `klassen` are the classes
+/// * `teachers` are the teachers
+/// * `subjects` are the subjects
+/// * `rooms` are the rooms
+/// * `code` is the deviation from the regular timetable, if any
+/// * `lesson_text` is the text of the lesson
+/// * `substitution_text` is the text of the substitution
+/// * `info` is additional information
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Period {
+    pub id: i64,
+    pub date: u32,
+    pub start_time: u32,
+    pub end_time: u32,
+    #[serde(default, rename = "kl")]
+    pub klassen: Vec<ElementRef>,
+    #[serde(default, rename = "te")]
+    pub teachers: Vec<ElementRef>,
+    #[serde(default, rename = "su")]
+    pub subjects: Vec<ElementRef>,
+    #[serde(default, rename = "ro")]
+    pub rooms: Vec<ElementRef>,
+    #[serde(default)]
+    pub code: Option<PeriodCode>,
+    #[serde(default, rename = "lstext")]
+    pub lesson_text: Option<String>,
+    #[serde(default, rename = "substText")]
+    pub substitution_text: Option<String>,
+    #[serde(default)]
+    pub info: Option<String>,
+}
+
+impl Period {
+    /// Returns the start of the period, `None` if WebUntis sent an invalid date or time
+    pub fn start(&self) -> Option<NaiveDateTime> {
+        Some(from_date(self.date)?.and_time(from_time(self.start_time)?))
+    }
+
+    /// Returns the end of the period, `None` if WebUntis sent an invalid date or time
+    pub fn end(&self) -> Option<NaiveDateTime> {
+        Some(from_date(self.date)?.and_time(from_time(self.end_time)?))
+    }
+}
+
+/// Write a date the way WebUntis does, `yyyymmdd`
+pub fn to_date(date: NaiveDate) -> u32 {
+    // School years are never before year 0
+    date.year() as u32 * 10000 + date.month() * 100 + date.day()
+}
+
+/// Read a WebUntis date, `yyyymmdd`
+pub fn from_date(date: u32) -> Option<NaiveDate> {
+    NaiveDate::from_ymd_opt((date / 10000) as i32, date / 100 % 100, date % 100)
+}
+
+/// Read a WebUntis time, `hhmm`
+pub fn from_time(time: u32) -> Option<NaiveTime> {
+    NaiveTime::from_hms_opt(time / 100, time % 100, 0)
+}
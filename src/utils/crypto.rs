@@ -0,0 +1,124 @@
+// utils/crypto.rs
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Nonce,
+};
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use rand_core::RngCore;
+use sha2::{Digest, Sha256};
+
+use crate::prelude::Error;
+
+/// Length of an encryption key in bytes
+pub const KEY_LENGTH: usize = 32;
+/// Length of an AES-GCM nonce in bytes
+const NONCE_LENGTH: usize = 12;
+/// Length of a random token in bytes
+const TOKEN_LENGTH: usize = 32;
+
+/// Authenticated encryption of the secrets stored in the database, with AES-256-GCM
+///
+/// Secrets are sealed with a random nonce and bound to a context, e.g. the id of their
+/// owner, so a sealed secret copied to another record does not open.
+pub struct Cipher(Aes256Gcm);
+
+impl Cipher {
+    /// Create a cipher from a key
+    ///
+    /// ## Arguments
+    ///
+    /// * `key` - The base64 encoded key of `KEY_LENGTH` bytes
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Config` - The key is not base64 or has the wrong length
+    pub fn new(key: &str) -> Result<Self, Error> {
+        let key = STANDARD
+            .decode(key.trim())
+            .map_err(|_| Error::Config("encryption key must be base64 encoded".to_string()))?;
+        if key.len() != KEY_LENGTH {
+            return Err(Error::Config(format!(
+                "encryption key must be {KEY_LENGTH} bytes long"
+            )));
+        }
+
+        // Safety: the length was checked above
+        Ok(Self(Aes256Gcm::new_from_slice(&key).unwrap()))
+    }
+
+    /// Seal a secret
+    ///
+    /// ## Arguments
+    ///
+    /// * `secret` - The secret
+    /// * `context` - The context the secret is bound to
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<String, Error>` - The base64 encoded nonce and ciphertext
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Encryption` - The secret cannot be encrypted
+    pub fn seal(&self, secret: &[u8], context: &str) -> Result<String, Error> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: secret,
+            aad: context.as_bytes(),
+        };
+        let ciphertext = self
+            .0
+            .encrypt(&nonce, payload)
+            .map_err(|_| Error::Encryption("cannot encrypt the secret".to_string()))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+
+        Ok(STANDARD.encode(sealed))
+    }
+
+    /// Open a sealed secret
+    ///
+    /// ## Arguments
+    ///
+    /// * `sealed` - The sealed secret
+    /// * `context` - The context the secret was bound to
+    ///
+    /// ## Errors
+    ///
+    /// * `Error::Encryption` - The secret is malformed, was sealed with another key or
+    ///   another context, or was tampered with
+    pub fn open(&self, sealed: &str, context: &str) -> Result<Vec<u8>, Error> {
+        let sealed = STANDARD
+            .decode(sealed)
+            .map_err(|_| Error::Encryption("the sealed secret is not base64".to_string()))?;
+        if sealed.len() < NONCE_LENGTH {
+            return Err(Error::Encryption("the sealed secret is too short".to_string()));
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: context.as_bytes(),
+        };
+        self.0
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| Error::Encryption("cannot decrypt the secret".to_string()))
+    }
+}
+
+/// Generate a random token, safe to use in URLs
+pub fn random_token() -> String {
+    let mut token = [0u8; TOKEN_LENGTH];
+    rand_core::OsRng.fill_bytes(&mut token);
+
+    URL_SAFE_NO_PAD.encode(token)
+}
+
+/// Returns the SHA-256 hash of a token, the form it is stored in
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
@@ -0,0 +1,121 @@
+// utils/ics.rs
+
+/// Identifier of the product generating the calendars
+const PRODID: &str = "-//backend//Timetable//EN";
+/// Maximum length of a content line in octets, without the line break
+const LINE_LENGTH: usize = 75;
+
+/// Event of an iCalendar (RFC 5545) calendar
+///
+/// ## Fields
+///
+/// * `UID` is the identifier of the event, stable across calendars so clients update it
+/// * `Stamp` is the last modification of the event, UTC, `yyyymmddThhmmssZ`
+/// * `Sequence` is the revision of the event, greater after each change
+/// * `Start` is the start of the event, UTC, `yyyymmddThhmmssZ`
+/// * `End` is the end of the event, UTC, `yyyymmddThhmmssZ`
+/// * `Summary` is the title of the event
+/// * `Location` is the place of the event, if any
+/// * `Description` is the description of the event, if any
+/// * `Cancelled` is whether the event was cancelled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub uid: String,
+    pub stamp: String,
+    pub sequence: u32,
+    pub start: String,
+    pub end: String,
+    pub summary: String,
+    pub location: Option<String>,
+    pub description: Option<String>,
+    pub cancelled: bool,
+}
+
+/// Escape a text value, RFC 5545 section 3.3.11
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Fold a content line longer than 75 octets, RFC 5545 section 3.1
+///
+/// ## Arguments
+///
+/// * `line` - The content line, without its line break
+///
+/// ## Returns
+///
+/// * `String` - The folded line, each part ending with `\r\n`
+pub fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    // Continuation lines start with a space, which counts towards their length
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > LINE_LENGTH {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+
+    folded
+}
+
+/// Render a calendar of events
+///
+/// ## Arguments
+///
+/// * `name` - The name of the calendar shown by clients
+/// * `timezone` - The IANA time zone clients show the events in, e.g. `Europe/Berlin`
+/// * `events` - The events
+///
+/// ## Returns
+///
+/// * `String` - The `text/calendar` document
+pub fn calendar(name: &str, timezone: &str, events: &[Event]) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{PRODID}"),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!("X-WR-CALNAME:{}", escape(name)),
+        format!("X-WR-TIMEZONE:{timezone}"),
+    ];
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}", event.uid));
+        lines.push(format!("DTSTAMP:{}", event.stamp));
+        lines.push(format!("LAST-MODIFIED:{}", event.stamp));
+        lines.push(format!("SEQUENCE:{}", event.sequence));
+        lines.push(format!("DTSTART:{}", event.start));
+        lines.push(format!("DTEND:{}", event.end));
+        lines.push(format!("SUMMARY:{}", escape(&event.summary)));
+        if let Some(location) = &event.location {
+            lines.push(format!("LOCATION:{}", escape(location)));
+        }
+        if let Some(description) = &event.description {
+            lines.push(format!("DESCRIPTION:{}", escape(description)));
+        }
+        let status = if event.cancelled { "CANCELLED" } else { "CONFIRMED" };
+        lines.push(format!("STATUS:{status}"));
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|line| fold(line)).collect()
+}
@@ -1,2 +1,3 @@
-// utils/mod.rs
+pub mod crypto;
 pub mod env;
+pub mod ics;